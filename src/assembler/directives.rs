@@ -0,0 +1,481 @@
+//! Parses assembler directives (lines starting with '.')
+
+use crate::assembler::error::AssemblerError;
+use crate::assembler::macros::{parse_macro_header, MacroParam};
+use crate::assembler::parser::{is_symbol_name, split_operands};
+use crate::assembler::section::SectionKind;
+use crate::assembler::symbols::Binding;
+
+#[derive(Debug, PartialEq)]
+pub enum Directive {
+    // .text, .data, .rodata, .bss, .section name[, "flags"[, @type]]
+    Section { name: String, kind: SectionKind },
+
+    // .byte, .half, .word, .dword and their aliases
+    Data { size: u32, values: Vec<String> },
+
+    // .ascii, .asciz, .string
+    Ascii { bytes: Vec<u8> },
+
+    // .align, .p2align (alignment is a power of two) and .balign (alignment in bytes)
+    Align {
+        alignment: String,
+        power_of_two: bool,
+        fill: Option<String>,
+        max_skip: Option<String>
+    },
+
+    // .equ/.set (redefinable) and .eqv
+    Equ { name: String, value: String, redefinable: bool },
+
+    // .skip, .space, .zero and .fill: repeat copies of a size-byte value
+    Fill {
+        repeat: String,
+        size: Option<String>,
+        value: Option<String>
+    },
+
+    // .macro name params: starts a definition that runs until the matching .endm
+    Macro { name: String, params: Vec<MacroParam> },
+    Endm,
+
+    // .purgem name
+    Purgem { name: String },
+
+    // .if and its variants, .elseif, .else, .endif
+    If { condition: Condition },
+    ElseIf { expr: String },
+    Else,
+    EndIf,
+
+    // .include "file"
+    Include { path: String },
+
+    // .incbin "file"[, offset[, length]]
+    Incbin {
+        path: String,
+        offset: Option<String>,
+        length: Option<String>
+    },
+
+    // .rept count ... .endr
+    Rept { count: String },
+
+    // .irp param, values... and .irpc param, chars: the body runs once per value with \param substituted
+    Repeat { param: String, values: Vec<String> },
+    Endr,
+
+    // .globl/.global, .local and .weak: symbol binding in object files
+    Binding { binding: Binding, names: Vec<String> }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Condition {
+    NonZero(String),    // .if expr, .ifne expr
+    Zero(String),       // .ifeq expr
+    Defined(String),    // .ifdef symbol
+    Undefined(String),  // .ifndef symbol, .ifnotdef symbol
+    Blank(String),      // .ifb text (usually a macro argument)
+    NotBlank(String)    // .ifnb text
+}
+
+impl Directive {
+    pub fn parse(line: &str) -> Result<Self, AssemblerError> {
+        let (name, args) = match line.find(char::is_whitespace) {
+            Some(idx) => (&line[..idx], line[idx..].trim()),
+            None => (line, "")
+        };
+        // Macro parameters have their own syntax
+        if name == ".macro" {
+            let (name, params) = parse_macro_header(args)?;
+            return Ok(Self::Macro { name, params });
+        }
+
+        // The text checked by .ifb/.ifnb is taken as-is
+        if name == ".ifb" || name == ".ifnb" {
+            let text = args.to_string();
+            let condition = if name == ".ifb" { Condition::Blank(text) } else { Condition::NotBlank(text) };
+
+            return Ok(Self::If { condition });
+        }
+
+        let args = split_operands(args);
+
+        match name {
+            ".text" | ".data" | ".rodata" | ".bss" => {
+                check_args(name, &args, 0)?;
+
+                Ok(Self::Section {
+                    name: name.to_string(),
+                    kind: SectionKind::from_name(name)
+                })
+            }
+
+            ".section" => {
+                if args.is_empty() || args.len() > 3 {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Expected a section name and optional flags for .section but received {} arguments",
+                        args.len()
+                    )));
+                }
+
+                let section = args[0].to_string();
+                let kind = match args.get(1) {
+                    Some(flags) => SectionKind::from_flags(flags.trim_matches('"'), args.get(2).copied()),
+                    None => SectionKind::from_name(&section)
+                };
+
+                Ok(Self::Section { name: section, kind })
+            }
+
+            ".byte" | ".half" | ".short" | ".2byte" | ".word" | ".long" | ".4byte"
+            | ".dword" | ".quad" | ".8byte" => {
+                let size = match name {
+                    ".byte" => 1,
+                    ".half" | ".short" | ".2byte" => 2,
+                    ".word" | ".long" | ".4byte" => 4,
+                    _ => 8
+                };
+
+                if args.iter().any(|arg| arg.is_empty()) {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Missing value in {} list",
+                        name
+                    )));
+                }
+
+                Ok(Self::Data {
+                    size,
+                    values: args.iter().map(|arg| arg.to_string()).collect()
+                })
+            }
+
+            ".ascii" | ".asciz" | ".string" => {
+                if args.is_empty() {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Expected at least one string for {}",
+                        name
+                    )));
+                }
+
+                let mut bytes = Vec::new();
+
+                for arg in args {
+                    bytes.extend(parse_string(arg)?);
+
+                    // .asciz and .string terminate every string with a NUL byte
+                    if name != ".ascii" {
+                        bytes.push(0);
+                    }
+                }
+
+                Ok(Self::Ascii { bytes })
+            }
+
+            ".equ" | ".set" | ".eqv" => {
+                check_args(name, &args, 2)?;
+
+                if !is_symbol_name(args[0]) || args[1].is_empty() {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Expected a symbol name and a value for {}",
+                        name
+                    )));
+                }
+
+                Ok(Self::Equ {
+                    name: args[0].to_string(),
+                    value: args[1].to_string(),
+                    redefinable: name != ".eqv"
+                })
+            }
+
+            ".align" | ".p2align" | ".balign" => {
+                check_args_range(name, &args, 1, 3)?;
+
+                Ok(Self::Align {
+                    alignment: args[0].to_string(),
+                    power_of_two: name != ".balign",
+                    fill: optional_arg(&args, 1),
+                    max_skip: optional_arg(&args, 2)
+                })
+            }
+
+            ".skip" | ".space" => {
+                check_args_range(name, &args, 1, 2)?;
+
+                Ok(Self::Fill {
+                    repeat: args[0].to_string(),
+                    size: None,
+                    value: optional_arg(&args, 1)
+                })
+            }
+
+            ".zero" => {
+                check_args(name, &args, 1)?;
+
+                Ok(Self::Fill {
+                    repeat: args[0].to_string(),
+                    size: None,
+                    value: None
+                })
+            }
+
+            ".fill" => {
+                check_args_range(name, &args, 1, 3)?;
+
+                Ok(Self::Fill {
+                    repeat: args[0].to_string(),
+                    size: optional_arg(&args, 1),
+                    value: optional_arg(&args, 2)
+                })
+            }
+
+            ".endm" => {
+                check_args(name, &args, 0)?;
+                Ok(Self::Endm)
+            }
+
+            ".if" | ".ifne" | ".ifeq" | ".elseif" => {
+                check_args(name, &args, 1)?;
+                let expr = args[0].to_string();
+
+                Ok(match name {
+                    ".ifeq" => Self::If { condition: Condition::Zero(expr) },
+                    ".elseif" => Self::ElseIf { expr },
+                    _ => Self::If { condition: Condition::NonZero(expr) }
+                })
+            }
+
+            ".ifdef" | ".ifndef" | ".ifnotdef" => {
+                check_args(name, &args, 1)?;
+                let symbol = args[0].to_string();
+
+                Ok(Self::If {
+                    condition: match name {
+                        ".ifdef" => Condition::Defined(symbol),
+                        _ => Condition::Undefined(symbol)
+                    }
+                })
+            }
+
+            ".else" => {
+                check_args(name, &args, 0)?;
+                Ok(Self::Else)
+            }
+
+            ".endif" => {
+                check_args(name, &args, 0)?;
+                Ok(Self::EndIf)
+            }
+
+            ".include" => {
+                check_args(name, &args, 1)?;
+
+                Ok(Self::Include { path: parse_path(args[0])? })
+            }
+
+            ".incbin" => {
+                check_args_range(name, &args, 1, 3)?;
+
+                Ok(Self::Incbin {
+                    path: parse_path(args[0])?,
+                    offset: optional_arg(&args, 1),
+                    length: optional_arg(&args, 2)
+                })
+            }
+
+            ".rept" => {
+                check_args(name, &args, 1)?;
+                Ok(Self::Rept { count: args[0].to_string() })
+            }
+
+            ".irp" | ".irpc" => {
+                if args.is_empty() || !is_symbol_name(args[0]) {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Expected a parameter name for {}",
+                        name
+                    )));
+                }
+
+                let param = args[0].to_string();
+
+                // .irpc iterates over the characters of its single argument
+                let values: Vec<String> = if name == ".irpc" {
+                    check_args_range(name, &args, 1, 2)?;
+                    args.get(1).map_or(vec![String::new()], |chars| {
+                        chars.chars().map(|c| c.to_string()).collect()
+                    })
+                } else if args.len() == 1 {
+                    vec![String::new()]
+                } else {
+                    args[1..].iter().map(|arg| arg.to_string()).collect()
+                };
+
+                Ok(Self::Repeat { param, values })
+            }
+
+            ".endr" => {
+                check_args(name, &args, 0)?;
+                Ok(Self::Endr)
+            }
+
+            ".purgem" => {
+                check_args(name, &args, 1)?;
+
+                Ok(Self::Purgem { name: args[0].to_string() })
+            }
+
+            ".globl" | ".global" | ".local" | ".weak" => {
+                if args.is_empty() || !args.iter().all(|arg| is_symbol_name(arg)) {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Expected symbol names for {}",
+                        name
+                    )));
+                }
+
+                let binding = match name {
+                    ".local" => Binding::Local,
+                    ".weak" => Binding::Weak,
+                    _ => Binding::Global
+                };
+
+                Ok(Self::Binding {
+                    binding,
+                    names: args.iter().map(|arg| arg.to_string()).collect()
+                })
+            }
+
+            _ => Err(AssemblerError::InvalidDirective(format!("Unknown directive: {}", name)))
+        }
+    }
+}
+
+// Validate the number of directive arguments
+fn check_args(name: &str, args: &[&str], expected_len: usize) -> Result<(), AssemblerError> {
+    if args.len() != expected_len {
+        return Err(AssemblerError::InvalidDirective(format!(
+            "Expected {} arguments for {} but received {}",
+            expected_len,
+            name,
+            args.len()
+        )));
+    }
+
+    Ok(())
+}
+
+// Validate a directive with optional trailing arguments
+fn check_args_range(name: &str, args: &[&str], min: usize, max: usize) -> Result<(), AssemblerError> {
+    if args.len() < min || args.len() > max {
+        return Err(AssemblerError::InvalidDirective(format!(
+            "Expected {} to {} arguments for {} but received {}",
+            min,
+            max,
+            name,
+            args.len()
+        )));
+    }
+
+    Ok(())
+}
+
+// Optional arguments may be omitted entirely or left empty (".p2align 2,,4")
+fn optional_arg(args: &[&str], idx: usize) -> Option<String> {
+    args.get(idx)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| arg.to_string())
+}
+
+// File names are quoted strings
+fn parse_path(literal: &str) -> Result<String, AssemblerError> {
+    String::from_utf8(parse_string(literal)?).map_err(|_| {
+        AssemblerError::InvalidDirective(format!("Invalid file name: {}", literal))
+    })
+}
+
+// Parse a double-quoted string literal with C-style escapes into bytes
+pub fn parse_string(literal: &str) -> Result<Vec<u8>, AssemblerError> {
+    let inner = literal
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| AssemblerError::InvalidOperand(format!("Invalid string: {}", literal)))?;
+
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        let escape = chars.next().ok_or_else(|| {
+            AssemblerError::InvalidOperand(format!("Unterminated escape sequence: {}", literal))
+        })?;
+
+        let byte = match escape {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            'a' => 0x07,
+            'b' => 0x08,
+            'f' => 0x0C,
+            'v' => 0x0B,
+            'e' => 0x1B,
+            '\\' => b'\\',
+            '"' => b'"',
+            '\'' => b'\'',
+
+            // Hexadecimal escape: \x41
+            'x' => {
+                let mut value: u32 = 0;
+                let mut digits = 0;
+
+                while let Some(d) = chars.peek().and_then(|d| d.to_digit(16)) {
+                    value = (value << 4) | d;
+                    digits += 1;
+                    chars.next();
+                }
+
+                if digits == 0 {
+                    return Err(AssemblerError::InvalidOperand(format!(
+                        "Invalid hexadecimal escape: {}",
+                        literal
+                    )));
+                }
+
+                value as u8
+            }
+
+            // Octal escape: \0, \101 (up to 3 digits)
+            '0'..='7' => {
+                let mut value = escape.to_digit(8).unwrap();
+
+                for _ in 0..2 {
+                    match chars.peek().and_then(|d| d.to_digit(8)) {
+                        Some(d) => {
+                            value = (value << 3) | d;
+                            chars.next();
+                        }
+                        None => break
+                    }
+                }
+
+                value as u8
+            }
+
+            _ => {
+                return Err(AssemblerError::InvalidOperand(format!(
+                    "Unknown escape sequence \\{} in {}",
+                    escape,
+                    literal
+                )));
+            }
+        };
+
+        bytes.push(byte);
+    }
+
+    Ok(bytes)
+}
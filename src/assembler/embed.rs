@@ -0,0 +1,82 @@
+//! Generates C headers and Rust source that embed an assembled image as an array of words
+//!
+//! The image is split into little-endian 32-bit words, the last one padded with zeros.
+//! Selected labels are exported next to the array as constants holding their addresses.
+
+use std::fmt::Write;
+
+// Words per line of the array
+const LINE_WORDS: usize = 4;
+
+// A header with `static const uint32_t name[]`, a NAME_LEN macro with its length in words
+// and a NAME_LABEL macro for every exported label
+pub fn generate_c_header(image: &[u8], name: &str, labels: &[(&str, u32)]) -> String {
+    let name = identifier(name);
+    let upper = name.to_uppercase();
+    let words = words(image);
+    let mut header = String::new();
+
+    writeln!(header, "#ifndef {}_H", upper).unwrap();
+    writeln!(header, "#define {}_H\n", upper).unwrap();
+    writeln!(header, "#include <stdint.h>\n").unwrap();
+    writeln!(header, "#define {}_LEN {}", upper, words.len()).unwrap();
+
+    for (label, address) in labels {
+        writeln!(header, "#define {}_{} 0x{:08x}u", upper, identifier(label).to_uppercase(), address).unwrap();
+    }
+
+    writeln!(header, "\nstatic const uint32_t {}[{}_LEN] = {{", name, upper).unwrap();
+    write_words(&mut header, &words);
+    header.push_str("};\n\n");
+    writeln!(header, "#endif /* {}_H */", upper).unwrap();
+    header
+}
+
+// A `pub const NAME: [u32; N]` and a `pub const NAME_LABEL: u32` for every exported label
+pub fn generate_rust_array(image: &[u8], name: &str, labels: &[(&str, u32)]) -> String {
+    let name = identifier(name).to_uppercase();
+    let words = words(image);
+    let mut source = String::new();
+
+    for (label, address) in labels {
+        writeln!(source, "pub const {}_{}: u32 = 0x{:08x};", name, identifier(label).to_uppercase(), address).unwrap();
+    }
+
+    if !labels.is_empty() {
+        source.push('\n');
+    }
+
+    writeln!(source, "pub const {}: [u32; {}] = [", name, words.len()).unwrap();
+    write_words(&mut source, &words);
+    source.push_str("];\n");
+    source
+}
+
+fn words(image: &[u8]) -> Vec<u32> {
+    image
+        .chunks(4)
+        .map(|chunk| chunk.iter().rev().fold(0u32, |word, &b| (word << 8) | b as u32))
+        .collect()
+}
+
+// Indented lines of comma separated words, the same syntax in C and Rust
+fn write_words(source: &mut String, words: &[u32]) {
+    for line in words.chunks(LINE_WORDS) {
+        let line: Vec<String> = line.iter().map(|word| format!("0x{:08x},", word)).collect();
+        writeln!(source, "    {}", line.join(" ")).unwrap();
+    }
+}
+
+// Replaces the characters that can't be part of a C or Rust identifier, such as the dots of local labels
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        identifier.insert(0, '_');
+    }
+
+    identifier
+}
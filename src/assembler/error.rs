@@ -8,7 +8,21 @@ pub enum AssemblerError {
     ParseError(String),
     InvalidInstruction(String),
     InvalidOperand(String),
-    UndefinedLabel(String)
+    InvalidDirective(String),
+    UndefinedLabel(String),
+    DuplicateSymbol(String),
+    LayoutError(String),  // Memory layout script that can't be read or doesn't fit
+    Context(String, Box<AssemblerError>)  // Where an error inside a macro expansion or an included file happened
+}
+
+impl AssemblerError {
+    // The error without any context
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Context(_, error) => error.root(),
+            error => error
+        }
+    }
 }
 
 impl fmt::Display for AssemblerError {
@@ -18,7 +32,11 @@ impl fmt::Display for AssemblerError {
             Self::ParseError(e) => write!(f, "Parser Error: {}", e),
             Self::InvalidInstruction(e) => write!(f, "Invalid Instruction: {}", e),
             Self::InvalidOperand(e) => write!(f, "Invalid Operand: {}", e),
-            Self::UndefinedLabel(e) => write!(f, "Invalid Label: {}", e)
+            Self::InvalidDirective(e) => write!(f, "Invalid Directive: {}", e),
+            Self::UndefinedLabel(e) => write!(f, "Invalid Label: {}", e),
+            Self::DuplicateSymbol(e) => write!(f, "Duplicate Symbol: {}", e),
+            Self::LayoutError(e) => write!(f, "Layout Error: {}", e),
+            Self::Context(context, e) => write!(f, "{}\n    {}", e, context)
         }
     }
 }
@@ -0,0 +1,376 @@
+//! Evaluates constant expressions in instruction operands and directives
+//!
+//! Operators follow C precedence. Labels evaluate to addresses, and an address may only
+//! be offset by a constant or subtracted from another address (which yields a constant).
+//! The RISC-V relocation operators %hi, %lo, %pcrel_hi and %pcrel_lo split a value
+//! into the 20-bit upper and signed 12-bit lower immediates.
+
+use crate::assembler::directives::parse_string;
+use crate::assembler::error::AssemblerError;
+use crate::assembler::parser::{local_label_reference, parse_integer};
+use crate::assembler::symbols::{SymbolTable, SymbolValue};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Symbol(String),
+    Dot,  // Current location
+    Op(&'static str),
+    Function(&'static str),  // Relocation operator such as %hi
+    LParen,
+    RParen
+}
+
+// Binary operators from the lowest to the highest precedence
+const BINARY_OPERATORS: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"]
+];
+
+// Relocation operators, longest first so that "%pcrel_hi" isn't read as "%hi"
+const FUNCTIONS: [&str; 4] = ["%pcrel_hi", "%pcrel_lo", "%hi", "%lo"];
+
+// Longest operators first so that "<<" isn't read as "<"
+const OPERATORS: [&str; 20] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "+", "-", "*", "/", "%", "&", "|", "^", "~", "!", "<", ">"
+];
+
+pub fn evaluate(
+    expr: &str,
+    current_address: u32,
+    symbols: &dyn SymbolTable
+) -> Result<SymbolValue, AssemblerError> {
+    let tokens = tokenize(expr)?;
+
+    if tokens.is_empty() {
+        return Err(invalid(expr, "empty expression"));
+    }
+
+    let mut evaluator = Evaluator {
+        expr,
+        tokens,
+        pos: 0,
+        current_address,
+        symbols
+    };
+
+    let value = evaluator.binary(0)?;
+
+    if evaluator.pos != evaluator.tokens.len() {
+        return Err(invalid(expr, "unexpected trailing input"));
+    }
+
+    Ok(value)
+}
+
+// Evaluate an expression that must not depend on a label address
+pub fn evaluate_constant(
+    expr: &str,
+    current_address: u32,
+    symbols: &dyn SymbolTable
+) -> Result<i64, AssemblerError> {
+    match evaluate(expr, current_address, symbols)? {
+        SymbolValue::Constant(value) => Ok(value),
+        SymbolValue::Address(_) => Err(AssemblerError::InvalidOperand(format!(
+            "Expected a constant but {} is an address",
+            expr
+        )))
+    }
+}
+
+struct Evaluator<'a> {
+    expr: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    current_address: u32,
+    symbols: &'a dyn SymbolTable
+}
+
+impl Evaluator<'_> {
+    // Precedence climbing over BINARY_OPERATORS
+    fn binary(&mut self, level: usize) -> Result<SymbolValue, AssemblerError> {
+        if level == BINARY_OPERATORS.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            if !BINARY_OPERATORS[level].contains(op) {
+                break;
+            }
+
+            let op = *op;
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = self.apply(op, lhs, rhs)?;
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<SymbolValue, AssemblerError> {
+        let token = self.tokens.get(self.pos).cloned()
+            .ok_or_else(|| invalid(self.expr, "unexpected end of expression"))?;
+        self.pos += 1;
+
+        match token {
+            Token::Number(value) => Ok(SymbolValue::Constant(value)),
+            Token::Dot => Ok(SymbolValue::Address(self.current_address)),
+            Token::Symbol(name) => self.symbols
+                .resolve(&name)
+                .ok_or(AssemblerError::UndefinedLabel(name)),
+
+            Token::LParen => self.parenthesized(),
+
+            Token::Function(function) => {
+                if self.tokens.get(self.pos) != Some(&Token::LParen) {
+                    return Err(invalid(self.expr, &format!("expected '(' after {}", function)));
+                }
+
+                self.pos += 1;
+                let value = self.parenthesized()?;
+                self.relocation(function, value)
+            }
+
+            Token::Op(op @ ("-" | "+" | "~" | "!")) => {
+                let value = self.unary()?;
+
+                match (op, value) {
+                    ("+", value) => Ok(value),
+                    ("-", SymbolValue::Constant(v)) => Ok(SymbolValue::Constant(v.wrapping_neg())),
+                    ("~", SymbolValue::Constant(v)) => Ok(SymbolValue::Constant(!v)),
+                    ("!", SymbolValue::Constant(v)) => Ok(SymbolValue::Constant((v == 0) as i64)),
+                    _ => Err(self.address_misuse(op))
+                }
+            }
+
+            _ => Err(invalid(self.expr, "expected a value"))
+        }
+    }
+
+    // The rest of a parenthesized expression, after the '('
+    fn parenthesized(&mut self) -> Result<SymbolValue, AssemblerError> {
+        let value = self.binary(0)?;
+
+        if self.tokens.get(self.pos) != Some(&Token::RParen) {
+            return Err(invalid(self.expr, "missing ')'"));
+        }
+
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn relocation(&self, function: &str, value: SymbolValue) -> Result<SymbolValue, AssemblerError> {
+        let value = match function {
+            "%hi" => hi20(numeric(value)),
+            "%lo" => lo12(numeric(value)),
+
+            // Relative to the auipc that holds the operand
+            "%pcrel_hi" => hi20(numeric(value) - self.current_address as i64),
+
+            // The operand names the auipc, whose %pcrel_hi target gives the offset
+            "%pcrel_lo" => {
+                let SymbolValue::Address(auipc) = value else {
+                    return Err(invalid(self.expr, "%pcrel_lo expects the label of an auipc"));
+                };
+
+                let target = self.symbols.pcrel_hi_target(auipc).ok_or_else(|| {
+                    invalid(self.expr, "%pcrel_lo label doesn't point to an auipc with %pcrel_hi")
+                })?;
+
+                lo12(numeric(target) - auipc as i64)
+            }
+
+            _ => return Err(invalid(self.expr, "unknown relocation operator"))
+        };
+
+        Ok(SymbolValue::Constant(value))
+    }
+
+    fn apply(
+        &self,
+        op: &str,
+        lhs: SymbolValue,
+        rhs: SymbolValue
+    ) -> Result<SymbolValue, AssemblerError> {
+        use SymbolValue::{Address, Constant};
+
+        let (a, b) = match (lhs, rhs) {
+            (Constant(a), Constant(b)) => (a, b),
+
+            // Offsetting a label keeps it an address
+            (Address(a), Constant(b)) if op == "+" || op == "-" => {
+                let offset = if op == "+" { b } else { b.wrapping_neg() };
+                return Ok(Address((a as i64).wrapping_add(offset) as u32));
+            }
+            (Constant(a), Address(b)) if op == "+" => {
+                return Ok(Address((b as i64).wrapping_add(a) as u32));
+            }
+
+            // The distance between two labels is a constant
+            (Address(a), Address(b)) if op == "-" => {
+                return Ok(Constant(a as i64 - b as i64));
+            }
+
+            _ => return Err(self.address_misuse(op))
+        };
+
+        let value = match op {
+            "+" => a.wrapping_add(b),
+            "-" => a.wrapping_sub(b),
+            "*" => a.wrapping_mul(b),
+            "/" | "%" if b == 0 => return Err(invalid(self.expr, "division by zero")),
+            "/" => a.wrapping_div(b),
+            "%" => a.wrapping_rem(b),
+            "<<" => a.wrapping_shl(b as u32),
+            ">>" => a.wrapping_shr(b as u32),
+            "&" => a & b,
+            "|" => a | b,
+            "^" => a ^ b,
+            "==" => (a == b) as i64,
+            "!=" => (a != b) as i64,
+            "<" => (a < b) as i64,
+            "<=" => (a <= b) as i64,
+            ">" => (a > b) as i64,
+            ">=" => (a >= b) as i64,
+            "&&" => (a != 0 && b != 0) as i64,
+            "||" => (a != 0 || b != 0) as i64,
+            _ => return Err(invalid(self.expr, "unknown operator"))
+        };
+
+        Ok(Constant(value))
+    }
+
+    fn address_misuse(&self, op: &str) -> AssemblerError {
+        AssemblerError::InvalidOperand(format!(
+            "Operator '{}' can't be applied to a label address in {}",
+            op,
+            self.expr
+        ))
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, AssemblerError> {
+    let mut tokens = Vec::new();
+    let bytes = expr.as_bytes();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let c = bytes[pos] as char;
+        let rest = &expr[pos..];
+
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() {
+            // Numbers: 42, 0x2A, 0b101010, 0o52, 1_000
+            // Local label references: 1f, 1b
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+
+            tokens.push(match local_label_reference(word) {
+                Some(_) => Token::Symbol(word.to_string()),
+                None => Token::Number(parse_integer(word)?)
+            });
+            pos += len;
+        } else if is_symbol_char(c) {
+            let len = rest
+                .find(|c: char| !(is_symbol_char(c) || c.is_ascii_digit()))
+                .unwrap_or(rest.len());
+
+            tokens.push(match &rest[..len] {
+                "." => Token::Dot,
+                name => Token::Symbol(name.to_string())
+            });
+            pos += len;
+        } else if c == '\'' {
+            let (value, len) = parse_char(rest)
+                .ok_or_else(|| invalid(expr, "invalid character literal"))?;
+
+            tokens.push(Token::Number(value));
+            pos += len;
+        } else if let Some(function) = FUNCTIONS.iter().find(|f| starts_with_word(rest, f)) {
+            tokens.push(Token::Function(function));
+            pos += function.len();
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            pos += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            pos += 1;
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            pos += op.len();
+        } else {
+            return Err(invalid(expr, &format!("unexpected character '{}'", c)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Upper 20 bits, rounded so that adding the sign-extended lower 12 bits gives the value back
+pub fn hi20(value: i64) -> i64 {
+    (value.wrapping_add(0x800) >> 12) & 0xFFFFF
+}
+
+// Lower 12 bits as a signed immediate
+pub fn lo12(value: i64) -> i64 {
+    ((value & 0xFFF) ^ 0x800) - 0x800
+}
+
+fn numeric(value: SymbolValue) -> i64 {
+    match value {
+        SymbolValue::Address(address) => address as i64,
+        SymbolValue::Constant(value) => value
+    }
+}
+
+// Match a prefix that isn't followed by more identifier characters
+fn starts_with_word(input: &str, word: &str) -> bool {
+    input.starts_with(word)
+        && !input[word.len()..].starts_with(|c: char| is_symbol_char(c) || c.is_ascii_digit())
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '$'
+}
+
+// Parse a character literal such as 'a' or '\n', returning its value and length
+fn parse_char(literal: &str) -> Option<(i64, usize)> {
+    let mut escaped = false;
+
+    for (idx, c) in literal.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\'' => {
+                let inner = &literal[1..idx];
+                let bytes = parse_string(&format!("\"{}\"", inner.replace('"', "\\\""))).ok()?;
+
+                return match bytes.as_slice() {
+                    [byte] => Some((*byte as i64, idx + 1)),
+                    _ => None
+                };
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn invalid(expr: &str, reason: &str) -> AssemblerError {
+    AssemblerError::InvalidOperand(format!("Invalid expression '{}': {}", expr, reason))
+}
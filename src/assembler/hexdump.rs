@@ -15,7 +15,7 @@ pub fn generate_hexdump(binary: &[u8]) -> String {
             _ => 0
         };
 
-        write!(&mut hexdump, "{:08x}: {:08x}\n", address, word).unwrap();
+        writeln!(&mut hexdump, "{:08x}: {:08x}", address, word).unwrap();
 
         address += 4;
     }
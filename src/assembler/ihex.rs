@@ -0,0 +1,123 @@
+//! Writes and reads Intel HEX files
+//!
+//! Every record is ":LLAAAATT<data>CC" with the byte count, the low 16 bits of the address,
+//! the record type and a checksum that makes the sum of all bytes zero. Addresses above
+//! 64 KiB are reached with extended linear address records that set the upper 16 bits.
+
+use std::fmt::Write;
+use crate::assembler::error::AssemblerError;
+use crate::assembler::section::{append_segment, Segment};
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const START_LINEAR_ADDRESS: u8 = 0x05;
+
+// Data bytes per record
+const RECORD_SIZE: usize = 16;
+
+// Data records for every segment, the entry point as a start linear address and the end of file record
+pub fn generate_ihex(segments: &[Segment], entry: Option<u32>) -> String {
+    let mut ihex = String::new();
+    let mut upper = 0;
+
+    for segment in segments {
+        let mut address = segment.address;
+        let mut data = segment.data.as_slice();
+
+        while !data.is_empty() {
+            if address >> 16 != upper {
+                upper = address >> 16;
+                write_record(&mut ihex, EXTENDED_LINEAR_ADDRESS, 0, &(upper as u16).to_be_bytes());
+            }
+
+            // Records don't cross a 64 KiB boundary
+            let len = RECORD_SIZE.min(0x10000 - (address & 0xFFFF) as usize).min(data.len());
+            write_record(&mut ihex, DATA, address as u16, &data[..len]);
+
+            address = address.wrapping_add(len as u32);
+            data = &data[len..];
+        }
+    }
+
+    if let Some(entry) = entry {
+        write_record(&mut ihex, START_LINEAR_ADDRESS, 0, &entry.to_be_bytes());
+    }
+
+    write_record(&mut ihex, END_OF_FILE, 0, &[]);
+    ihex
+}
+
+// Segments and entry point of an Intel HEX file
+pub fn parse_ihex(ihex: &str) -> Result<(Vec<Segment>, Option<u32>), AssemblerError> {
+    let mut segments = Vec::new();
+    let mut entry = None;
+    let mut base = 0u32;
+
+    for (idx, line) in ihex.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message: &str| AssemblerError::ParseError(format!("Line {}: {}", idx + 1, message));
+
+        let record = line
+            .strip_prefix(':')
+            .ok_or_else(|| error("Records start with ':'"))
+            .and_then(|hex| parse_hex_bytes(hex).ok_or_else(|| error("Invalid hex digits")))?;
+
+        if record.len() < 5 || record.len() != record[0] as usize + 5 {
+            return Err(error("Record length doesn't match its byte count"));
+        }
+
+        if record.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+            return Err(error("Checksum mismatch"));
+        }
+
+        let offset = u16::from_be_bytes([record[1], record[2]]) as u32;
+        let data = &record[4..record.len() - 1];
+        let value = || data.iter().fold(0u32, |value, &b| (value << 8) | b as u32);
+
+        match (record[3], data.len()) {
+            (DATA, _) => append_segment(&mut segments, base.wrapping_add(offset), data),
+            (END_OF_FILE, 0) => break,
+            (EXTENDED_SEGMENT_ADDRESS, 2) => base = value() << 4,
+            (EXTENDED_LINEAR_ADDRESS, 2) => base = value() << 16,
+            (START_SEGMENT_ADDRESS, 4) => entry = Some((value() >> 16 << 4) + (value() & 0xFFFF)),
+            (START_LINEAR_ADDRESS, 4) => entry = Some(value()),
+            (record_type, _) => return Err(error(&format!("Invalid record of type {:02X}", record_type)))
+        }
+    }
+
+    Ok((segments, entry))
+}
+
+fn write_record(ihex: &mut String, record_type: u8, address: u16, data: &[u8]) {
+    let [high, low] = address.to_be_bytes();
+    let mut checksum = (data.len() as u8).wrapping_add(high).wrapping_add(low).wrapping_add(record_type);
+
+    write!(ihex, ":{:02X}{:04X}{:02X}", data.len(), address, record_type).unwrap();
+
+    for &byte in data {
+        write!(ihex, "{:02X}", byte).unwrap();
+        checksum = checksum.wrapping_add(byte);
+    }
+
+    writeln!(ihex, "{:02X}", checksum.wrapping_neg()).unwrap();
+}
+
+// Pairs of hex digits
+pub(crate) fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).ok())
+        .collect()
+}
@@ -228,7 +228,7 @@ static INSTRUCTIONS: phf::Map<&'static str, InstructionFormat> = phf_map! {
     // Shift Right Arithmetic Immediate Word (RV64I)
     "sraiw" => InstructionFormat {
         fmt: InstructionType::I,
-        opcode: 0b0010011,
+        opcode: 0b0011011,
         funct3: Some(0b101),
         funct7: Some(0b0100000)
     },
@@ -556,8 +556,15 @@ static INSTRUCTIONS: phf::Map<&'static str, InstructionFormat> = phf_map! {
     }
 };
 
+#[derive(Debug, Copy, Clone)]
 pub struct InstructionSet;
 
+impl Default for InstructionSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl InstructionSet {
     pub fn new() -> Self {
         InstructionSet
@@ -566,4 +573,9 @@ impl InstructionSet {
     pub fn get_instruction(&self, instr: &str) -> Option<&InstructionFormat> {
         INSTRUCTIONS.get(instr)
     }
+
+    // Every instruction of the table with its mnemonic, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &'static InstructionFormat)> {
+        INSTRUCTIONS.entries().map(|(mnemonic, fmt)| (*mnemonic, fmt))
+    }
 }
\ No newline at end of file
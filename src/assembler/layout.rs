@@ -0,0 +1,778 @@
+//! Places sections in memory from a subset of the GNU ld script syntax
+//!
+//! MEMORY
+//! {
+//!     ROM (rx)  : ORIGIN = 0x00000000, LENGTH = 16K
+//!     RAM (rwx) : ORIGIN = 0x80000000, LENGTH = 0x4000
+//! }
+//!
+//! SECTIONS
+//! {
+//!     .text : { *(.text.init) *(.text .text.*) } > ROM
+//!     .rodata : { *(.rodata*) } > ROM
+//!     .data : { *(.data*) } > RAM
+//!     .bss : { *(.bss*) } > RAM
+//!     _stack_top = ORIGIN(RAM) + LENGTH(RAM);
+//! }
+//!
+//! Also supported: ENTRY(symbol), PROVIDE(symbol = expr), KEEP(...), `. = expr` and the
+//! ORIGIN, LENGTH, ADDR, SIZEOF, ALIGN, MIN and MAX functions. Sections the script doesn't
+//! mention follow the last section of the same kind.
+
+use std::collections::HashMap;
+use std::fs;
+use crate::assembler::align_up;
+use crate::assembler::error::AssemblerError;
+use crate::assembler::expr::evaluate;
+use crate::assembler::section::{aligned_start, section_end, Section};
+use crate::assembler::symbols::SymbolValue;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub origin: u32,
+    pub length: u64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    // Symbol definition, name is "." to move the location counter
+    Assign { name: String, expr: String, provide: bool },
+
+    // *(patterns): input sections whose names match one of the patterns
+    Input(Vec<String>),
+
+    // name [address] : { commands } [> region]
+    Output {
+        name: String,
+        address: Option<String>,
+        commands: Vec<Command>,
+        region: Option<usize>
+    }
+}
+
+// Where the commands of the script are allowed
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Level {
+    Script,    // Top level
+    Sections,  // Inside SECTIONS
+    Output     // Inside an output section
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryLayout {
+    regions: Vec<MemoryRegion>,
+    commands: Vec<Command>,
+    entry: Option<String>
+}
+
+impl MemoryLayout {
+    pub fn from_file(path: &str) -> Result<Self, AssemblerError> {
+        let script = fs::read_to_string(path)
+            .map_err(|e| AssemblerError::IOError(format!("{}: {}", path, e)))?;
+
+        Self::parse(&script)
+    }
+
+    pub fn parse(script: &str) -> Result<Self, AssemblerError> {
+        let text = strip_comments(script);
+        let mut layout = Self::default();
+        layout.commands = layout.parse_commands(&text, Level::Script)?;
+        Ok(layout)
+    }
+
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    // Symbol given by ENTRY(symbol)
+    pub fn entry(&self) -> Option<&str> {
+        self.entry.as_deref()
+    }
+
+    // Assign the address of every section and return the symbols the script defines
+    // PROVIDE only defines symbols that is_defined says the program doesn't
+    pub fn place(
+        &self,
+        sections: &mut [Section],
+        is_defined: &dyn Fn(&str) -> bool
+    ) -> Result<HashMap<String, u32>, AssemblerError> {
+        let mut placement = Placement {
+            layout: self,
+            location: 0,
+            cursors: self.regions.iter().map(|r| r.origin).collect(),
+            symbols: HashMap::new(),
+            outputs: HashMap::new(),
+            placed: vec![false; sections.len()],
+            last_region: None
+        };
+
+        // Sections the script doesn't mention go right after the output section with the last
+        // section of the same kind, so the sections and symbols that follow it move up like in ld
+        let outputs = self.outputs(sections);
+        let mut orphans: Vec<usize> = (0..sections.len()).filter(|&idx| outputs[idx].is_none()).collect();
+        orphans.sort_by_key(|&idx| sections[idx].kind);
+
+        let mut following: Vec<(usize, usize)> = Vec::new();  // Output command and section
+        let mut remaining = Vec::new();
+
+        for idx in orphans {
+            let output = (0..sections.len())
+                .filter(|&other| sections[other].kind == sections[idx].kind)
+                .filter_map(|other| outputs[other])
+                .max();
+
+            match output {
+                Some(output) => following.push((output, idx)),
+                None => remaining.push(idx)
+            }
+        }
+
+        for (command_idx, command) in self.commands.iter().enumerate() {
+            placement.run(std::slice::from_ref(command), sections, is_defined, None)?;
+
+            let region = match command {
+                Command::Output { region, .. } => *region,
+                _ => None
+            };
+
+            for &(_, idx) in following.iter().filter(|(output, _)| *output == command_idx) {
+                placement.place_orphan(&mut sections[idx], region)?;
+            }
+        }
+
+        // Sections of a kind the script doesn't place at all end up after the last region used
+        for idx in remaining {
+            if let Some(region) = placement.last_region {
+                placement.location = placement.cursors[region];
+            }
+
+            placement.place_orphan(&mut sections[idx], placement.last_region)?;
+        }
+
+        Ok(placement.symbols)
+    }
+
+    // Index of the output section command each section is placed by, if any
+    fn outputs(&self, sections: &[Section]) -> Vec<Option<usize>> {
+        let mut outputs = vec![None; sections.len()];
+
+        for (command_idx, command) in self.commands.iter().enumerate() {
+            let Command::Output { commands, .. } = command else {
+                continue;
+            };
+
+            for command in commands {
+                let Command::Input(patterns) = command else {
+                    continue;
+                };
+
+                for (idx, section) in sections.iter().enumerate() {
+                    if outputs[idx].is_none() && patterns.iter().any(|p| glob_match(p, &section.name)) {
+                        outputs[idx] = Some(command_idx);
+                    }
+                }
+            }
+        }
+
+        outputs
+    }
+
+    fn parse_commands(&mut self, text: &str, level: Level) -> Result<Vec<Command>, AssemblerError> {
+        let mut cursor = Cursor { text, pos: 0 };
+        let mut commands = Vec::new();
+
+        while !cursor.at_end() {
+            if cursor.eat(";") {
+                continue;
+            }
+
+            let word = cursor.word();
+
+            if word.is_empty() {
+                return Err(layout_error(&format!("Unexpected '{}'", cursor.rest().trim())));
+            }
+
+            match (level, word) {
+                (Level::Script, "MEMORY") => {
+                    let body = cursor.group('{', '}')?;
+                    self.parse_memory(body)?;
+                }
+                (Level::Script, "SECTIONS") => {
+                    let body = cursor.group('{', '}')?;
+                    let sections = self.parse_commands(body, Level::Sections)?;
+                    commands.extend(sections);
+                }
+                (Level::Script, "ENTRY") => {
+                    self.entry = Some(cursor.group('(', ')')?.trim().to_string());
+                }
+
+                // There is only one output format
+                (Level::Script, "OUTPUT_ARCH" | "OUTPUT_FORMAT") => {
+                    cursor.group('(', ')')?;
+                }
+                (_, "PROVIDE" | "PROVIDE_HIDDEN") => {
+                    let inner = cursor.group('(', ')')?;
+                    let (name, expr) = inner
+                        .split_once('=')
+                        .ok_or_else(|| layout_error(&format!("Expected an assignment in PROVIDE({})", inner)))?;
+
+                    commands.push(Command::Assign {
+                        name: name.trim().to_string(),
+                        expr: expr.trim().to_string(),
+                        provide: true
+                    });
+                }
+                (Level::Output, "KEEP") => {
+                    let inner = cursor.group('(', ')')?;
+                    commands.extend(self.parse_commands(inner, Level::Output)?);
+                }
+                (Level::Output, "*") => {
+                    let patterns = cursor.group('(', ')')?;
+
+                    commands.push(Command::Input(
+                        patterns
+                            .split(|c: char| c.is_whitespace() || c == ',')
+                            .filter(|p| !p.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    ));
+                }
+                (Level::Output, file) if cursor.rest().trim_start().starts_with('(') => {
+                    return Err(layout_error(&format!("Input files can only be selected with *, not {}", file)));
+                }
+                (_, name) if cursor.eat("+=") => {
+                    let expr = cursor.statement()?;
+                    commands.push(Command::Assign {
+                        name: name.to_string(),
+                        expr: format!("{} + ({})", name, expr),
+                        provide: false
+                    });
+                }
+                (_, name) if cursor.eat("=") => {
+                    let expr = cursor.statement()?;
+                    commands.push(Command::Assign {
+                        name: name.to_string(),
+                        expr: expr.to_string(),
+                        provide: false
+                    });
+                }
+                (Level::Sections, name) => commands.push(self.parse_output(name, &mut cursor)?),
+                (_, word) => return Err(layout_error(&format!("Unsupported command {}", word)))
+            }
+        }
+
+        Ok(commands)
+    }
+
+    // The rest of "name [address] : { commands } [> region]"
+    fn parse_output(&mut self, name: &str, cursor: &mut Cursor) -> Result<Command, AssemblerError> {
+        let address = cursor.until(&[':', '{', ';']).trim();
+
+        if !cursor.eat(":") {
+            return Err(layout_error(&format!("Expected ':' after the output section {}", name)));
+        }
+
+        let attributes = cursor.until(&['{', ';']).trim();
+
+        if !attributes.is_empty() {
+            return Err(layout_error(&format!("Unsupported attributes {} of {}", attributes, name)));
+        }
+
+        let body = cursor.group('{', '}')?;
+        let commands = self.parse_commands(body, Level::Output)?;
+
+        let region = match cursor.eat(">") {
+            true => {
+                let region = cursor.word();
+
+                Some(self.region_index(region).ok_or_else(|| {
+                    layout_error(&format!("{} is placed in {}, which isn't a MEMORY region", name, region))
+                })?)
+            }
+            false => None
+        };
+
+        if cursor.rest().trim_start().starts_with("AT") {
+            return Err(layout_error(&format!("Load addresses (AT) of {} are not supported", name)));
+        }
+
+        Ok(Command::Output {
+            name: name.to_string(),
+            address: (!address.is_empty()).then(|| address.to_string()),
+            commands,
+            region
+        })
+    }
+
+    // Lines of "name (attributes) : ORIGIN = expr, LENGTH = expr"
+    fn parse_memory(&mut self, text: &str) -> Result<(), AssemblerError> {
+        let mut cursor = Cursor { text, pos: 0 };
+
+        while !cursor.at_end() {
+            let name = cursor.word().to_string();
+
+            // Attributes (rwx) only matter for sections the script doesn't place
+            if cursor.rest().trim_start().starts_with('(') {
+                cursor.group('(', ')')?;
+            }
+
+            if name.is_empty() || !cursor.eat(":") {
+                return Err(layout_error(&format!("Expected a MEMORY region but found '{}'", cursor.rest().trim())));
+            }
+
+            let mut origin = None;
+            let mut length = None;
+
+            for _ in 0..2 {
+                let key = cursor.word();
+                let value = match cursor.eat("=") {
+                    true => cursor.until(&[',', '\n']),
+                    false => return Err(layout_error(&format!("Expected '=' after {} in {}", key, name)))
+                };
+                cursor.eat(",");
+
+                let value = Placement::constant(self, value)?;
+
+                match key {
+                    "ORIGIN" | "org" | "o" => origin = Some(value),
+                    "LENGTH" | "len" | "l" => length = Some(value),
+                    _ => return Err(layout_error(&format!("Unknown MEMORY attribute {} in {}", key, name)))
+                }
+            }
+
+            let (Some(origin), Some(length)) = (origin, length) else {
+                return Err(layout_error(&format!("{} needs an ORIGIN and a LENGTH", name)));
+            };
+
+            if self.region_index(&name).is_some() {
+                return Err(layout_error(&format!("MEMORY region {} is defined twice", name)));
+            }
+
+            let out_of_range = || layout_error(&format!("{} doesn't fit in the 32-bit address space", name));
+
+            self.regions.push(MemoryRegion {
+                origin: u32::try_from(origin).map_err(|_| out_of_range())?,
+                length: u64::try_from(length).map_err(|_| out_of_range())?,
+                name
+            });
+        }
+
+        Ok(())
+    }
+
+    fn region_index(&self, name: &str) -> Option<usize> {
+        self.regions.iter().position(|r| r.name == name)
+    }
+}
+
+// State of the location counter while the commands run
+struct Placement<'a> {
+    layout: &'a MemoryLayout,
+    location: u32,
+    cursors: Vec<u32>,                      // Next free address in each region
+    symbols: HashMap<String, u32>,
+    outputs: HashMap<String, (u32, u32)>,   // Address and size of each output section
+    placed: Vec<bool>,
+    last_region: Option<usize>
+}
+
+impl Placement<'_> {
+    fn run(
+        &mut self,
+        commands: &[Command],
+        sections: &mut [Section],
+        is_defined: &dyn Fn(&str) -> bool,
+        region: Option<usize>
+    ) -> Result<(), AssemblerError> {
+        for command in commands {
+            match command {
+                Command::Assign { name, expr, provide } => {
+                    if *provide && is_defined(name) {
+                        continue;
+                    }
+
+                    let value = self.value(expr)?;
+
+                    if name == "." {
+                        if region.is_some() && value < self.location {
+                            return Err(layout_error(&format!("'. = {}' moves the location counter backwards", expr)));
+                        }
+
+                        self.location = value;
+                    } else {
+                        self.symbols.insert(name.clone(), value);
+                    }
+                }
+                Command::Input(patterns) => {
+                    for (idx, section) in sections.iter_mut().enumerate() {
+                        if self.placed[idx] || !patterns.iter().any(|p| glob_match(p, &section.name)) {
+                            continue;
+                        }
+
+                        self.place_section(section)?;
+                        self.placed[idx] = true;
+                    }
+                }
+                Command::Output { name, address, commands, region } => {
+                    self.location = match (address, region) {
+                        (Some(address), _) => self.value(address)?,
+                        (None, Some(region)) => self.cursors[*region],
+                        (None, None) => self.location
+                    };
+
+                    // The output section starts where its first input section does
+                    let align = first_input_align(commands, sections, &self.placed);
+                    self.location = align_up(self.location, align);
+
+                    let start = self.location;
+                    self.run(commands, sections, is_defined, *region)?;
+                    self.outputs.insert(name.clone(), (start, self.location - start));
+
+                    if let Some(region) = *region {
+                        self.cursors[region] = self.location;
+                        self.last_region = Some(region);
+                        self.check_region(region, name)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn place_section(&mut self, section: &mut Section) -> Result<(), AssemblerError> {
+        section.address = aligned_start(self.location, section.align, &section.name)?;
+        self.location = section_end(section)?;
+
+        Ok(())
+    }
+
+    // A section the script doesn't mention is an output section of its own
+    fn place_orphan(&mut self, section: &mut Section, region: Option<usize>) -> Result<(), AssemblerError> {
+        self.place_section(section)?;
+        self.outputs.insert(section.name.clone(), (section.address, section.size()));
+
+        if let Some(region) = region {
+            self.cursors[region] = self.location;
+            self.check_region(region, &section.name)?;
+        }
+
+        Ok(())
+    }
+
+    // The contents placed in a region have to fit between its origin and end
+    fn check_region(&self, region: usize, name: &str) -> Result<(), AssemblerError> {
+        let region = &self.layout.regions[region];
+        let end = region.origin as u64 + region.length;
+
+        if (self.location as u64) > end {
+            return Err(layout_error(&format!(
+                "{} overflows {} by {} bytes",
+                name,
+                region.name,
+                self.location as u64 - end
+            )));
+        }
+
+        if self.location < region.origin {
+            return Err(layout_error(&format!("{} is placed before the start of {}", name, region.name)));
+        }
+
+        Ok(())
+    }
+
+    // Value of an expression at the current location
+    fn value(&self, expr: &str) -> Result<u32, AssemblerError> {
+        let expanded = self.expand(expr)?;
+
+        match evaluate(&expanded, self.location, &self.symbols)? {
+            SymbolValue::Address(address) => Ok(address),
+            SymbolValue::Constant(value) => Ok(value as u32)
+        }
+    }
+
+    // Value of an expression that doesn't depend on any section, such as a MEMORY attribute
+    fn constant(layout: &MemoryLayout, expr: &str) -> Result<i64, AssemblerError> {
+        let placement = Placement {
+            layout,
+            location: 0,
+            cursors: Vec::new(),
+            symbols: HashMap::new(),
+            outputs: HashMap::new(),
+            placed: Vec::new(),
+            last_region: None
+        };
+
+        let expanded = placement.expand(expr)?;
+
+        match evaluate(&expanded, 0, &placement.symbols)? {
+            SymbolValue::Address(address) => Ok(address as i64),
+            SymbolValue::Constant(value) => Ok(value)
+        }
+    }
+
+    // Replace the script functions and K/M size suffixes with their values,
+    // leaving an expression the assembler can evaluate
+    fn expand(&self, expr: &str) -> Result<String, AssemblerError> {
+        let mut out = String::with_capacity(expr.len());
+        let mut rest = expr;
+
+        while let Some(c) = rest.chars().next() {
+            if !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$') {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            rest = &rest[len..];
+
+            let is_call = rest.trim_start().starts_with('(');
+
+            if is_call && FUNCTIONS.contains(&word) {
+                let mut cursor = Cursor { text: rest, pos: 0 };
+                let args = cursor.group('(', ')')?;
+                rest = cursor.rest();
+
+                out.push_str(&self.call(word, args)?.to_string());
+            } else if let Some(value) = size_suffix(word) {
+                out.push_str(&value.to_string());
+            } else {
+                out.push_str(word);
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn call(&self, function: &str, args: &str) -> Result<u32, AssemblerError> {
+        let args: Vec<&str> = split_arguments(args);
+        let region = |name: &str| {
+            self.layout.regions
+                .iter()
+                .find(|r| r.name == name)
+                .ok_or_else(|| layout_error(&format!("{}({}): no such MEMORY region", function, name)))
+        };
+        let output = |name: &str| {
+            self.outputs
+                .get(name)
+                .copied()
+                .ok_or_else(|| layout_error(&format!("{}({}): the section isn't placed yet", function, name)))
+        };
+        let alignment = |expr: &str| -> Result<u32, AssemblerError> {
+            let align = self.value(expr)?;
+
+            match align.is_power_of_two() {
+                true => Ok(align),
+                false => Err(layout_error(&format!("ALIGN({}) is not a power of two", expr)))
+            }
+        };
+
+        match (function, args.as_slice()) {
+            ("ORIGIN", [name]) => Ok(region(name)?.origin),
+            ("LENGTH", [name]) => Ok(region(name)?.length as u32),
+            ("ADDR", [name]) => Ok(output(name)?.0),
+            ("SIZEOF", [name]) => Ok(output(name)?.1),
+            ("ALIGN", [align]) => Ok(align_up(self.location, alignment(align)?)),
+            ("ALIGN", [expr, align]) => Ok(align_up(self.value(expr)?, alignment(align)?)),
+            ("MIN", [a, b]) => Ok(self.value(a)?.min(self.value(b)?)),
+            ("MAX", [a, b]) => Ok(self.value(a)?.max(self.value(b)?)),
+            _ => Err(layout_error(&format!("Wrong number of arguments for {}", function)))
+        }
+    }
+}
+
+const FUNCTIONS: [&str; 7] = ["ORIGIN", "LENGTH", "ADDR", "SIZEOF", "ALIGN", "MIN", "MAX"];
+
+// Tokens of the script: names, patterns and punctuation
+struct Cursor<'a> {
+    text: &'a str,
+    pos: usize
+}
+
+impl<'a> Cursor<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.pos >= self.text.len()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    // A name, section name or file pattern
+    fn word(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || "(){}:;=,>+".contains(c))
+            .unwrap_or(rest.len());
+
+        self.pos += len;
+        &rest[..len]
+    }
+
+    // Text up to one of the stop characters outside of parentheses, which is left in place
+    fn until(&mut self, stops: &[char]) -> &'a str {
+        let rest = self.rest();
+        let mut depth = 0;
+
+        let len = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+
+                depth <= 0 && stops.contains(&c)
+            })
+            .map_or(rest.len(), |(idx, _)| idx);
+
+        self.pos += len;
+        &rest[..len]
+    }
+
+    // An expression terminated by ';'
+    fn statement(&mut self) -> Result<&'a str, AssemblerError> {
+        let expr = self.until(&[';', '}']).trim();
+
+        match self.eat(";") {
+            true => Ok(expr),
+            false => Err(layout_error(&format!("Expected ';' after {}", expr)))
+        }
+    }
+
+    // The contents between an opening and its matching closing character
+    fn group(&mut self, open: char, close: char) -> Result<&'a str, AssemblerError> {
+        if !self.eat(&open.to_string()) {
+            return Err(layout_error(&format!("Expected '{}' before '{}'", open, self.rest().trim())));
+        }
+
+        let rest = self.rest();
+        let mut depth = 1;
+
+        for (idx, c) in rest.char_indices() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+
+                if depth == 0 {
+                    self.pos += idx + 1;
+                    return Ok(&rest[..idx]);
+                }
+            }
+        }
+
+        Err(layout_error(&format!("Missing '{}'", close)))
+    }
+}
+
+// Alignment of the first section an output section takes
+fn first_input_align(commands: &[Command], sections: &[Section], placed: &[bool]) -> u32 {
+    commands
+        .iter()
+        .filter_map(|command| match command {
+            Command::Input(patterns) => sections
+                .iter()
+                .zip(placed)
+                .find(|(section, placed)| !**placed && patterns.iter().any(|p| glob_match(p, &section.name)))
+                .map(|(section, _)| section.align),
+            _ => None
+        })
+        .next()
+        .unwrap_or(1)
+}
+
+// Shell-style pattern with * and ?
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let name = name.as_bytes();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+// 16K, 4M
+fn size_suffix(word: &str) -> Option<i64> {
+    let (number, scale) = match word.as_bytes() {
+        [b'0'..=b'9', .., b'K'] => (&word[..word.len() - 1], 1 << 10),
+        [b'0'..=b'9', .., b'M'] => (&word[..word.len() - 1], 1 << 20),
+        _ => return None
+    };
+
+    crate::assembler::parser::parse_integer(number).ok().map(|value| value * scale)
+}
+
+// Arguments separated by commas outside of parentheses
+fn split_arguments(args: &str) -> Vec<&str> {
+    let mut cursor = Cursor { text: args, pos: 0 };
+    let mut parts = Vec::new();
+
+    loop {
+        parts.push(cursor.until(&[',']).trim());
+
+        if !cursor.eat(",") {
+            return parts;
+        }
+    }
+}
+
+// Remove /* ... */ comments, keeping the line breaks that end MEMORY entries
+fn strip_comments(script: &str) -> String {
+    let mut out = String::with_capacity(script.len());
+    let mut rest = script;
+
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find("*/").map_or(rest.len(), |end| start + end + 2);
+        out.extend(rest[start..end].chars().filter(|&c| c == '\n'));
+        rest = &rest[end..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn layout_error(message: &str) -> AssemblerError {
+    AssemblerError::LayoutError(message.to_string())
+}
@@ -0,0 +1,191 @@
+//! Records the source lines of the second pass and renders them as an assembly listing
+//!
+//! Every line shows its number, the address it was assembled at and the bytes it emitted:
+//! one row per machine word for instructions, so the expansion of a pseudo-instruction is
+//! visible, and up to 8 bytes per row for data. Lines from included files, macro expansions
+//! and repetitions are grouped under a header naming where they came from.
+
+use std::fmt::Write;
+use crate::assembler::section::Section;
+use crate::assembler::symbols::{Symbol, SymbolKind};
+
+// Bytes per row of data and rows per line before the rest is elided, like GNU as
+const DATA_ROW_BYTES: usize = 8;
+const DATA_ROWS: usize = 4;
+
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    lines: Vec<ListingLine>,
+    contexts: Vec<String>,  // Files, macro expansions and repetitions the lines came from
+    context: usize          // Index of the current context
+}
+
+// The source line that emitted the bytes at an address, for debuggers
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub address: u32,
+    pub size: u32,
+    pub file: String,
+    pub line: usize,
+    pub source: String
+}
+
+#[derive(Debug, Clone)]
+struct ListingLine {
+    context: usize,
+    file: String,
+    line: usize,
+    source: String,
+    address: Option<u32>,  // Lines that emit nothing and define no label have no address
+    bytes: Vec<u8>,        // Empty in .bss
+    size: u32,
+    instruction: bool      // Shown as words instead of bytes
+}
+
+impl Listing {
+    // Forget the lines of an earlier pass and start again in the given file
+    pub(crate) fn start(&mut self, file: &str) {
+        self.lines.clear();
+        self.contexts = vec![file.to_string()];
+        self.context = 0;
+    }
+
+    // Lines recorded until leave() belong to a new context, returns the one to go back to
+    pub(crate) fn enter(&mut self, context: String) -> usize {
+        self.contexts.push(context);
+        std::mem::replace(&mut self.context, self.contexts.len() - 1)
+    }
+
+    pub(crate) fn leave(&mut self, context: usize) {
+        self.context = context;
+    }
+
+    // Record a line before it is assembled, returns its index for finish()
+    pub(crate) fn begin(&mut self, file: &str, line: usize, source: &str) -> usize {
+        self.lines.push(ListingLine {
+            context: self.context,
+            file: file.to_string(),
+            line,
+            source: source.to_string(),
+            address: None,
+            bytes: Vec::new(),
+            size: 0,
+            instruction: false
+        });
+
+        self.lines.len() - 1
+    }
+
+    // Record what a line emitted into the section from offset on
+    // Lines that expanded into other lines (macros, includes, repetitions) leave the bytes to those
+    pub(crate) fn finish(&mut self, index: usize, section: &Section, offset: u32, label: bool) {
+        if index + 1 != self.lines.len() {
+            return;
+        }
+
+        let line = &mut self.lines[index];
+        line.size = section.size() - offset;
+
+        if line.size > 0 || label {
+            line.address = Some(section.address.wrapping_add(offset));
+        }
+
+        if let Some(bytes) = section.data.get(offset as usize..) {
+            line.bytes = bytes.to_vec();
+        }
+    }
+
+    // The line being assembled is an instruction
+    pub(crate) fn mark_instruction(&mut self) {
+        if let Some(line) = self.lines.last_mut() {
+            line.instruction = true;
+        }
+    }
+
+    // Lines that emitted bytes in address order, macro and repeated lines with the line of their body
+    pub fn line_map(&self) -> Vec<SourceLine> {
+        let mut lines: Vec<SourceLine> = self.lines
+            .iter()
+            .filter(|line| line.size > 0)
+            .filter_map(|line| Some(SourceLine {
+                address: line.address?,
+                size: line.size,
+                file: line.file.clone(),
+                line: line.line,
+                source: line.source.clone()
+            }))
+            .collect();
+
+        lines.sort_by_key(|line| line.address);
+        lines
+    }
+
+    // Text of the listing followed by the symbol table
+    pub fn render(&self, sections: &[Section], symbols: &[(&str, Symbol)]) -> String {
+        let mut listing = String::new();
+        let mut context = None;
+
+        for line in &self.lines {
+            if context != Some(line.context) {
+                context = Some(line.context);
+                writeln!(listing, "; {}", self.contexts[line.context]).unwrap();
+            }
+
+            let rows = match line.instruction {
+                true => line.bytes.chunks(4).map(|word| format!("{:08x}", le_value(word))).collect(),
+                false => line.bytes.chunks(DATA_ROW_BYTES).map(hex_bytes).collect::<Vec<_>>()
+            };
+
+            let address = |row: usize| {
+                let step = if line.instruction { 4 } else { DATA_ROW_BYTES as u32 };
+                line.address.map_or(String::new(), |a| format!("{:08x}", a.wrapping_add(row as u32 * step)))
+            };
+
+            let first = rows.first().map_or("", String::as_str);
+            write_row(&mut listing, &line.line.to_string(), &address(0), first, &line.source);
+
+            for (idx, row) in rows.iter().enumerate().skip(1) {
+                if !line.instruction && idx == DATA_ROWS {
+                    write_row(&mut listing, "", "", "...", "");
+                    break;
+                }
+
+                write_row(&mut listing, "", &address(idx), row, "");
+            }
+        }
+
+        listing.push_str("\nSymbols\n");
+
+        let mut symbols: Vec<(u64, &str, &str)> = symbols
+            .iter()
+            .map(|(name, symbol)| match symbol.kind {
+                SymbolKind::Label { section } => (
+                    sections[section].address.wrapping_add(symbol.value as u32) as u64,
+                    sections[section].name.as_str(),
+                    *name
+                ),
+                SymbolKind::Constant { .. } => (symbol.value as u64, "*ABS*", *name)
+            })
+            .collect();
+        symbols.sort();
+
+        for (value, section, name) in symbols {
+            writeln!(listing, "{:08x}  {:<10} {}", value, section, name).unwrap();
+        }
+
+        listing
+    }
+}
+
+fn write_row(listing: &mut String, line: &str, address: &str, code: &str, source: &str) {
+    let row = format!("{:>5}  {:<8}  {:<16}  {}", line, address, code, source);
+    writeln!(listing, "{}", row.trim_end()).unwrap();
+}
+
+fn le_value(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0, |value, &b| (value << 8) | b as u32)
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
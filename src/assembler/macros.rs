@@ -0,0 +1,215 @@
+//! Defines GNU-style macros (.macro/.endm) and expands their invocations into source lines
+
+use std::collections::HashMap;
+use crate::assembler::error::AssemblerError;
+use crate::assembler::parser::{is_symbol_name, split_operands};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroParam {
+    pub name: String,
+    pub default: Option<String>,
+    pub required: bool,  // name:req
+    pub vararg: bool     // name:vararg takes the remaining arguments
+}
+
+impl MacroParam {
+    // Parse "name", "name=default", "name:req" or "name:vararg"
+    pub fn parse(param: &str) -> Result<Self, AssemblerError> {
+        let (param, default) = match param.split_once('=') {
+            Some((param, default)) => (param.trim(), Some(default.trim().to_string())),
+            None => (param.trim(), None)
+        };
+
+        let (name, qualifier) = match param.split_once(':') {
+            Some((name, qualifier)) => (name.trim(), Some(qualifier.trim())),
+            None => (param, None)
+        };
+
+        if !is_symbol_name(name) {
+            return Err(AssemblerError::InvalidDirective(format!(
+                "Invalid macro parameter: {}",
+                param
+            )));
+        }
+
+        let (required, vararg) = match qualifier {
+            None => (false, false),
+            Some("req") => (true, false),
+            Some("vararg") => (false, true),
+            Some(qualifier) => {
+                return Err(AssemblerError::InvalidDirective(format!(
+                    "Unknown macro parameter qualifier :{}",
+                    qualifier
+                )));
+            }
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            default,
+            required,
+            vararg
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macro {
+    pub name: String,
+    pub params: Vec<MacroParam>,
+    pub body: Vec<String>,
+    pub file: String,  // Where the macro was defined, for error messages
+    pub line: usize    // Line of the .macro directive
+}
+
+impl Macro {
+    // Substitute the arguments of an invocation into the body
+    // counter replaces \@ so that every expansion can define its own labels
+    pub fn expand(&self, args: &str, counter: usize) -> Result<Vec<String>, AssemblerError> {
+        let values = self.bind(args)?;
+
+        Ok(self.body
+            .iter()
+            .map(|line| substitute(line, &values, Some(counter)))
+            .collect())
+    }
+
+    // Match invocation arguments to parameters, positionally or as name=value
+    fn bind(&self, args: &str) -> Result<HashMap<&str, String>, AssemblerError> {
+        let args = split_operands(args);
+        let mut values: HashMap<&str, String> = HashMap::new();
+        let mut position = 0;
+
+        for (idx, arg) in args.iter().enumerate() {
+            // Named argument
+            if let Some((name, value)) = arg.split_once('=')
+                && let Some(param) = self.params.iter().find(|p| p.name == name.trim())
+            {
+                values.insert(&param.name, value.trim().to_string());
+                continue;
+            }
+
+            let param = self.params.get(position).ok_or_else(|| {
+                AssemblerError::InvalidOperand(format!(
+                    "Too many arguments for macro {}: expected {} but received {}",
+                    self.name,
+                    self.params.len(),
+                    args.len()
+                ))
+            })?;
+
+            // A vararg parameter takes the rest of the line as-is
+            if param.vararg {
+                values.insert(&param.name, args[idx..].join(", "));
+                break;
+            }
+
+            if !arg.is_empty() {
+                values.insert(&param.name, arg.to_string());
+            }
+
+            position += 1;
+        }
+
+        for param in &self.params {
+            if values.contains_key(param.name.as_str()) {
+                continue;
+            }
+
+            if param.required {
+                return Err(AssemblerError::InvalidOperand(format!(
+                    "Missing value for required parameter {} of macro {}",
+                    param.name,
+                    self.name
+                )));
+            }
+
+            values.insert(&param.name, param.default.clone().unwrap_or_default());
+        }
+
+        Ok(values)
+    }
+}
+
+// Parse the operands of a .macro directive: "name a, b=1, rest:vararg"
+// The name may be separated from the parameters by a space or a comma
+pub fn parse_macro_header(args: &str) -> Result<(String, Vec<MacroParam>), AssemblerError> {
+    let args = args.trim();
+    let end = args
+        .find(|c: char| c.is_whitespace() || c == ',')
+        .unwrap_or(args.len());
+    let name = &args[..end];
+
+    if !is_symbol_name(name) {
+        return Err(AssemblerError::InvalidDirective(format!(
+            "Expected a macro name for .macro but received '{}'",
+            name
+        )));
+    }
+
+    let rest = args[end..].trim_start().trim_start_matches(',');
+    let params = split_operands(rest)
+        .into_iter()
+        .map(MacroParam::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if params.iter().rev().skip(1).any(|param| param.vararg) {
+        return Err(AssemblerError::InvalidDirective(format!(
+            "Only the last parameter of macro {} can be :vararg",
+            name
+        )));
+    }
+
+    Ok((name.to_string(), params))
+}
+
+// Substitute a single parameter into a body, as done by .irp and .irpc
+pub fn substitute_param(body: &[String], name: &str, value: &str) -> Vec<String> {
+    let values = HashMap::from([(name, value.to_string())]);
+
+    body.iter()
+        .map(|line| substitute(line, &values, None))
+        .collect()
+}
+
+// Replace \param, \@ and \() in one line of a macro body
+// \@ is left alone outside of macros
+fn substitute(line: &str, values: &HashMap<&str, String>, counter: Option<usize>) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(idx) = rest.find('\\') {
+        result.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+
+        // \@ is the number of macros expanded so far
+        if let Some(counter) = counter && let Some(after) = rest.strip_prefix('@') {
+            result.push_str(&counter.to_string());
+            rest = after;
+            continue;
+        }
+
+        // \() separates a parameter from text that follows it
+        if let Some(after) = rest.strip_prefix("()") {
+            rest = after;
+            continue;
+        }
+
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+
+        match values.get(&rest[..len]) {
+            Some(value) => {
+                result.push_str(value);
+                rest = &rest[len..];
+            }
+
+            // Not a parameter, such as an escape inside a string
+            None => result.push('\\')
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
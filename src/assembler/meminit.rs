@@ -0,0 +1,154 @@
+//! Generates memory initialization files for FPGA block RAMs
+//!
+//! Verilog `$readmemh`/`$readmemb` text, Xilinx COE and Intel MIF files all hold one memory
+//! word per entry. Words are built from the little-endian bytes of the image and indexed from
+//! an origin address, so the first word at the origin is entry 0 of the memory.
+
+use std::fmt::Write;
+use crate::assembler::error::AssemblerError;
+use crate::assembler::section::{append_segment, Segment, MAX_PADDING};
+
+// Verilog text for $readmemh, one word in hex per line
+// With addresses, every run of words starts with an @index marker and gaps are skipped
+pub fn generate_readmemh(segments: &[Segment], origin: u32, width: usize, addresses: bool) -> Result<String, AssemblerError> {
+    generate_readmem(segments, origin, width, addresses, |word| format!("{:0digits$x}", word, digits = width * 2))
+}
+
+// Verilog text for $readmemb, one word in binary per line
+pub fn generate_readmemb(segments: &[Segment], origin: u32, width: usize, addresses: bool) -> Result<String, AssemblerError> {
+    generate_readmem(segments, origin, width, addresses, |word| format!("{:0digits$b}", word, digits = width * 8))
+}
+
+// Xilinx coefficient file with a hex initialization vector
+pub fn generate_coe(segments: &[Segment], origin: u32, width: usize) -> Result<String, AssemblerError> {
+    let words = flat_words(segments, origin, width)?;
+    let mut coe = String::from("memory_initialization_radix=16;\nmemory_initialization_vector=\n");
+
+    for (idx, word) in words.iter().enumerate() {
+        let end = if idx + 1 == words.len() { ';' } else { ',' };
+        writeln!(coe, "{:0digits$x}{}", word, end, digits = width * 2).unwrap();
+    }
+
+    // An empty vector still needs its terminator
+    if words.is_empty() {
+        coe.push_str(";\n");
+    }
+
+    Ok(coe)
+}
+
+// Intel memory initialization file with hex addresses and data
+pub fn generate_mif(segments: &[Segment], origin: u32, width: usize) -> Result<String, AssemblerError> {
+    let words = flat_words(segments, origin, width)?;
+    let mut mif = String::new();
+
+    writeln!(mif, "DEPTH = {};", words.len()).unwrap();
+    writeln!(mif, "WIDTH = {};", width * 8).unwrap();
+    mif.push_str("ADDRESS_RADIX = HEX;\nDATA_RADIX = HEX;\nCONTENT\nBEGIN\n");
+
+    for (idx, word) in words.iter().enumerate() {
+        writeln!(mif, "{:x} : {:0digits$x};", idx, word, digits = width * 2).unwrap();
+    }
+
+    mif.push_str("END;\n");
+    Ok(mif)
+}
+
+// Splits every word of `lanes` bytes across one memory per byte, lane N holding byte N of each word
+// The segments of a lane are addressed as origin + word index so they can be written with a width of 1
+pub fn split_byte_lanes(segments: &[Segment], origin: u32, lanes: usize) -> Vec<Vec<Segment>> {
+    let mut split = vec![Vec::new(); lanes];
+
+    for segment in segments {
+        for (idx, &byte) in segment.data.iter().enumerate() {
+            let offset = segment.address.wrapping_add(idx as u32).wrapping_sub(origin) as usize;
+            append_segment(&mut split[offset % lanes], origin + (offset / lanes) as u32, &[byte]);
+        }
+    }
+
+    split
+}
+
+fn generate_readmem(
+    segments: &[Segment],
+    origin: u32,
+    width: usize,
+    addresses: bool,
+    format_word: impl Fn(u64) -> String
+) -> Result<String, AssemblerError> {
+    let mut readmem = String::new();
+
+    for (start, words) in word_runs(segments, origin, width, addresses)? {
+        if addresses {
+            writeln!(readmem, "@{:x}", start).unwrap();
+        }
+
+        for word in words {
+            writeln!(readmem, "{}", format_word(word)).unwrap();
+        }
+    }
+
+    Ok(readmem)
+}
+
+// Every word from the origin to the end of the last segment, with zeros in the gaps
+fn flat_words(segments: &[Segment], origin: u32, width: usize) -> Result<Vec<u64>, AssemblerError> {
+    Ok(word_runs(segments, origin, width, false)?
+        .into_iter()
+        .next()
+        .map_or_else(Vec::new, |(_, words)| words))
+}
+
+// Runs of consecutive words and the index of their first word
+// Segments that don't start or end on a word boundary are padded with zeros,
+// and without `sparse` the gaps between segments are filled too so there is a single run,
+// as long as that doesn't take more zeros than a flat binary may hold
+fn word_runs(
+    segments: &[Segment],
+    origin: u32,
+    width: usize,
+    sparse: bool
+) -> Result<Vec<(u64, Vec<u64>)>, AssemblerError> {
+    let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut padding = 0;
+
+    for segment in segments {
+        let offset = segment.address.wrapping_sub(origin) as usize;
+
+        if !sparse {
+            padding += offset.saturating_sub(runs.last().map_or(0, |(start, bytes)| start + bytes.len()));
+
+            if padding > MAX_PADDING as usize {
+                return Err(AssemblerError::LayoutError(format!(
+                    "Segment at {:#x} is too far from the origin {:#x} to fill the gap with zeros (use --addresses with -O readmemh or readmemb)",
+                    segment.address,
+                    origin
+                )));
+            }
+        }
+
+        match runs.last_mut() {
+            Some((run_start, bytes)) if !sparse || offset - offset % width <= *run_start + bytes.len() => {
+                bytes.resize(offset - *run_start, 0);
+                bytes.extend_from_slice(&segment.data);
+            }
+            _ => {
+                let start = if sparse { offset - offset % width } else { 0 };
+                let mut bytes = vec![0; offset - start];
+                bytes.extend_from_slice(&segment.data);
+                runs.push((start, bytes));
+            }
+        }
+    }
+
+    Ok(runs.into_iter()
+        .map(|(start, bytes)| {
+            let words = bytes
+                .chunks(width)
+                .map(|chunk| chunk.iter().rev().fold(0u64, |word, &b| (word << 8) | b as u64))
+                .collect();
+
+            ((start / width) as u64, words)
+        })
+        .collect())
+}
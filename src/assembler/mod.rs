@@ -3,135 +3,1377 @@ mod error;
 pub mod instructions;
 pub mod encoder;
 pub mod hexdump;
+pub mod ihex;
+pub mod srec;
+pub mod meminit;
+pub mod embed;
 pub mod pseudo_instructions;
-mod csr;
-mod registers;
+pub mod directives;
+pub mod expr;
+pub mod macros;
+pub mod layout;
+pub mod listing;
+pub mod relocation;
+pub mod section;
+pub mod symbols;
+pub mod symbol_map;
+pub(crate) mod csr;
+pub(crate) mod registers;
 
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use crate::elf::{self, ElfContents};
 
 pub use error::AssemblerError;
 pub use parser::Parser;
 pub use encoder::*;
+pub use layout::MemoryLayout;
+pub use listing::{Listing, SourceLine};
+pub use section::{Section, SectionKind, Segment};
+pub use symbols::{Binding, Symbol, SymbolKind, SymbolTable, SymbolValue};
+pub use symbol_map::SymbolMap;
+
+use directives::{Condition, Directive};
+use expr::{evaluate, evaluate_constant};
+use macros::{substitute_param, Macro};
+use parser::{is_local_label, local_label_reference, split_instruction, split_label, strip_comment};
+
+// Register width of the target, which picks the ELF class of object files
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Xlen {
+    Rv32,
+    Rv64
+}
+
+// The assembler reads the source twice
+// Both passes go through the same line handling so that the addresses agree
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Pass {
+    Collect,  // Define labels and size every section
+    Emit      // Generate the machine code
+}
 
 pub struct Assembler {
     parser: Parser,
-    symbols: HashMap<String, u32>  // Store labels
+    xlen: Xlen,
+    base_address: u32,             // Address of the first section
+    layout: Option<MemoryLayout>,  // Places the sections instead of the base address
+    entry: Option<String>,         // Entry symbol of executables
+    relocatable: bool,                 // Assembling an object file: undefined symbols become relocations
+    executable: bool,                  // Assembling an ELF executable: segments start on their own page
+    symbols: HashMap<String, Symbol>,  // Store labels and constants
+    bindings: HashMap<String, Binding>,  // .globl, .local and .weak declarations
+    sections: Vec<Section>,
+    current_section: usize,
+    instruction_sizes: Vec<u32>,  // Bytes reserved for each instruction on the first pass
+    instruction_index: usize,
+    local_labels: Vec<(String, usize, u32)>,  // Numeric labels in source order: (number, section, offset)
+    local_position: usize,                    // Numeric labels defined so far in the current pass
+    pcrel_hi: HashMap<(usize, u32), (String, usize)>,  // %pcrel_hi target and local_position by auipc location
+    macros: HashMap<String, Macro>,
+    block: Option<(Block, usize)>,  // Block being recorded and the depth of nested blocks of its kind
+    macro_count: usize,             // Expansions so far, substituted for \@
+    macro_depth: usize,
+    conditions: Vec<Conditional>,   // Open .if blocks, innermost last
+    condition_results: Vec<bool>,   // Conditions evaluated on the first pass
+    condition_index: usize,
+    defines: HashMap<String, i64>,  // Constants defined outside the source (-D)
+    include_paths: Vec<PathBuf>,    // Searched after the directory of the including file (-I)
+    include_stack: Vec<PathBuf>,    // Files being assembled, outermost first
+    location: (String, usize),      // File and line being processed
+    listing: Option<Listing>        // Lines of the second pass, when a listing is wanted
+}
+
+// Lines recorded up to a closing directive instead of being assembled right away
+enum Block {
+    Macro(Macro),  // .macro ... .endm
+
+    // .rept, .irp and .irpc ... .endr: the body runs once per value
+    Repeat {
+        param: Option<String>,
+        values: Vec<String>,
+        body: Vec<String>,
+        line: usize
+    }
+}
+
+impl Block {
+    // Directives that open a nested block of the same kind and the one that closes it
+    fn delimiters(&self) -> (&'static [&'static str], &'static str) {
+        match self {
+            Block::Macro(_) => (&[".macro"], ".endm"),
+            Block::Repeat { .. } => (&[".rept", ".irp", ".irpc"], ".endr")
+        }
+    }
+
+    fn body_mut(&mut self) -> &mut Vec<String> {
+        match self {
+            Block::Macro(definition) => &mut definition.body,
+            Block::Repeat { body, .. } => body
+        }
+    }
+}
+
+// An open .if block
+#[derive(Debug, Copy, Clone)]
+struct Conditional {
+    active: bool,   // Lines of the current branch are assembled
+    done: bool,     // A branch was taken already, or the enclosing block is skipped
+    has_else: bool
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Assembler {
     pub fn new() -> Self {
         Self {
             parser: Parser::new(),
-            symbols: HashMap::new()
+            xlen: Xlen::Rv32,
+            base_address: 0,
+            layout: None,
+            entry: None,
+            relocatable: false,
+            executable: false,
+            symbols: HashMap::new(),
+            bindings: HashMap::new(),
+            sections: Vec::new(),
+            current_section: 0,
+            instruction_sizes: Vec::new(),
+            instruction_index: 0,
+            local_labels: Vec::new(),
+            local_position: 0,
+            pcrel_hi: HashMap::new(),
+            macros: HashMap::new(),
+            block: None,
+            macro_count: 0,
+            macro_depth: 0,
+            conditions: Vec::new(),
+            condition_results: Vec::new(),
+            condition_index: 0,
+            defines: HashMap::new(),
+            include_paths: Vec::new(),
+            include_stack: Vec::new(),
+            location: (String::new(), 0),
+            listing: None
         }
     }
 
     pub fn assemble(&mut self, path: &str) -> Result<Vec<u8>, AssemblerError> {
+        self.assemble_sections(path)?;
+        self.image()
+    }
+
+    // Assemble into an ELF relocatable object (.o) for the target XLEN
+    // References to other sections and undefined symbols are left to the linker as relocations
+    pub fn assemble_object(&mut self, path: &str) -> Result<Vec<u8>, AssemblerError> {
+        self.relocatable = true;
+        let result = self.assemble_sections(path);
+        self.relocatable = false;
+        result?;
+
+        for (name, binding) in &self.bindings {
+            if *binding == Binding::Local && !self.symbols.contains_key(name) {
+                return Err(AssemblerError::UndefinedLabel(format!(
+                    "{} is declared .local but never defined",
+                    name
+                )));
+            }
+        }
+
+        Ok(elf::object::write_object(&ElfContents::from(&*self)))
+    }
+
+    // Assemble into a static ELF executable that loads at the base address
+    pub fn assemble_executable(&mut self, path: &str) -> Result<Vec<u8>, AssemblerError> {
+        self.executable = true;
+        let result = self.assemble_sections(path);
+        self.executable = false;
+        result?;
+
+        let entry = self.entry_address()?;
+        Ok(elf::executable::write_executable(&ElfContents::from(&*self), entry))
+    }
+
+    // Assemble into the contents of each run of adjacent sections, for Intel HEX and S-records
+    pub fn assemble_segments(&mut self, path: &str) -> Result<Vec<Segment>, AssemblerError> {
+        self.assemble_sections(path)?;
+        Ok(section::segments(&self.sections))
+    }
+
+    // Where execution starts after assembling: the entry symbol, _start, or the first byte of .text
+    pub fn entry_address(&self) -> Result<u32, AssemblerError> {
+        let entry = self.entry.as_deref().or(self.layout.as_ref().and_then(|l| l.entry()));
+
+        match entry {
+            Some(name) => self.symbol_address(name).ok_or_else(|| {
+                AssemblerError::UndefinedLabel(format!("Entry symbol {} is not defined", name))
+            }),
+            None => Ok(self.symbol_address("_start").unwrap_or_else(|| {
+                self.sections
+                    .iter()
+                    .find(|s| s.name == ".text")
+                    .map_or(self.base_address, |s| s.address)
+            }))
+        }
+    }
+
+    fn assemble_sections(&mut self, path: &str) -> Result<(), AssemblerError> {
         // Collect labels on the first pass
-        let file = File::open(path)?;
-        self.collect_labels(file)?;
+        self.collect_labels(path)?;
+
+        // Place the sections now that their sizes are known
+        self.layout_sections()?;
+        let sizes: Vec<u32> = self.sections.iter().map(|s| s.size()).collect();
 
         // Generate the machine code on the second pass
-        let file = File::open(path)?;
-        let mut output = Vec::new();
-        let mut current_address = 0;
-
-        for line in BufReader::new(file).lines() {
-            // Skip comments or extract the asm before a comment
-            let line = line?;
-            let line = line.splitn(2, '#').next().unwrap().trim();
-
-            // Skip blank lines
-            if line.is_empty() {
-                continue;
-            }
-
-            // Handle label-only lines or lines with both label and instruction
-            if let Some(colon_index) = line.find(':') {
-                let code = line[colon_index + 1..].trim();
-                
-                // Check for label-only line
-                if code.is_empty() {
-                    continue;
-                }
-                
-                // Process the instruction after the label
-                self.process_instruction(code, &mut current_address, &mut output)?;
-            } else {
-                // No label
-                self.process_instruction(line, &mut current_address, &mut output)?;
+        self.run_pass(path, Pass::Emit)?;
+
+        for (section, size) in self.sections.iter().zip(sizes) {
+            if section.size() != size {
+                return Err(AssemblerError::ParseError(format!(
+                    "Size of {} changed between passes ({} -> {} bytes)",
+                    section.name,
+                    size,
+                    section.size()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // All sections in the order they were first used
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn symbols(&self) -> &HashMap<String, Symbol> {
+        &self.symbols
+    }
+
+    // Symbols declared .globl, .local or .weak, defined or not
+    pub fn bindings(&self) -> &HashMap<String, Binding> {
+        &self.bindings
+    }
+
+    pub fn xlen(&self) -> Xlen {
+        self.xlen
+    }
+
+    pub fn set_xlen(&mut self, xlen: Xlen) {
+        self.xlen = xlen;
+    }
+
+    // Address the sections are laid out from, 0 by default
+    pub fn set_base_address(&mut self, address: u32) {
+        self.base_address = address;
+    }
+
+    // Symbol executables start at instead of _start
+    pub fn set_entry(&mut self, name: &str) {
+        self.entry = Some(name.to_string());
+    }
+
+    // Place the sections in memory regions instead of from the base address (-T script)
+    pub fn set_layout(&mut self, layout: MemoryLayout) {
+        self.layout = Some(layout);
+    }
+
+    // Absolute address of a label after layout, or the value of an absolute symbol
+    // such as the ones a layout script defines
+    pub fn symbol_address(&self, name: &str) -> Option<u32> {
+        match self.symbols.get(name)? {
+            Symbol { kind: SymbolKind::Label { section }, value } => {
+                Some(self.sections[*section].address.wrapping_add(*value as u32))
             }
+            Symbol { kind: SymbolKind::Constant { .. }, value } => Some(*value as u32)
         }
+    }
+
+    // Define a constant before assembling, like `-D NAME=VALUE` or `.eqv NAME, VALUE`
+    pub fn define(&mut self, name: &str, value: i64) {
+        self.defines.insert(name.to_string(), value);
+    }
+
+    // Add a directory to search for .include and .incbin files, like `-I DIR`
+    pub fn add_include_path(&mut self, path: &str) {
+        self.include_paths.push(PathBuf::from(path));
+    }
+
+    // Record the lines of the next assembly for listing()
+    pub fn set_listing(&mut self, enabled: bool) {
+        self.listing = enabled.then(Listing::default);
+    }
+
+    // Listing of the last assembly with addresses, machine code, source text and the symbol table
+    pub fn listing(&self) -> Option<String> {
+        let symbols: Vec<(&str, Symbol)> = self.symbols
+            .iter()
+            .map(|(name, symbol)| (name.as_str(), *symbol))
+            .collect();
+
+        self.listing.as_ref().map(|listing| listing.render(&self.sections, &symbols))
+    }
+
+    // Source line of every address that holds code or data, when a listing was recorded
+    pub fn line_map(&self) -> Option<Vec<SourceLine>> {
+        self.listing.as_ref().map(Listing::line_map)
+    }
+
+    // Final addresses of the labels and values of the constants of the last assembly
+    pub fn symbol_map(&self) -> SymbolMap<'_> {
+        SymbolMap::new(&self.sections, &self.symbols, &self.bindings)
+    }
+
+    // Parser view of the symbol table during a pass
+    fn view(&self, pass: Pass) -> SymbolView<'_> {
+        SymbolView {
+            symbols: &self.symbols,
+            sections: &self.sections,
+            local_labels: &self.local_labels,
+            local_position: self.local_position,
+            pcrel_hi: &self.pcrel_hi,
+            pass,
 
-        Ok(output)
+            // Placeholder value of undefined symbols in object files, so that branches to them stay in range
+            undefined: (self.relocatable && pass == Pass::Emit)
+                .then(|| self.sections[self.current_section].current_address())
+        }
     }
 
-    fn collect_labels(&mut self, file: File) -> Result<(), AssemblerError> {
+    fn collect_labels(&mut self, path: &str) -> Result<(), AssemblerError> {
         self.symbols.clear();
-        let mut current_address = 0;
+        self.bindings.clear();
+        self.sections.clear();
+        self.instruction_sizes.clear();
+        self.local_labels.clear();
+        self.pcrel_hi.clear();
+        self.condition_results.clear();
+
+        for (name, &value) in &self.defines {
+            self.symbols.insert(name.clone(), Symbol {
+                kind: SymbolKind::Constant { redefinable: false },
+                value
+            });
+        }
+
+        self.run_pass(path, Pass::Collect)
+    }
+
+    fn run_pass(&mut self, path: &str, pass: Pass) -> Result<(), AssemblerError> {
+        for section in self.sections.iter_mut() {
+            section.reset();
+        }
+
+        self.instruction_index = 0;
+        self.local_position = 0;
+
+        // Macros are defined again on every pass so that \@ numbers the expansions the same way
+        self.macros.clear();
+        self.block = None;
+        self.macro_count = 0;
+
+        self.conditions.clear();
+        self.condition_index = 0;
+
+        // Code before any section directive goes into .text
+        self.switch_section(".text", SectionKind::Text);
+
+        self.include_stack.clear();
+        let lines = self.read_source(Path::new(path))?;
 
-        for line in BufReader::new(file).lines() {
-            let line = line?;
-            let line = line.splitn(2, '#').next().unwrap().trim();
+        self.location = (path.to_string(), 0);
 
-            // Skip blank lines
-            if line.is_empty() {
-                continue;
+        if let Some(listing) = self.listing.as_mut().filter(|_| pass == Pass::Emit) {
+            listing.start(path);
+        }
+
+        self.process_lines(&lines, 1, pass)?;
+
+        match &self.block {
+            Some((Block::Macro(definition), _)) => {
+                return Err(AssemblerError::InvalidDirective(format!(
+                    "Missing .endm for macro {} at {}:{}",
+                    definition.name,
+                    definition.file,
+                    definition.line
+                )));
             }
+            Some((Block::Repeat { line, .. }, _)) => {
+                return Err(AssemblerError::InvalidDirective(format!(
+                    "Missing .endr for the repetition at {}:{}",
+                    path,
+                    line
+                )));
+            }
+            None => {}
+        }
 
-            // Process labels
-            if let Some(colon_index) = line.find(':') {
-                let label = line[..colon_index].trim();
-                let code = line[colon_index + 1..].trim();
+        if !self.conditions.is_empty() {
+            return Err(AssemblerError::InvalidDirective(format!(
+                "Missing .endif for {} open conditional blocks",
+                self.conditions.len()
+            )));
+        }
 
-                // Add the label to our symbol table
-                if !label.is_empty() {
-                    self.symbols.insert(label.to_string(), current_address);
+        Ok(())
+    }
+
+    // Read a file and push it on the include stack, refusing files that include themselves
+    fn read_source(&mut self, path: &Path) -> Result<Vec<String>, AssemblerError> {
+        let canonical = path.canonicalize().map_err(|e| {
+            AssemblerError::IOError(format!("{}: {}", path.display(), e))
+        })?;
+
+        if self.include_stack.contains(&canonical) {
+            let chain: Vec<String> = self.include_stack
+                .iter()
+                .chain([&canonical])
+                .map(|file| file.display().to_string())
+                .collect();
+
+            return Err(AssemblerError::InvalidDirective(format!(
+                "Circular include: {}",
+                chain.join(" -> ")
+            )));
+        }
+
+        let lines = BufReader::new(File::open(path)?)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.include_stack.push(canonical);
+        Ok(lines)
+    }
+
+    // Assemble another file in place of an .include directive
+    // Errors are wrapped with the line inside the file and the include site
+    fn include(&mut self, name: &str, pass: Pass) -> Result<(), AssemblerError> {
+        let path = self.find_file(name)?;
+        let lines = self.read_source(&path)?;
+        let included_from = std::mem::replace(&mut self.location, (path.display().to_string(), 0));
+        let context = self.enter_listing(pass, |_| format!(
+            "{}, included from {}:{}",
+            path.display(),
+            included_from.0,
+            included_from.1
+        ));
+
+        let result = self.process_lines(&lines, 1, pass).map_err(|e| AssemblerError::Context(
+            format!(
+                "in {}:{}, included from {}:{}",
+                self.location.0,
+                self.location.1,
+                included_from.0,
+                included_from.1
+            ),
+            Box::new(e)
+        ));
+
+        self.include_stack.pop();
+        self.location = included_from;
+        self.leave_listing(context);
+
+        result
+    }
+
+    // Look for a file next to the current file, then in the include paths
+    fn find_file(&self, name: &str) -> Result<PathBuf, AssemblerError> {
+        let name = Path::new(name);
+
+        if name.is_absolute() {
+            return Ok(name.to_path_buf());
+        }
+
+        let current_dir = Path::new(&self.location.0).parent().unwrap_or(Path::new(""));
+
+        std::iter::once(current_dir)
+            .chain(self.include_paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| AssemblerError::IOError(format!(
+                "Can't find {} next to {} or in the include paths",
+                name.display(),
+                self.location.0
+            )))
+    }
+
+    // Process consecutive lines of the current file, numbered from first_line
+    fn process_lines(&mut self, lines: &[String], first_line: usize, pass: Pass) -> Result<(), AssemblerError> {
+        for (idx, line) in lines.iter().enumerate() {
+            self.location.1 = first_line + idx;
+
+            match self.listing.is_some() && pass == Pass::Emit {
+                true => self.process_listed_line(line)?,
+                false => self.process_line(line, pass)?
+            }
+        }
+
+        Ok(())
+    }
+
+    // Process a line of the second pass and record what it emitted for the listing
+    fn process_listed_line(&mut self, line: &str) -> Result<(), AssemblerError> {
+        let section = self.current_section;
+        let offset = self.sections[section].size();
+        let index = self.listing.as_mut().unwrap().begin(&self.location.0, self.location.1, line);
+
+        // Labels in the body of a macro or repetition being recorded aren't defined yet
+        let recorded = self.block.is_some();
+        self.process_line(line, Pass::Emit)?;
+
+        // A line that switched sections emitted nothing into the one it started in
+        if self.current_section == section {
+            let label = !recorded && split_label(strip_comment(line).trim()).0.is_some();
+            self.listing.as_mut().unwrap().finish(index, &self.sections[section], offset, label);
+        }
+
+        Ok(())
+    }
+
+    // Group the following lines of the listing under a header, returns the context to go back to
+    fn enter_listing(&mut self, pass: Pass, context: impl FnOnce(&Self) -> String) -> Option<usize> {
+        if self.listing.is_none() || pass != Pass::Emit {
+            return None;
+        }
+
+        let context = context(self);
+        self.listing.as_mut().map(|listing| listing.enter(context))
+    }
+
+    fn leave_listing(&mut self, context: Option<usize>) {
+        if let (Some(listing), Some(context)) = (self.listing.as_mut(), context) {
+            listing.leave(context);
+        }
+    }
+
+    fn process_line(&mut self, line: &str, pass: Pass) -> Result<(), AssemblerError> {
+        // Lines of a macro or repetition are only stored until the block ends
+        if self.block.is_some() {
+            return self.record_block_line(line, pass);
+        }
+
+        // Skip comments or extract the asm before a comment
+        let line = strip_comment(line).trim();
+
+        // Skip blank lines
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        // Handle label-only lines or lines with both label and instruction
+        let (label, code) = split_label(line);
+
+        // Conditionals are tracked even inside skipped blocks so that nesting works
+        let directive = code.split_whitespace().next().unwrap_or("");
+
+        if CONDITIONAL_DIRECTIVES.contains(&directive) {
+            return self.process_directive(code, pass);
+        }
+
+        if !self.conditions.iter().all(|c| c.active) {
+            return Ok(());
+        }
+
+        match label {
+            // Numeric labels may repeat, so they are kept in source order instead
+            Some(label) if is_local_label(label) => {
+                if pass == Pass::Collect {
+                    let offset = self.sections[self.current_section].size();
+                    self.local_labels.push((label.to_string(), self.current_section, offset));
                 }
 
-                // Process the instruction after the label if it exists
-                if !code.is_empty() {
-                    self.increment_address(line, &mut current_address);
+                self.local_position += 1;
+            }
+
+            // Add the label to our symbol table
+            Some(label) if pass == Pass::Collect => {
+                if self.symbols.contains_key(label) {
+                    return Err(AssemblerError::DuplicateSymbol(label.to_string()));
+                }
+
+                let section = &self.sections[self.current_section];
+
+                self.symbols.insert(label.to_string(), Symbol {
+                    kind: SymbolKind::Label { section: self.current_section },
+                    value: section.size() as i64
+                });
+            }
+
+            _ => {}
+        }
+
+        // Check for label-only line
+        if code.is_empty() {
+            return Ok(());
+        }
+
+        if code.starts_with('.') {
+            return self.process_directive(code, pass);
+        }
+
+        let (mnemonic, args) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+
+        if self.macros.contains_key(mnemonic) {
+            return self.expand_macro(mnemonic, args, pass);
+        }
+
+        match pass {
+            Pass::Collect => self.increment_address(code),
+            Pass::Emit => self.process_instruction(code)
+        }
+    }
+
+    fn process_directive(&mut self, src: &str, pass: Pass) -> Result<(), AssemblerError> {
+        match Directive::parse(src)? {
+            Directive::Section { name, kind } => self.switch_section(&name, kind),
+
+            Directive::Data { size, values } => {
+                for value in values {
+                    let bytes = match pass {
+                        // Labels may not be defined yet, only the size matters
+                        Pass::Collect => vec![0; size as usize],
+                        Pass::Emit => {
+                            let address = self.location(Pass::Emit);
+                            let relocation = match self.relocatable {
+                                true => self.data_relocation(&value, size, address)?,
+                                false => None
+                            };
+
+                            // Relocated values are filled in by the linker
+                            let value = match relocation {
+                                Some(relocation) => {
+                                    self.sections[self.current_section].relocations.push(relocation);
+                                    0
+                                }
+                                None => self.resolve_value(&value)?
+                            };
+
+                            check_data_range(value, size)?;
+                            value.to_le_bytes()[..size as usize].to_vec()
+                        }
+                    };
+
+                    self.emit_bytes(&bytes, pass)?;
+                }
+            }
+
+            Directive::Ascii { bytes } => self.emit_bytes(&bytes, pass)?,
+
+            Directive::Equ { name, value, redefinable } => {
+                self.define_constant(&name, &value, redefinable, pass)?
+            }
+
+            Directive::Align { alignment, power_of_two, fill, max_skip } => {
+                let alignment = self.resolve_count(&alignment)?;
+                let alignment = if power_of_two {
+                    if alignment >= 32 {
+                        return Err(AssemblerError::InvalidDirective(format!(
+                            "Alignment 2^{} is too large",
+                            alignment
+                        )));
+                    }
+
+                    1 << alignment
+                } else {
+                    alignment.max(1)
+                };
+
+                if !alignment.is_power_of_two() {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Alignment must be a power of two but received {}",
+                        alignment
+                    )));
+                }
+
+                let fill = fill.map(|f| self.resolve_constant(&f)).transpose()?;
+                let max_skip = max_skip.map(|m| self.resolve_count(&m)).transpose()?;
+                self.align(alignment, fill, max_skip, pass)?;
+            }
+
+            Directive::Fill { repeat, size, value } => {
+                let repeat = self.resolve_count(&repeat)?;
+                let size = size.map(|s| self.resolve_count(&s)).transpose()?.unwrap_or(1);
+                let value = value.map(|v| self.resolve_constant(&v)).transpose()?.unwrap_or(0);
+
+                if size > 8 {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Fill size must be at most 8 bytes but received {}",
+                        size
+                    )));
+                }
+
+                let pattern = &value.to_le_bytes()[..size as usize];
+                self.emit_bytes(&pattern.repeat(repeat as usize), pass)?;
+            }
+
+            Directive::Macro { name, params } => {
+                let (file, line) = self.location.clone();
+                let definition = Macro { name, params, body: Vec::new(), file, line };
+
+                self.block = Some((Block::Macro(definition), 0));
+            }
+
+            Directive::Endm => {
+                return Err(AssemblerError::InvalidDirective(".endm without .macro".to_string()));
+            }
+
+            Directive::Purgem { name } => {
+                if self.macros.remove(&name).is_none() {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Can't purge undefined macro {}",
+                        name
+                    )));
+                }
+            }
+
+            Directive::If { condition } => {
+                // Nested conditions in a skipped block aren't evaluated
+                let enclosing = self.conditions.iter().all(|c| c.active);
+                let taken = enclosing && self.condition(condition, pass)?;
+
+                self.conditions.push(Conditional {
+                    active: taken,
+                    done: taken || !enclosing,
+                    has_else: false
+                });
+            }
+
+            Directive::ElseIf { expr } => {
+                let current = self.current_conditional(".elseif")?;
+                let taken = !current.done && self.condition(Condition::NonZero(expr), pass)?;
+
+                let current = self.conditions.last_mut().unwrap();
+                current.active = taken;
+                current.done |= taken;
+            }
+
+            Directive::Else => {
+                self.current_conditional(".else")?;
+
+                let current = self.conditions.last_mut().unwrap();
+                current.active = !current.done;
+                current.done = true;
+                current.has_else = true;
+            }
+
+            Directive::EndIf => {
+                if self.conditions.pop().is_none() {
+                    return Err(AssemblerError::InvalidDirective(".endif without .if".to_string()));
+                }
+            }
+
+            Directive::Include { path } => self.include(&path, pass)?,
+
+            Directive::Incbin { path, offset, length } => {
+                let path = self.find_file(&path)?;
+                let data = fs::read(&path)?;
+
+                let offset = offset.map(|o| self.resolve_count(&o)).transpose()?.unwrap_or(0) as usize;
+                let end = match length {
+                    Some(length) => offset.checked_add(self.resolve_count(&length)? as usize),
+                    None => Some(data.len())
+                };
+
+                let bytes = end
+                    .filter(|&end| offset <= end && end <= data.len())
+                    .map(|end| &data[offset..end])
+                    .ok_or_else(|| AssemblerError::InvalidDirective(format!(
+                        "{} is only {} bytes long",
+                        path.display(),
+                        data.len()
+                    )))?;
+
+                self.emit_bytes(bytes, pass)?;
+            }
+
+            Directive::Rept { count } => {
+                let count = self.resolve_count(&count)?;
+
+                self.block = Some((Block::Repeat {
+                    param: None,
+                    values: vec![String::new(); count as usize],
+                    body: Vec::new(),
+                    line: self.location.1
+                }, 0));
+            }
+
+            Directive::Repeat { param, values } => {
+                self.block = Some((Block::Repeat {
+                    param: Some(param),
+                    values,
+                    body: Vec::new(),
+                    line: self.location.1
+                }, 0));
+            }
+
+            Directive::Endr => {
+                return Err(AssemblerError::InvalidDirective(".endr without .rept, .irp or .irpc".to_string()));
+            }
+
+            Directive::Binding { binding, names } => {
+                for name in names {
+                    if pass == Pass::Collect {
+                        self.bindings.insert(name, binding);
+                    }
                 }
-            } else {
-                // No label
-                self.increment_address(line, &mut current_address);
             }
         }
 
         Ok(())
     }
 
-    // Helper function to process a single instruction and update the address
-    fn process_instruction(
-        &self,
-        src: &str,
-        addr: &mut u32,
-        out: &mut Vec<u8>
-    ) -> Result<(), AssemblerError> {
-        match self.parser.parse_line(src, *addr, &self.symbols) {
-            Ok(instructions) => {
-                for word in instructions {
-                    out.extend_from_slice(&word.to_le_bytes());
-                    *addr += 4;
+    // Store a line of the block being recorded, or run the block once its closing directive is reached
+    fn record_block_line(&mut self, line: &str, pass: Pass) -> Result<(), AssemblerError> {
+        let (_, code) = split_label(strip_comment(line).trim());
+        let directive = code.split_whitespace().next().unwrap_or("");
+        let Some((block, depth)) = self.block.as_mut() else {
+            return Ok(());
+        };
+        let (openers, terminator) = block.delimiters();
+
+        if openers.contains(&directive) {
+            *depth += 1;
+        } else if directive == terminator && *depth > 0 {
+            *depth -= 1;
+        } else if directive == terminator {
+            let (block, _) = self.block.take().unwrap();
+            return self.finish_block(block, pass);
+        }
+
+        block.body_mut().push(line.to_string());
+        Ok(())
+    }
+
+    fn finish_block(&mut self, block: Block, pass: Pass) -> Result<(), AssemblerError> {
+        match block {
+            Block::Macro(definition) => {
+                if self.macros.contains_key(&definition.name) {
+                    return Err(AssemblerError::InvalidDirective(format!(
+                        "Macro {} is already defined",
+                        definition.name
+                    )));
+                }
+
+                self.macros.insert(definition.name.clone(), definition);
+            }
+
+            Block::Repeat { param, values, body, line } => {
+                let end = self.location.1;
+                let count = values.len();
+
+                for (idx, value) in values.into_iter().enumerate() {
+                    let lines = match &param {
+                        Some(param) => substitute_param(&body, param, &value),
+                        None => body.clone()
+                    };
+
+                    let context = self.enter_listing(pass, |this| format!(
+                        "repetition {} of {} at {}:{}",
+                        idx + 1,
+                        count,
+                        this.location.0,
+                        line
+                    ));
+                    let result = self.process_lines(&lines, line + 1, pass);
+                    self.leave_listing(context);
+                    result?;
                 }
 
+                self.location.1 = end;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The innermost .if block, which must not have had its .else yet
+    fn current_conditional(&self, directive: &str) -> Result<Conditional, AssemblerError> {
+        match self.conditions.last() {
+            Some(current) if !current.has_else => Ok(*current),
+            Some(_) => Err(AssemblerError::InvalidDirective(format!("{} after .else", directive))),
+            None => Err(AssemblerError::InvalidDirective(format!("{} without .if", directive)))
+        }
+    }
+
+    // Conditions are evaluated on the first pass and replayed on the second
+    // so that both passes assemble the same lines
+    fn condition(&mut self, condition: Condition, pass: Pass) -> Result<bool, AssemblerError> {
+        if pass == Pass::Emit {
+            let result = self.condition_results[self.condition_index];
+            self.condition_index += 1;
+            return Ok(result);
+        }
+
+        let result = match condition {
+            Condition::NonZero(expr) => self.resolve_constant(&expr)? != 0,
+            Condition::Zero(expr) => self.resolve_constant(&expr)? == 0,
+            Condition::Defined(name) => self.symbols.contains_key(&name),
+            Condition::Undefined(name) => !self.symbols.contains_key(&name),
+            Condition::Blank(text) => text.trim().is_empty(),
+            Condition::NotBlank(text) => !text.trim().is_empty()
+        };
+
+        self.condition_results.push(result);
+        Ok(result)
+    }
+
+    // Process the body of a macro with the invocation's arguments substituted
+    // Errors are wrapped with the line inside the macro and the invocation site
+    fn expand_macro(&mut self, name: &str, args: &str, pass: Pass) -> Result<(), AssemblerError> {
+        if self.macro_depth >= MAX_MACRO_DEPTH {
+            return Err(AssemblerError::InvalidDirective(format!(
+                "Macros nested more than {} levels deep while expanding {}",
+                MAX_MACRO_DEPTH,
+                name
+            )));
+        }
+
+        let definition = &self.macros[name];
+        let lines = definition.expand(args, self.macro_count)?;
+        let first_line = definition.line + 1;
+        let invocation = std::mem::replace(&mut self.location, (definition.file.clone(), first_line));
+        let context = self.enter_listing(pass, |this| format!(
+            "macro {} from {}:{}, invoked at {}:{}",
+            name,
+            this.location.0,
+            first_line - 1,
+            invocation.0,
+            invocation.1
+        ));
+
+        self.macro_count += 1;
+        self.macro_depth += 1;
+
+        let result = self.process_lines(&lines, first_line, pass);
+
+        let result = result.map_err(|e| AssemblerError::Context(
+            format!(
+                "in macro {} at {}:{}, invoked at {}:{}",
+                name,
+                self.location.0,
+                self.location.1,
+                invocation.0,
+                invocation.1
+            ),
+            Box::new(e)
+        ));
+
+        self.macro_depth -= 1;
+        self.location = invocation;
+        self.leave_listing(context);
+
+        result
+    }
+
+    // Append raw bytes to the current section (only sized on the first pass)
+    fn emit_bytes(&mut self, bytes: &[u8], pass: Pass) -> Result<(), AssemblerError> {
+        let section = &mut self.sections[self.current_section];
+
+        match pass {
+            Pass::Collect => {
+                section.reserve(bytes.len() as u32);
                 Ok(())
             }
+            Pass::Emit => section.emit(bytes)
+        }
+    }
+
+    // Pad the current section up to a multiple of alignment
+    // Executable sections are padded with nops unless a fill byte is given
+    fn align(
+        &mut self,
+        alignment: u32,
+        fill: Option<i64>,
+        max_skip: Option<u32>,
+        pass: Pass
+    ) -> Result<(), AssemblerError> {
+        let section = &mut self.sections[self.current_section];
+        let offset = section.size();
+        let padding = align_up(offset, alignment) - offset;
+
+        // Skip the alignment entirely if it needs too much padding
+        if max_skip.is_some_and(|max| padding > max) {
+            return Ok(());
+        }
+
+        // The section start has to be at least as aligned as anything inside it
+        section.align = section.align.max(alignment);
+
+        let bytes = match fill {
+            Some(fill) => vec![fill as u8; padding as usize],
+            None if section.kind == SectionKind::Text => {
+                // Zero bytes up to the next word boundary, then nops
+                let zeros = align_up(offset, 4).min(offset + padding) - offset;
+                let mut bytes = vec![0; zeros as usize];
+
+                while (bytes.len() as u32) < padding {
+                    bytes.extend_from_slice(&NOP.to_le_bytes());
+                }
+
+                bytes
+            }
+            None => vec![0; padding as usize]
+        };
+
+        self.emit_bytes(&bytes, pass)
+    }
+
+    // .equ/.set/.eqv: define a named constant or an alias of a label
+    fn define_constant(
+        &mut self,
+        name: &str,
+        value: &str,
+        redefinable: bool,
+        pass: Pass
+    ) -> Result<(), AssemblerError> {
+        // Redefinitions are checked once, on the first pass
+        if pass == Pass::Collect && let Some(existing) = self.symbols.get(name) {
+            let allowed = matches!(existing.kind, SymbolKind::Constant { redefinable: true }) && redefinable;
+
+            if !allowed {
+                return Err(AssemblerError::DuplicateSymbol(name.to_string()));
+            }
+        }
+
+        let symbol = match self.symbols.get(value) {
+            Some(label) if label.is_label() => *label,
+            _ => match evaluate(value, self.location(pass), &self.view(pass)) {
+                Ok(SymbolValue::Constant(value)) => Symbol {
+                    kind: SymbolKind::Constant { redefinable },
+                    value
+                },
+                Ok(SymbolValue::Address(address)) => self.label_at(address, pass),
+
+                // The value may depend on labels, try again on the second pass
+                Err(_) if pass == Pass::Collect => return Ok(()),
+                Err(e) => return Err(e)
+            }
+        };
+
+        self.symbols.insert(name.to_string(), symbol);
+        Ok(())
+    }
+
+    // Label symbol for an address computed by an expression
+    fn label_at(&self, address: u32, pass: Pass) -> Symbol {
+        // Only '.' yields an address on the first pass, which is relative to the current section
+        let section = match pass {
+            Pass::Collect => self.current_section,
+            Pass::Emit => self.sections
+                .iter()
+                .position(|s| (s.address..s.address + s.size()).contains(&address))
+                .unwrap_or(self.current_section)
+        };
+
+        let base = match pass {
+            Pass::Collect => 0,
+            Pass::Emit => self.sections[section].address
+        };
+
+        Symbol {
+            kind: SymbolKind::Label { section },
+            value: address.wrapping_sub(base) as i64
+        }
+    }
+
+    // Location counter as seen by expressions ('.')
+    // Absolute on the second pass, section-relative on the first
+    fn location(&self, pass: Pass) -> u32 {
+        let section = &self.sections[self.current_section];
+
+        match pass {
+            Pass::Collect => section.size(),
+            Pass::Emit => section.current_address()
+        }
+    }
+
+    // Resolve a value that must be known on the first pass (counts, sizes, fill values)
+    fn resolve_constant(&self, value: &str) -> Result<i64, AssemblerError> {
+        let location = self.location(Pass::Collect);
+
+        evaluate_constant(value, location, &self.view(Pass::Collect)).map_err(|e| match e {
+            AssemblerError::UndefinedLabel(name) => AssemblerError::UndefinedLabel(format!(
+                "{} must be a constant defined before it is used in {}",
+                name,
+                value
+            )),
+            e => e
+        })
+    }
+
+    // Resolve a non-negative constant such as a repeat count or an alignment
+    fn resolve_count(&self, value: &str) -> Result<u32, AssemblerError> {
+        let count = self.resolve_constant(value)?;
+
+        u32::try_from(count).map_err(|_| AssemblerError::InvalidOperand(format!(
+            "Expected a non-negative count but received {}",
+            value
+        )))
+    }
+
+    // Resolve a data value that is either a constant or an address
+    fn resolve_value(&self, value: &str) -> Result<i64, AssemblerError> {
+        match evaluate(value, self.location(Pass::Emit), &self.view(Pass::Emit))? {
+            SymbolValue::Constant(value) => Ok(value),
+            SymbolValue::Address(address) => Ok(address as i64)
+        }
+    }
+
+    // Make the named section current, creating it on first use
+    fn switch_section(&mut self, name: &str, kind: SectionKind) {
+        self.current_section = match self.sections.iter().position(|s| s.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.sections.push(Section::new(name, kind));
+                self.sections.len() - 1
+            }
+        };
+    }
+
+    // Place the sections as the memory layout says, or one after the other from the base address
+    // Object files are laid out from the base address since the linker places them
+    fn layout_sections(&mut self) -> Result<(), AssemblerError> {
+        let layout = match &self.layout {
+            Some(layout) if !self.relocatable => layout,
+            _ => {
+                let page_size = self.executable.then_some(elf::PAGE_SIZE as u32);
+                return section::layout(&mut self.sections, self.base_address, page_size);
+            }
+        };
+
+        let symbols = &self.symbols;
+        let defined = layout.place(&mut self.sections, &|name| symbols.contains_key(name))?;
+
+        for (name, value) in defined {
+            if self.symbols.contains_key(&name) {
+                return Err(AssemblerError::DuplicateSymbol(format!(
+                    "{} is defined by both the source and the memory layout",
+                    name
+                )));
+            }
 
-            Err(e) => Err(e)
+            self.symbols.insert(name, Symbol {
+                kind: SymbolKind::Constant { redefinable: false },
+                value: value as i64
+            });
         }
+
+        Ok(())
+    }
+
+    // Flatten every section with contents into a single image starting at the base address,
+    // or at the lowest section address when a memory layout places them
+    fn image(&self) -> Result<Vec<u8>, AssemblerError> {
+        let base_address = match self.layout {
+            Some(_) => section::lowest_address(&self.sections),
+            None => self.base_address
+        };
+
+        section::flatten(&self.sections, base_address)
+    }
+
+    // Helper function to process a single instruction and update the address
+    fn process_instruction(&mut self, src: &str) -> Result<(), AssemblerError> {
+        let address = self.sections[self.current_section].current_address();
+        let mut instructions = self.parser.parse_line(src, address, &self.view(Pass::Emit))?;
+
+        let relocations = match self.relocatable {
+            true => self.instruction_relocations(src, address)?,
+            false => Vec::new()
+        };
+
+        // The first pass may have reserved room for a longer expansion (li with a forward reference)
+        let reserved = self.instruction_sizes[self.instruction_index];
+        self.instruction_index += 1;
+
+        let size = instructions.len() as u32 * 4;
+
+        if size > reserved {
+            return Err(AssemblerError::ParseError(format!(
+                "{} needs {} bytes but only {} were reserved on the first pass",
+                src,
+                size,
+                reserved
+            )));
+        }
+
+        if let Some(listing) = &mut self.listing {
+            listing.mark_instruction();
+        }
+
+        let section = &mut self.sections[self.current_section];
+
+        // Leave the relocated immediates for the linker
+        for relocation in relocations {
+            let first = ((relocation.offset - section.size()) / 4) as usize;
+
+            for (word, mask) in instructions[first..].iter_mut().zip(relocation.kind.masks()) {
+                *word &= !mask;
+            }
+
+            section.relocations.push(relocation);
+        }
+
+        for word in instructions {
+            section.emit(&word.to_le_bytes())?;
+        }
+
+        for _ in 0..(reserved - size) / 4 {
+            section.emit(&NOP.to_le_bytes())?;
+        }
+
+        Ok(())
     }
 
     // Helper function to increment the address
     // Necessary to handle pseudo-instructions that split into multiple base instructions
-    fn increment_address(&self, src: &str, addr: &mut u32) {
-        match self.parser.parse_line(src, *addr, &HashMap::new()) {
-            // Ensures symbols have correct addresses
-            Ok(instructions) => *addr += instructions.len() as u32 * 4,
-            Err(_) => *addr += 4
+    fn increment_address(&mut self, src: &str) -> Result<(), AssemblerError> {
+        let size = self.parser.instruction_size(src, &self.view(Pass::Collect));
+
+        // Remember what each auipc points to so that %pcrel_lo can refer back to it
+        let (mnemonic, operands) = split_instruction(src);
+
+        if mnemonic == "auipc" && let Some(target) = operands.get(1)
+            .and_then(|operand| operand.strip_prefix("%pcrel_hi("))
+            .and_then(|operand| operand.strip_suffix(')'))
+        {
+            let section = &self.sections[self.current_section];
+            self.pcrel_hi.insert(
+                (self.current_section, section.size()),
+                (target.to_string(), self.local_position)
+            );
         }
+
+        self.instruction_sizes.push(size);
+        self.sections[self.current_section].reserve(size);
+
+        Ok(())
+    }
+}
+
+// addi x0, x0, 0
+const NOP: u32 = 0x00000013;
+
+// Guards against macros that invoke themselves without end
+const MAX_MACRO_DEPTH: usize = 100;
+
+const CONDITIONAL_DIRECTIVES: [&str; 11] = [
+    ".if", ".ifeq", ".ifne", ".ifdef", ".ifndef", ".ifnotdef", ".ifb", ".ifnb",
+    ".elseif", ".else", ".endif"
+];
+
+// Data values must fit the directive width as either a signed or an unsigned number
+fn check_data_range(value: i64, size: u32) -> Result<(), AssemblerError> {
+    if size >= 8 {
+        return Ok(());
+    }
+
+    let bits = size * 8;
+    let min = -(1i64 << (bits - 1));
+    let max = (1i64 << bits) - 1;
+
+    if !(min..=max).contains(&value) {
+        return Err(AssemblerError::InvalidOperand(format!(
+            "Value {} does not fit in {} bytes",
+            value,
+            size
+        )));
     }
-}
\ No newline at end of file
+
+    Ok(())
+}
+
+// Round an address up to the next multiple of align
+pub(crate) fn align_up(address: u32, align: u32) -> u32 {
+    if align <= 1 {
+        address
+    } else {
+        address.div_ceil(align) * align
+    }
+}
+
+// Resolves symbols for the parser
+// Label addresses are only final once the sections have been placed after the first pass
+#[derive(Copy, Clone)]
+struct SymbolView<'a> {
+    symbols: &'a HashMap<String, Symbol>,
+    sections: &'a [Section],
+    local_labels: &'a [(String, usize, u32)],
+    local_position: usize,
+    pcrel_hi: &'a HashMap<(usize, u32), (String, usize)>,
+    pass: Pass,
+    undefined: Option<u32>  // Address of symbols that aren't defined, only while assembling an object file
+}
+
+impl SymbolView<'_> {
+    // Nearest definition of a numeric label after ("1f") or before ("1b") the current position
+    fn resolve_local(&self, number: &str, forward: bool) -> Option<(usize, u32)> {
+        let (before, after) = self.local_labels.split_at(self.local_position.min(self.local_labels.len()));
+
+        let (_, section, offset) = if forward {
+            after.iter().find(|(n, ..)| n == number)?
+        } else {
+            before.iter().rev().find(|(n, ..)| n == number)?
+        };
+
+        Some((*section, *offset))
+    }
+
+    // Section and section-relative offset of a label
+    fn locate(&self, name: &str) -> Option<(usize, u32)> {
+        if let Some((number, forward)) = local_label_reference(name) {
+            return self.resolve_local(number, forward);
+        }
+
+        match self.symbols.get(name)? {
+            Symbol { kind: SymbolKind::Label { section }, value } => Some((*section, *value as u32)),
+            _ => None
+        }
+    }
+}
+
+impl SymbolTable for SymbolView<'_> {
+    fn resolve(&self, name: &str) -> Option<SymbolValue> {
+        if let Some((number, forward)) = local_label_reference(name) {
+            return match self.pass {
+                Pass::Collect => None,
+                Pass::Emit => self.resolve_local(number, forward).map(|(section, offset)| {
+                    SymbolValue::Address(self.sections[section].address.wrapping_add(offset))
+                })
+            };
+        }
+
+        let Some(symbol) = self.symbols.get(name) else {
+            return self.undefined.map(SymbolValue::Address);
+        };
+
+        match symbol.kind {
+            SymbolKind::Label { .. } if self.pass == Pass::Collect => None,
+            SymbolKind::Label { section } => Some(SymbolValue::Address(
+                self.sections[section].address.wrapping_add(symbol.value as u32)
+            )),
+            SymbolKind::Constant { .. } => Some(SymbolValue::Constant(symbol.value))
+        }
+    }
+
+    fn pcrel_hi_target(&self, address: u32) -> Option<SymbolValue> {
+        if self.pass == Pass::Collect {
+            return None;
+        }
+
+        let (_, (target, local_position)) = self.pcrel_hi.iter().find(|((section, offset), _)| {
+            self.sections[*section].address.wrapping_add(*offset) == address
+        })?;
+
+        // Evaluated as if it were the operand of the auipc
+        let view = SymbolView { local_position: *local_position, ..*self };
+        evaluate(target, address, &view).ok()
+    }
+}
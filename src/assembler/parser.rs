@@ -4,9 +4,11 @@ use std::collections::HashMap;
 use crate::assembler::csr::CSR_ADDRESSES;
 use crate::assembler::encoder::*;
 use crate::assembler::error::AssemblerError;
+use crate::assembler::expr::{evaluate, evaluate_constant};
 use crate::assembler::instructions::{InstructionFormat, InstructionSet, InstructionType};
-use crate::assembler::pseudo_instructions::PseudoInstructions;
+use crate::assembler::pseudo_instructions::{PseudoInstructions, TranslatedInstruction};
 use crate::assembler::registers::ABI_NAME_REGISTERS;
+use crate::assembler::symbols::{SymbolTable, SymbolValue};
 
 static NO_OPERAND_INSTRUCTIONS: phf::Set<&'static str> = phf::phf_set! {
     "nop",
@@ -19,6 +21,12 @@ pub struct Parser {
     instructions: InstructionSet
 }
 
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Parser {
     pub fn new() -> Self {
         Self {
@@ -30,19 +38,17 @@ impl Parser {
         &self,
         mut line: &str,
         current_address: u32,
-        symbols: &HashMap<String, u32>
+        symbols: &dyn SymbolTable
     ) -> Result<Vec<u32>, AssemblerError> {
         // Skip comments or extract the code before a comment
-        line = line.split('#').next().unwrap().trim();
+        line = strip_comment(line).trim();
 
         if line.is_empty() {
             return Ok(vec![]);
         }
 
         // Label detection
-        if let Some(colon_index) = line.find(':') {
-            let after_label = &line[colon_index + 1..].trim();
-
+        if let (Some(_), after_label) = split_label(line) {
             if after_label.is_empty() {
                 // No instruction after label
                 return Ok(vec![]);
@@ -51,14 +57,47 @@ impl Parser {
             line = after_label;
         }
 
-        // "add x4, x5, x6" -> vec!["add", "x4", "x5", "x6"]
-        let parts: Vec<&str> = line.split_whitespace()
-            .map(|s| s.trim_end_matches(','))
-            .collect();
-        
-        // Holds the instruction and operands
-        let mnemonic = parts[0];
-        let operands = &parts[1..];
+        let translated = self.expand_line(line, current_address, symbols)?;
+        let mut result = Vec::with_capacity(translated.len());
+
+        for (idx, instr) in translated.iter().enumerate() {
+            let instr_operands: Vec<&str> = instr
+                .operands
+                .iter()
+                .map(|s| s.as_str()).collect();
+
+            let instr_format = self.instructions.get_instruction(instr.mnemonic)
+                .ok_or_else(|| AssemblerError::InvalidInstruction(instr.mnemonic.to_string()))?;
+
+            // Calculate the address offset for each instruction in the expansion
+            let instr_address = current_address + (idx as u32 * 4);
+
+            let parsed = match instr_format.fmt {
+                InstructionType::R => self.parse_r_type(instr_format, &instr_operands)?,
+                InstructionType::I => self.parse_i_type(instr_format, &instr_operands, instr_address, symbols)?,
+                InstructionType::S => self.parse_s_type(instr_format, &instr_operands, instr_address, symbols)?,
+                InstructionType::B => self.parse_b_type(instr_format, &instr_operands, instr_address, symbols)?,
+                InstructionType::U => self.parse_u_type(instr_format, &instr_operands, instr_address, symbols)?,
+                InstructionType::J => self.parse_j_type(instr_format, &instr_operands, instr_address, symbols)?,
+            };
+
+            result.push(parsed);
+        }
+
+        Ok(result)
+    }
+
+    // The base instructions an instruction line (without a label) stands for
+    // Pseudo-instructions are translated, base instructions are returned as they are
+    pub fn expand_line<'a>(
+        &self,
+        line: &'a str,
+        current_address: u32,
+        symbols: &dyn SymbolTable
+    ) -> Result<Vec<TranslatedInstruction<'a>>, AssemblerError> {
+        // "add x4, x5, x6" -> ("add", vec!["x4", "x5", "x6"])
+        let (mnemonic, operands) = split_instruction(line);
+        let operands = operands.as_slice();
 
         // Throw an error if operands are empty unless the instruction is nop or ret
         if operands.is_empty() && !NO_OPERAND_INSTRUCTIONS.contains(mnemonic) {
@@ -66,55 +105,30 @@ impl Parser {
         }
 
         // Check for a pseudo-instruction first
-        if PseudoInstructions::is_pseudo_instruction(mnemonic, operands.len()) {
-            let translated = PseudoInstructions::expand(mnemonic, operands)?;
-            
-            // Handle multiple expanded instructions
-            let mut result = Vec::with_capacity(translated.len());
-            
-            // Loop through each item in translated vec
-            for (idx, instr) in translated.iter().enumerate() {
-                let instr_operands: Vec<&str> = instr
-                    .operands
-                    .iter()
-                    .map(|s| s.as_str()).collect();
-
-                if let Some(instr_format) = self.instructions.get_instruction(instr.mnemonic) {
-                    // Calculate the address offset for each instruction in the expansion
-                    let instr_address = current_address + (idx as u32 * 4);
-                    
-                    let parsed = match instr_format.fmt {
-                        InstructionType::R => self.parse_r_type(instr_format, &instr_operands)?,
-                        InstructionType::I => self.parse_i_type(instr_format, &instr_operands)?,
-                        InstructionType::S => self.parse_s_type(instr_format, &instr_operands)?,
-                        InstructionType::B => self.parse_b_type(instr_format, &instr_operands, instr_address, symbols)?,
-                        InstructionType::U => self.parse_u_type(instr_format, &instr_operands, symbols)?,
-                        InstructionType::J => self.parse_j_type(instr_format, &instr_operands, instr_address, symbols)?,
-                    };
-                    
-                    result.push(parsed);
-                } else {
-                    return Err(AssemblerError::InvalidInstruction(instr.mnemonic.to_string()));
-                }
-            }
-            
-            return Ok(result);
+        if PseudoInstructions::is_pseudo_instruction(mnemonic, operands) {
+            return PseudoInstructions::expand(mnemonic, operands, current_address, symbols);
         }
 
-        // Handle base instructions
-        let instr = self.instructions.get_instruction(mnemonic)
-            .ok_or_else(|| AssemblerError::InvalidInstruction(mnemonic.to_string()))?.clone();
-
-        let parsed = match instr.fmt {
-            InstructionType::R => self.parse_r_type(&instr, operands)?,
-            InstructionType::I => self.parse_i_type(&instr, operands)?,
-            InstructionType::S => self.parse_s_type(&instr, operands)?,
-            InstructionType::B => self.parse_b_type(&instr, operands, current_address, symbols)?,
-            InstructionType::U => self.parse_u_type(&instr, operands, symbols)?,
-            InstructionType::J => self.parse_j_type(&instr, operands, current_address, symbols)?,
-        };
-        
-        Ok(vec![parsed])
+        Ok(vec![TranslatedInstruction {
+            mnemonic,
+            operands: operands.iter().map(|op| op.to_string()).collect()
+        }])
+    }
+
+    // Number of bytes an instruction occupies, without resolving its operands
+    // Unresolvable li immediates are sized for the longest expansion (lui + addi)
+    pub fn instruction_size(&self, line: &str, symbols: &dyn SymbolTable) -> u32 {
+        let (mnemonic, operands) = split_instruction(line);
+
+        if !PseudoInstructions::is_pseudo_instruction(mnemonic, &operands) {
+            return 4;
+        }
+
+        match PseudoInstructions::expand(mnemonic, &operands, 0, symbols) {
+            Ok(translated) => translated.len() as u32 * 4,
+            Err(_) if mnemonic == "li" => 8,
+            Err(_) => 4
+        }
     }
 
     // Parse R-type instructions
@@ -148,6 +162,8 @@ impl Parser {
         &self,
         fmt: &InstructionFormat,
         operands: &[&str],
+        current_address: u32,
+        symbols: &dyn SymbolTable
     ) -> Result<u32, AssemblerError> {
         if fmt.opcode == 0b1110011 {
             if fmt.funct7 == Some(0x0) {
@@ -184,10 +200,13 @@ impl Parser {
                         }
 
                         let rd = parse_register(operands[0])?;
-                        let csr = parse_csr(operands[1])?;
+                        let csr = match parse_csr(operands[1]) {
+                            Ok(csr) => csr,
+                            Err(e) => resolve_immediate(operands[1], current_address, symbols).map_err(|_| e)?
+                        };
 
                         // Out-of-range check for 12-bit CSR address
-                        if csr < 0 || csr > 0xFFF {
+                        if !(0..=0xFFF).contains(&csr) {
                             return Err(AssemblerError::InvalidOperand(
                                 format!("CSR value is out of range: {}", operands[1])
                             ));
@@ -196,10 +215,10 @@ impl Parser {
                         let rs1_imm = if f3 & 0b100 == 0 {
                             parse_register(operands[2])?
                         } else {
-                            let imm = parse_immediate(operands[2])?;
+                            let imm = resolve_immediate(operands[2], current_address, symbols)?;
 
                             // Out-of-bounds check for 5-bit immediate
-                            if imm < 0 || imm > 31 {
+                            if !(0..=31).contains(&imm) {
                                 return Err(AssemblerError::InvalidOperand(
                                     format!(
                                         "Immediate must be between 0-31 for a CSR instruction but received {}",
@@ -228,7 +247,7 @@ impl Parser {
             // I-type load instructions
             2 => {
                 let rd = parse_register(operands[0])?;
-                let (imm, rs1) = parse_offset(operands[1])?;
+                let (imm, rs1) = resolve_offset(operands[1], current_address, symbols)?;
                 (rd, rs1, imm)
             }
 
@@ -236,7 +255,7 @@ impl Parser {
             3 => {
                 let rd = parse_register(operands[0])?;
                 let rs1 = parse_register(operands[1])?;
-                let imm = parse_immediate(operands[2])?;
+                let imm = resolve_immediate(operands[2], current_address, symbols)?;
                 (rd, rs1, imm)
             }
 
@@ -248,6 +267,24 @@ impl Parser {
             }
         };
 
+        // Shifts keep the shift amount in imm[5:0] and funct7 above it
+        let imm = match fmt.funct7 {
+            Some(funct7) => {
+                let max_shift = if fmt.opcode == 0b0011011 { 31 } else { 63 };
+
+                if !(0..=max_shift).contains(&imm) {
+                    return Err(AssemblerError::InvalidOperand(format!(
+                        "Shift amount must be between 0-{} but received {}",
+                        max_shift,
+                        imm
+                    )));
+                }
+
+                imm | (funct7 << 5) as i32
+            }
+            None => check_immediate(imm, 12, 1, operands[operands.len() - 1])?
+        };
+
         Ok(encode_i_type(
             fmt.opcode,
             rd,
@@ -260,7 +297,9 @@ impl Parser {
     pub fn parse_s_type(
         &self,
         fmt: &InstructionFormat,
-        operands: &[&str]
+        operands: &[&str],
+        current_address: u32,
+        symbols: &dyn SymbolTable
     ) -> Result<u32, AssemblerError> {
         if operands.len() != 2 {
             return Err(AssemblerError::ParseError(format!(
@@ -270,7 +309,8 @@ impl Parser {
         }
 
         let rs2 = parse_register(operands[0])?;
-        let (imm, rs1) = parse_offset(operands[1])?;
+        let (imm, rs1) = resolve_offset(operands[1], current_address, symbols)?;
+        let imm = check_immediate(imm, 12, 1, operands[1])?;
 
         Ok(encode_s_type(
             fmt.opcode, 
@@ -286,7 +326,7 @@ impl Parser {
         fmt: &InstructionFormat,
         operands: &[&str],
         current_address: u32,
-        symbols: &HashMap<String, u32>
+        symbols: &dyn SymbolTable
     ) -> Result<u32, AssemblerError> {
         if operands.len() != 3 {
             return Err(AssemblerError::ParseError(format!(
@@ -298,16 +338,13 @@ impl Parser {
         let rs1 = parse_register(operands[0])?;
         let rs2 = parse_register(operands[1])?;
 
-        let offset = match parse_immediate(operands[2]) {
-            Ok(immediate) => immediate,
-            Err(_) => {
-                let target_address = symbols.get(operands[2])
-                    .ok_or_else(|| AssemblerError::UndefinedLabel(operands[2].to_string()))?;
+        let offset = match resolve_operand(operands[2], current_address, symbols)? {
+            SymbolValue::Constant(immediate) => to_i32(immediate, operands[2])?,
 
-                // PC relative addressing
-                (*target_address as i32) - (current_address as i32)
-            }
+            // PC relative addressing
+            SymbolValue::Address(target_address) => (target_address as i32) - (current_address as i32)
         };
+        let offset = check_immediate(offset, 13, 2, operands[2])?;
 
         Ok(encode_b_type(
             fmt.opcode,
@@ -322,7 +359,8 @@ impl Parser {
         &self,
         fmt: &InstructionFormat,
         operands: &[&str],
-        symbols: &HashMap<String, u32>
+        current_address: u32,
+        symbols: &dyn SymbolTable
     ) -> Result<u32, AssemblerError> {
         if operands.len() != 2 {
             return Err(AssemblerError::ParseError(format!(
@@ -333,22 +371,21 @@ impl Parser {
 
         let rd = parse_register(operands[0])?;
 
-        let imm = match parse_immediate(operands[1]) {
-            Ok(immediate) => immediate,
-            Err(_) => {
-                let label = operands[1];
-                let target_address = symbols.get(label)
-                    .ok_or_else(|| AssemblerError::UndefinedLabel(label.to_string()))?;
-
-                *target_address as i32
-            }
+        let imm = match resolve_operand(operands[1], current_address, symbols)? {
+            SymbolValue::Constant(immediate) => to_i32(immediate, operands[1])?,
+            SymbolValue::Address(target_address) => target_address as i32
         };
 
-        Ok(encode_u_type(
-            fmt.opcode,
-            rd,
+        // %hi and %pcrel_hi already give the 20-bit field
+        // Otherwise the operand is a full 32-bit value and only imm[31:12] is encoded
+        let operand = operands[1].trim_start();
+        let imm = if operand.starts_with("%hi(") || operand.starts_with("%pcrel_hi(") {
             imm
-        ))
+        } else {
+            imm >> 12
+        };
+
+        Ok(encode_u_type(fmt.opcode, rd, imm))
     }
 
     pub fn parse_j_type(
@@ -356,7 +393,7 @@ impl Parser {
         fmt: &InstructionFormat,
         operands: &[&str],
         current_address: u32,
-        symbols: &HashMap<String, u32>
+        symbols: &dyn SymbolTable
     ) -> Result<u32, AssemblerError> {
         if operands.len() != 2 {
             return Err(AssemblerError::ParseError(format!(
@@ -367,16 +404,13 @@ impl Parser {
 
         let rd = parse_register(operands[0])?;
 
-        let imm = match parse_immediate(operands[1]) {
-            Ok(immediate) => immediate,
-            Err(_) => {
-                let target_address = symbols.get(operands[1])
-                    .ok_or_else(|| AssemblerError::UndefinedLabel(operands[1].to_string()))?;
+        let imm = match resolve_operand(operands[1], current_address, symbols)? {
+            SymbolValue::Constant(immediate) => to_i32(immediate, operands[1])?,
 
-                // PC relative addressing
-                (*target_address as i32) - (current_address as i32)
-            }
+            // PC relative addressing
+            SymbolValue::Address(target_address) => (target_address as i32) - (current_address as i32)
         };
+        let imm = check_immediate(imm, 21, 2, operands[1])?;
 
         Ok(encode_j_type(
             fmt.opcode,
@@ -440,7 +474,7 @@ pub fn parse_immediate(imm: &str) -> Result<i32, AssemblerError> {
     // Parse the immediate
     let parsed_imm = if radix == 10 {
         // Decimal
-        let value = i32::from_str_radix(num_str, 10).map_err(|e| {
+        let value = num_str.parse::<i32>().map_err(|e| {
             AssemblerError::InvalidOperand(format!("Invalid immediate: {}", e))
         })?;
 
@@ -465,26 +499,60 @@ pub fn parse_immediate(imm: &str) -> Result<i32, AssemblerError> {
     Ok(parsed_imm)
 }
 
-pub fn parse_offset(offset: &str) -> Result<(i32, u32), AssemblerError> {
-    let mut parts = offset.split('(');
+// Parse an integer literal into 64 bits (used for data values)
+pub fn parse_integer(imm: &str) -> Result<i64, AssemblerError> {
+    let imm_str = imm.replace('_', "");
 
-    let imm_str = parts.next()
-        .ok_or_else(|| AssemblerError::InvalidOperand(format!("Invalid immediate: {}", offset)))?;
-    let mut rs1_str = parts.next()
-        .ok_or_else(|| AssemblerError::InvalidOperand(format!("Invalid register: {}", offset)))?;
+    let negative = imm_str.starts_with('-');
+    let unsigned_imm_str = imm_str.strip_prefix('-').unwrap_or(&imm_str);
 
-    if parts.next().is_some() {
-        return Err(AssemblerError::ParseError(format!(
-            "Invalid offset format: {}",
-            offset
-        )));
-    }
+    let (num_str, radix) = if let Some(hex) = unsigned_imm_str.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(bin) = unsigned_imm_str.strip_prefix("0b") {
+        (bin, 2)
+    } else if let Some(oct) = unsigned_imm_str.strip_prefix("0o") {
+        (oct, 8)
+    } else {
+        (unsigned_imm_str, 10)
+    };
+
+    // Parse as unsigned so that 0xFFFF_FFFF_FFFF_FFFF is accepted
+    let value = u64::from_str_radix(num_str, radix).map_err(|e| {
+        AssemblerError::InvalidOperand(format!("Invalid immediate: {}", e))
+    })? as i64;
+
+    Ok(if negative { value.wrapping_neg() } else { value })
+}
+
+pub fn parse_offset(offset: &str) -> Result<(i32, u32), AssemblerError> {
+    resolve_offset(offset, 0, &HashMap::<String, u32>::new())
+}
+
+// Parse "imm(rs1)" where the immediate may be any constant expression ("BUF+4(sp)", "(sp)")
+pub fn resolve_offset(
+    offset: &str,
+    current_address: u32,
+    symbols: &dyn SymbolTable
+) -> Result<(i32, u32), AssemblerError> {
+    let offset = offset.trim();
 
-    rs1_str = rs1_str.strip_suffix(')').ok_or_else(|| {
+    let body = offset.strip_suffix(')').ok_or_else(|| {
         AssemblerError::ParseError(format!("Invalid offset format: {}", offset))
     })?;
 
-    let imm = parse_immediate(imm_str)?;
+    // The register is inside the last pair of parentheses
+    let open_index = body.rfind('(').ok_or_else(|| {
+        AssemblerError::InvalidOperand(format!("Invalid register: {}", offset))
+    })?;
+
+    let imm_str = body[..open_index].trim();
+    let rs1_str = body[open_index + 1..].trim();
+
+    let imm = if imm_str.is_empty() {
+        0
+    } else {
+        resolve_immediate(imm_str, current_address, symbols)?
+    };
     let rs1 = parse_register(rs1_str)?;
 
     // x0 is hardwired to 0 so it's immutable
@@ -498,6 +566,64 @@ pub fn parse_offset(offset: &str) -> Result<(i32, u32), AssemblerError> {
     Ok((imm, rs1))
 }
 
+// Resolve an operand expression made of literals, constants and labels
+pub fn resolve_operand(
+    operand: &str,
+    current_address: u32,
+    symbols: &dyn SymbolTable
+) -> Result<SymbolValue, AssemblerError> {
+    evaluate(operand, current_address, symbols)
+}
+
+// Resolve an immediate operand, label addresses are not allowed here
+pub fn resolve_immediate(
+    imm: &str,
+    current_address: u32,
+    symbols: &dyn SymbolTable
+) -> Result<i32, AssemblerError> {
+    // Keep the exact literal rules for plain numbers
+    if let Ok(literal) = parse_immediate(imm) {
+        return Ok(literal);
+    }
+
+    to_i32(evaluate_constant(imm, current_address, symbols)?, imm)
+}
+
+// Narrow a constant to 32 bits, accepting both signed and unsigned values
+fn to_i32(value: i64, operand: &str) -> Result<i32, AssemblerError> {
+    if (i32::MIN as i64..=u32::MAX as i64).contains(&value) {
+        Ok(value as i32)
+    } else {
+        Err(AssemblerError::InvalidOperand(format!(
+            "Immediate does not fit in 32 bits: {}",
+            operand
+        )))
+    }
+}
+
+// Check that a value fits in a signed immediate of `bits` bits and is a multiple of `align`
+fn check_immediate(value: i32, bits: u32, align: i32, operand: &str) -> Result<i32, AssemblerError> {
+    if !(-(1 << (bits - 1))..1 << (bits - 1)).contains(&value) {
+        return Err(AssemblerError::InvalidOperand(format!(
+            "Immediate {} does not fit in {} bits: {}",
+            value,
+            bits,
+            operand
+        )));
+    }
+
+    if value % align != 0 {
+        return Err(AssemblerError::InvalidOperand(format!(
+            "Offset {} is not a multiple of {}: {}",
+            value,
+            align,
+            operand
+        )));
+    }
+
+    Ok(value)
+}
+
 pub fn parse_csr(imm: &str) -> Result<i32, AssemblerError> {
     if let Ok(parsed) = parse_immediate(imm) {
         return Ok(parsed);
@@ -510,4 +636,119 @@ pub fn parse_csr(imm: &str) -> Result<i32, AssemblerError> {
     Err(AssemblerError::InvalidOperand(
         format!("Invalid CSR name or immediate: {}", imm)
     ))
+}
+
+// Split an instruction into its mnemonic and comma-separated operands
+pub fn split_instruction(line: &str) -> (&str, Vec<&str>) {
+    let line = line.trim();
+
+    match line.find(char::is_whitespace) {
+        Some(idx) => (&line[..idx], split_operands(&line[idx..])),
+        None => (line, Vec::new())
+    }
+}
+
+// Remove a trailing '#' comment, ignoring '#' inside string and character literals
+pub fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (idx, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..idx],
+            None => {}
+        }
+    }
+
+    line
+}
+
+// Split "label: code" into the label and the remaining code
+// Only a valid symbol name before the first ':' counts as a label
+pub fn split_label(line: &str) -> (Option<&str>, &str) {
+    if let Some(colon_index) = line.find(':') {
+        let label = line[..colon_index].trim();
+
+        if is_symbol_name(label) || is_local_label(label) {
+            return (Some(label), line[colon_index + 1..].trim());
+        }
+    }
+
+    (None, line.trim())
+}
+
+// Symbol names may contain letters, digits, '_', '.' and '$' but can't start with a digit
+pub fn is_symbol_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '$' => {}
+        _ => return false
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+// Numeric local labels ("1:") may be defined any number of times
+pub fn is_local_label(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_digit())
+}
+
+// A reference to the next ("1f") or previous ("1b") definition of a numeric local label
+// Returns the label number and whether the reference is forward
+pub fn local_label_reference(name: &str) -> Option<(&str, bool)> {
+    let forward = match name.chars().last()? {
+        'f' => true,
+        'b' => false,
+        _ => return None
+    };
+    let number = &name[..name.len() - 1];
+
+    is_local_label(number).then_some((number, forward))
+}
+
+// Split operands on top-level commas
+// Commas inside quotes or parentheses don't separate operands
+pub fn split_operands(operands: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (idx, c) in operands.char_indices() {
+        if let Some(q) = quote {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                _ if c == q => quote = None,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(operands[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+
+    let last = operands[start..].trim();
+
+    if !last.is_empty() || !result.is_empty() {
+        result.push(last);
+    }
+
+    result
 }
\ No newline at end of file
@@ -2,7 +2,8 @@
 
 use phf::phf_set;
 use crate::assembler::AssemblerError;
-use crate::assembler::parser::parse_immediate;
+use crate::assembler::parser::resolve_immediate;
+use crate::assembler::symbols::SymbolTable;
 
 pub struct TranslatedInstruction<'a> {
     pub mnemonic: &'a str,
@@ -53,13 +54,17 @@ pub struct PseudoInstructions;
 
 impl PseudoInstructions {
     // Check if a mnemonic is a pseudo-instruction
-    pub fn is_pseudo_instruction(mnemonic: &str, operands_length: usize) -> bool {
+    pub fn is_pseudo_instruction(mnemonic: &str, operands: &[&str]) -> bool {
         if PSEUDO_INSTRUCTIONS.contains(mnemonic) {
-            if (mnemonic == "jal" || mnemonic == "jalr") && operands_length != 1 {
-                return false;
-            }
+            match mnemonic {
+                "jal" | "jalr" => operands.len() == 1,
+
+                // Loads and stores are only pseudo-instructions with a symbol instead of imm(rs1)
+                "lb" | "lh" | "lw" | "ld" => !operands.get(1).is_some_and(|op| op.contains('(')),
+                "sb" | "sh" | "sw" | "sd" => operands.len() == 3,
 
-            true
+                _ => true
+            }
         } else {
             false
         }
@@ -68,20 +73,22 @@ impl PseudoInstructions {
     // Translate a pseudo-instruction into one or more base instructions
     pub fn expand<'a>(
         mnemonic: &'a str, 
-        operands: &[&str]
+        operands: &[&str],
+        current_address: u32,
+        symbols: &dyn SymbolTable
     ) -> Result<Vec<TranslatedInstruction<'a>>, AssemblerError> {
         match mnemonic {
-            "la" => Self::translate_la(operands),
-            "lb" => Self::translate_load_global(mnemonic, operands),
-            "lh" => Self::translate_load_global(mnemonic, operands),
-            "lw" => Self::translate_load_global(mnemonic, operands),
-            "ld" => Self::translate_load_global(mnemonic, operands),
-            "sb" => Self::translate_store_global(mnemonic, operands),
-            "sh" => Self::translate_store_global(mnemonic, operands),
-            "sw" => Self::translate_store_global(mnemonic, operands),
-            "sd" => Self::translate_store_global(mnemonic, operands),
+            "la" => Self::translate_la(operands, current_address),
+            "lb" => Self::translate_load_global(mnemonic, operands, current_address),
+            "lh" => Self::translate_load_global(mnemonic, operands, current_address),
+            "lw" => Self::translate_load_global(mnemonic, operands, current_address),
+            "ld" => Self::translate_load_global(mnemonic, operands, current_address),
+            "sb" => Self::translate_store_global(mnemonic, operands, current_address),
+            "sh" => Self::translate_store_global(mnemonic, operands, current_address),
+            "sw" => Self::translate_store_global(mnemonic, operands, current_address),
+            "sd" => Self::translate_store_global(mnemonic, operands, current_address),
             "nop" => Self::translate_nop(operands),
-            "li" => Self::translate_li(operands),
+            "li" => Self::translate_li(operands, current_address, symbols),
             "mv" => Self::translate_mv(operands),
             "not" => Self::translate_not(operands),
             "neg" => Self::translate_neg(operands),
@@ -106,8 +113,8 @@ impl PseudoInstructions {
             "jr" => Self::translate_jr(operands),
             "jalr" => Self::translate_jalr(operands),
             "ret" => Self::translate_ret(operands),
-            "call" => Self::translate_call(operands),
-            "tail" => Self::translate_tail(operands),
+            "call" => Self::translate_call(operands, current_address),
+            "tail" => Self::translate_tail(operands, current_address),
             _ => Err(AssemblerError::InvalidInstruction(format!(
                 "Unknown pseudo-instruction: {}", mnemonic
             )))
@@ -117,11 +124,12 @@ impl PseudoInstructions {
     // Load address
     // la rd, symbol => auipc + addi
     fn translate_la<'a>(
-        operands: &[&str]
+        operands: &[&str],
+        current_address: u32
     ) -> Result<Vec<TranslatedInstruction<'a>>, AssemblerError> {
         check_operands("la", operands, 2)?;
         let rd = operands[0];
-        let symbol = operands[1];
+        let (hi, lo) = pcrel_split(operands[1], current_address);
 
         Ok(vec![
             // Upper 20-bits
@@ -129,7 +137,7 @@ impl PseudoInstructions {
                 mnemonic: "auipc",
                 operands: vec![
                     rd.to_string(),
-                    symbol.to_string()
+                    hi
                 ]
             },
             // Lower 12-bits
@@ -138,7 +146,7 @@ impl PseudoInstructions {
                 operands: vec![
                     rd.to_string(),
                     rd.to_string(),
-                    symbol.to_string()
+                    lo
                 ]
             }
         ])
@@ -148,25 +156,26 @@ impl PseudoInstructions {
     // l{b|h|w|d} rd, symbol => auipc + l{b|h|w|d}
     fn translate_load_global<'a>(
         instr: &'a str,
-        operands: &[&str]
+        operands: &[&str],
+        current_address: u32
     ) -> Result<Vec<TranslatedInstruction<'a>>, AssemblerError> {
         check_operands(instr, operands, 2)?;
         let rd = operands[0];
-        let label = operands[1];
+        let (hi, lo) = pcrel_split(operands[1], current_address);
 
         Ok(vec![
             TranslatedInstruction {
                 mnemonic: "auipc",
                 operands: vec![
                     rd.to_string(),
-                    label.to_string()
+                    hi
                 ]
             },
             TranslatedInstruction {
                 mnemonic: instr,  // lb/lh/lw/ld
                 operands: vec![
                     rd.to_string(),
-                    format!("{}({})", label, rd)
+                    format!("{}({})", lo, rd)
                 ]
             }
         ])
@@ -176,26 +185,27 @@ impl PseudoInstructions {
     // s{b|h|w|d} rd, symbol, rt => auipc + l{b|h|w|d}
     fn translate_store_global<'a>(
         instr: &'a str,
-        operands: &[&str]
+        operands: &[&str],
+        current_address: u32
     ) -> Result<Vec<TranslatedInstruction<'a>>, AssemblerError> {
         check_operands(instr, operands, 3)?;
         let rd = operands[0];
-        let symbol = operands[1];
         let rt = operands[2];
+        let (hi, lo) = pcrel_split(operands[1], current_address);
 
         Ok(vec![
             TranslatedInstruction {
                 mnemonic: "auipc",
                 operands: vec![
                     rt.to_string(),
-                    symbol.to_string()
+                    hi
                 ]
             },
             TranslatedInstruction {
                 mnemonic: instr,  // sb/sh/sw/sd
                 operands: vec![
                     rd.to_string(),
-                    format!("{}({})", symbol, rt)
+                    format!("{}({})", lo, rt)
                 ]
             }
         ])
@@ -223,11 +233,13 @@ impl PseudoInstructions {
     // Load immediate
     // li rd, immediate => lui + addi
     fn translate_li<'a>(
-        operands: &[&str]
+        operands: &[&str],
+        current_address: u32,
+        symbols: &dyn SymbolTable
     ) -> Result<Vec<TranslatedInstruction<'a>>, AssemblerError> {
         check_operands("li", operands,2)?;
         let rd = operands[0];
-        let imm = parse_immediate(operands[1])?;
+        let imm = resolve_immediate(operands[1], current_address, symbols)?;
 
         // Case 1: immediate fits within the 12-bit range
         if (-2048..=2047).contains(&imm) {
@@ -244,15 +256,15 @@ impl PseudoInstructions {
         }
 
         // Case 2: immediate requires lui + addi
-        let imm_upper = ((imm + 0x800) >> 12) as i32;  // Upper 20 bits
-        let imm_lower = imm - (imm_upper << 12);       // Lower 12 bits signed
+        let imm_upper = imm.wrapping_add(0x800) >> 12;  // Upper 20 bits
+        let imm_lower = imm.wrapping_sub(imm_upper << 12);  // Lower 12 bits signed
 
         let mut expanded = vec![
             TranslatedInstruction {
                 mnemonic: "lui",
                 operands: vec![
-                    rd.to_string(),        // rd
-                    imm_upper.to_string()  // imm[31:12]
+                    rd.to_string(),                  // rd
+                    (imm_upper << 12).to_string()    // imm[31:12]
                 ]
             }
         ];
@@ -730,18 +742,19 @@ impl PseudoInstructions {
     // Call far-away subroutine
     // call offset => auipc + jalr
     fn translate_call<'a>(
-        operands: &[&str]
+        operands: &[&str],
+        current_address: u32
     ) -> Result<Vec<TranslatedInstruction<'a>>, AssemblerError> {
         check_operands("call", operands, 1)?;
         let reg = "x1";
-        let label = operands[0];
+        let (hi, lo) = pcrel_split(operands[0], current_address);
 
         Ok(vec![
             TranslatedInstruction {
                 mnemonic: "auipc",
                 operands: vec![
                     reg.to_string(),
-                    label.to_string()
+                    hi
                 ]
             },
             TranslatedInstruction {
@@ -749,7 +762,7 @@ impl PseudoInstructions {
                 operands: vec![
                     reg.to_string(),
                     reg.to_string(),
-                    label.to_string()
+                    lo
                 ]
             }
         ])
@@ -758,18 +771,19 @@ impl PseudoInstructions {
     // Tail call far-away subroutine
     // tail offset => auipc + jalr
     fn translate_tail<'a>(
-        operands: &[&str]
+        operands: &[&str],
+        current_address: u32
     ) -> Result<Vec<TranslatedInstruction<'a>>, AssemblerError> {
-        check_operands("call", operands, 1)?;
+        check_operands("tail", operands, 1)?;
         let reg = "x6";
-        let label = operands[0];
+        let (hi, lo) = pcrel_split(operands[0], current_address);
 
         Ok(vec![
             TranslatedInstruction {
                 mnemonic: "auipc",
                 operands: vec![
                     reg.to_string(),
-                    label.to_string()
+                    hi
                 ]
             },
             TranslatedInstruction {
@@ -777,13 +791,22 @@ impl PseudoInstructions {
                 operands: vec![
                     "x0".to_string(),
                     reg.to_string(),
-                    label.to_string()
+                    lo
                 ]
             }
         ])
     }
 }
 
+// Operands for an auipc at auipc_address and the instruction that follows it
+// Both halves are relative to the auipc, so the pair reaches symbol from anywhere
+fn pcrel_split(symbol: &str, auipc_address: u32) -> (String, String) {
+    (
+        format!("%pcrel_hi({})", symbol),
+        format!("%lo(({}) - {})", symbol, auipc_address)
+    )
+}
+
 // Validate operands
 fn check_operands(
     name: &str,
@@ -34,4 +34,11 @@ pub(crate) const ABI_NAME_REGISTERS: phf::Map<&'static str, u32> = phf_map! {
     "t4" => 29,   // Temporary
     "t5" => 30,   // Temporary
     "t6" => 31,   // Temporary
-};
\ No newline at end of file
+};
+// ABI name of each register number, s0 rather than fp for x8
+pub(crate) const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6"
+];
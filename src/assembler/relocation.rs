@@ -0,0 +1,419 @@
+//! Records the references an object file leaves for the linker to fix up (R_RISCV_* relocations)
+
+use std::cell::RefCell;
+use crate::assembler::error::AssemblerError;
+use crate::assembler::expr::evaluate;
+use crate::assembler::instructions::{InstructionFormat, InstructionSet, InstructionType};
+use crate::assembler::parser::{local_label_reference, split_instruction};
+use crate::assembler::symbols::{SymbolTable, SymbolValue};
+use crate::assembler::{Assembler, Pass, SymbolView};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RelocationKind {
+    Abs32,       // .word symbol
+    Abs64,       // .dword symbol
+    Branch,      // b-type offset
+    Jal,         // j-type offset
+    CallPlt,     // auipc + jalr pair of call and tail
+    PcrelHi20,   // auipc rd, %pcrel_hi(symbol)
+    PcrelLo12I,  // %pcrel_lo(label) in an i-type instruction, label is the auipc
+    PcrelLo12S,  // %pcrel_lo(label) in an s-type instruction
+    Hi20,        // lui rd, %hi(symbol)
+    Lo12I,       // %lo(symbol) in an i-type instruction
+    Lo12S        // %lo(symbol) in an s-type instruction
+}
+
+impl RelocationKind {
+    // ELF relocation type from the RISC-V psABI
+    pub fn elf_type(self) -> u32 {
+        match self {
+            Self::Abs32 => 1,
+            Self::Abs64 => 2,
+            Self::Branch => 16,
+            Self::Jal => 17,
+            Self::CallPlt => 19,
+            Self::PcrelHi20 => 23,
+            Self::PcrelLo12I => 24,
+            Self::PcrelLo12S => 25,
+            Self::Hi20 => 26,
+            Self::Lo12I => 27,
+            Self::Lo12S => 28
+        }
+    }
+
+    // Kind of an ELF relocation type, R_RISCV_CALL is handled like R_RISCV_CALL_PLT
+    pub fn from_elf_type(elf_type: u32) -> Option<Self> {
+        match elf_type {
+            1 => Some(Self::Abs32),
+            2 => Some(Self::Abs64),
+            16 => Some(Self::Branch),
+            17 => Some(Self::Jal),
+            18 | 19 => Some(Self::CallPlt),
+            23 => Some(Self::PcrelHi20),
+            24 => Some(Self::PcrelLo12I),
+            25 => Some(Self::PcrelLo12S),
+            26 => Some(Self::Hi20),
+            27 => Some(Self::Lo12I),
+            28 => Some(Self::Lo12S),
+            _ => None
+        }
+    }
+
+    // Offsets are relative to the relocated instruction rather than absolute
+    pub fn is_pc_relative(self) -> bool {
+        matches!(self, Self::Branch | Self::Jal | Self::CallPlt | Self::PcrelHi20)
+    }
+
+    // Immediate bits of each instruction word the relocation covers, left for the linker to fill in
+    pub fn masks(self) -> &'static [u32] {
+        match self {
+            Self::Abs32 | Self::Abs64 => &[],
+            Self::Branch | Self::PcrelLo12S | Self::Lo12S => &[0xFE000F80],
+            Self::Jal | Self::PcrelHi20 | Self::Hi20 => &[0xFFFFF000],
+            Self::CallPlt => &[0xFFFFF000, 0xFFF00000],
+            Self::PcrelLo12I | Self::Lo12I => &[0xFFF00000]
+        }
+    }
+}
+
+// What a relocation refers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelocationSymbol {
+    Named(String),   // A label of this file or an undefined symbol
+    Section(usize),  // The start of a section, for numeric and .L labels that don't go in the symbol table
+
+    // The auipc a %pcrel_lo belongs to, which gets a .Lpcrel_hi label in the object file
+    PcrelHi { section: usize, offset: u32 }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub offset: u32,  // Section-relative offset of the relocated instruction or data
+    pub kind: RelocationKind,
+    pub symbol: RelocationSymbol,
+    pub addend: i64
+}
+
+// Where an address operand points to
+struct Reference {
+    symbol: RelocationSymbol,
+    addend: i64,
+    section: Option<usize>  // None for undefined symbols
+}
+
+impl Assembler {
+    // Relocations for the instructions of one line at address, on the second pass of an object file
+    // PC-relative references within the current section are already final and need none
+    pub(super) fn instruction_relocations(&self, src: &str, address: u32) -> Result<Vec<Relocation>, AssemblerError> {
+        let view = self.view(Pass::Emit);
+        let instructions = self.parser.expand_line(src, address, &view)?;
+        let (mnemonic, _) = split_instruction(src);
+        let is_call = mnemonic == "call" || mnemonic == "tail";
+        let base = self.sections[self.current_section].address;
+        let instruction_set = InstructionSet::new();
+        let mut relocations: Vec<Relocation> = Vec::new();
+
+        for (idx, instr) in instructions.iter().enumerate() {
+            let instr_address = address + idx as u32 * 4;
+            let offset = instr_address.wrapping_sub(base);
+
+            let Some(format) = instruction_set.get_instruction(instr.mnemonic) else {
+                continue;
+            };
+            let Some(operand) = immediate_operand(format, &instr.operands) else {
+                continue;
+            };
+
+            // The second half of an auipc pair in a pseudo-instruction
+            if idx > 0 && instructions[idx - 1].mnemonic == "auipc" {
+                // The jalr of call and tail is covered by R_RISCV_CALL_PLT
+                if !is_call {
+                    let kind = lo12_kind(format, RelocationKind::PcrelLo12I, RelocationKind::PcrelLo12S);
+                    relocations.extend(self.pcrel_lo_relocation(kind, instr_address - 4, offset, &relocations));
+                }
+
+                continue;
+            }
+
+            let (operator, expr) = split_relocation_operator(operand);
+
+            let kind = match (format.fmt, operator) {
+                (InstructionType::B, None) => RelocationKind::Branch,
+                (InstructionType::J, None) => RelocationKind::Jal,
+                (InstructionType::U, Some("%hi")) => RelocationKind::Hi20,
+                (InstructionType::U, Some("%pcrel_hi")) if is_call => RelocationKind::CallPlt,
+                (InstructionType::U, Some("%pcrel_hi")) => RelocationKind::PcrelHi20,
+                (InstructionType::I | InstructionType::S, Some("%lo")) => {
+                    lo12_kind(format, RelocationKind::Lo12I, RelocationKind::Lo12S)
+                }
+
+                // The operand is the label of the auipc
+                (InstructionType::I | InstructionType::S, Some("%pcrel_lo")) => {
+                    let SymbolValue::Address(auipc_address) = evaluate(expr, instr_address, &view)? else {
+                        return Err(AssemblerError::InvalidOperand(format!(
+                            "%pcrel_lo expects the label of an auipc but received {}",
+                            expr
+                        )));
+                    };
+
+                    let kind = lo12_kind(format, RelocationKind::PcrelLo12I, RelocationKind::PcrelLo12S);
+                    relocations.extend(self.pcrel_lo_relocation(kind, auipc_address, offset, &relocations));
+                    continue;
+                }
+
+                // Anything else has to be known without the final addresses
+                _ => {
+                    if self.reference(operand, instr_address)?.is_some() {
+                        return Err(AssemblerError::InvalidOperand(format!(
+                            "{} can't be relocated in {}, use %hi/%lo or %pcrel_hi/%pcrel_lo",
+                            operand,
+                            src
+                        )));
+                    }
+
+                    continue;
+                }
+            };
+
+            let Some(reference) = self.reference(expr, instr_address)? else {
+                continue;
+            };
+
+            if kind.is_pc_relative() && reference.section == Some(self.current_section) {
+                continue;
+            }
+
+            relocations.push(Relocation {
+                offset,
+                kind,
+                symbol: reference.symbol,
+                addend: reference.addend
+            });
+        }
+
+        Ok(relocations)
+    }
+
+    // Relocation for a .byte/.half/.word/.dword value at address, on the second pass of an object file
+    pub(super) fn data_relocation(&self, value: &str, size: u32, address: u32) -> Result<Option<Relocation>, AssemblerError> {
+        let Some(reference) = self.reference(value, address)? else {
+            return Ok(None);
+        };
+
+        let kind = match size {
+            4 => RelocationKind::Abs32,
+            8 => RelocationKind::Abs64,
+            _ => {
+                return Err(AssemblerError::InvalidOperand(format!(
+                    "{} can't be relocated in a {}-byte value",
+                    value,
+                    size
+                )));
+            }
+        };
+
+        Ok(Some(Relocation {
+            offset: address.wrapping_sub(self.sections[self.current_section].address),
+            kind,
+            symbol: reference.symbol,
+            addend: reference.addend
+        }))
+    }
+
+    // %pcrel_lo is only relocated when the high part of its auipc is
+    fn pcrel_lo_relocation(
+        &self,
+        kind: RelocationKind,
+        auipc_address: u32,
+        offset: u32,
+        pending: &[Relocation]
+    ) -> Option<Relocation> {
+        let section = &self.sections[self.current_section];
+        let auipc_offset = auipc_address.wrapping_sub(section.address);
+
+        let relocated = section.relocations
+            .iter()
+            .chain(pending)
+            .any(|r| r.offset == auipc_offset && r.kind == RelocationKind::PcrelHi20);
+
+        relocated.then_some(Relocation {
+            offset,
+            kind,
+            symbol: RelocationSymbol::PcrelHi {
+                section: self.current_section,
+                offset: auipc_offset
+            },
+            addend: 0
+        })
+    }
+
+    // The symbol and addend an expression refers to, or None if its value doesn't depend on the layout
+    fn reference(&self, expr: &str, address: u32) -> Result<Option<Reference>, AssemblerError> {
+        let view = self.view(Pass::Emit);
+        let recorder = RecordingTable { view, names: RefCell::new(Vec::new()) };
+        let value = evaluate(expr, address, &recorder)?;
+
+        let names = recorder.names.into_inner();
+        let locations: Vec<Option<(usize, u32)>> = names.iter().map(|name| view.locate(name)).collect();
+        let undefined: Vec<&String> = names
+            .iter()
+            .zip(&locations)
+            .filter(|(_, location)| location.is_none())
+            .map(|(name, _)| name)
+            .collect();
+
+        let address = match value {
+            // Differences of labels in the same section are final, others are only known to the linker
+            SymbolValue::Constant(_) => {
+                let mut sections = locations.iter().flatten().map(|(section, _)| section);
+                let first = sections.next();
+
+                if undefined.is_empty() && sections.all(|section| Some(section) == first) {
+                    return Ok(None);
+                }
+
+                return Err(AssemblerError::InvalidOperand(format!(
+                    "{} can't be expressed as a relocation",
+                    expr
+                )));
+            }
+            SymbolValue::Address(address) => address
+        };
+
+        match undefined.as_slice() {
+            [] => {}
+            [name] => {
+                return Ok(Some(Reference {
+                    symbol: RelocationSymbol::Named(name.to_string()),
+                    addend: offset_from(address, view.undefined.unwrap_or(0)),
+                    section: None
+                }));
+            }
+            _ => {
+                return Err(AssemblerError::InvalidOperand(format!(
+                    "{} refers to more than one undefined symbol",
+                    expr
+                )));
+            }
+        }
+
+        // Only '.' refers to the current section without naming a label
+        let (section, label_offset) = locations
+            .first()
+            .copied()
+            .flatten()
+            .unwrap_or((self.current_section, 0));
+        let base = self.sections[section].address;
+
+        let symbol = match names.first() {
+            Some(name) if !is_temporary(name) => RelocationSymbol::Named(name.to_string()),
+            _ => RelocationSymbol::Section(section)
+        };
+
+        let addend = match symbol {
+            RelocationSymbol::Named(_) => offset_from(address, base.wrapping_add(label_offset)),
+            _ => offset_from(address, base)
+        };
+
+        Ok(Some(Reference { symbol, addend, section: Some(section) }))
+    }
+}
+
+// Passes lookups through to the assembler symbols and remembers the labels an expression used
+struct RecordingTable<'a> {
+    view: SymbolView<'a>,
+    names: RefCell<Vec<String>>
+}
+
+impl SymbolTable for RecordingTable<'_> {
+    fn resolve(&self, name: &str) -> Option<SymbolValue> {
+        let value = self.view.resolve(name);
+
+        if let Some(SymbolValue::Address(_)) = value {
+            self.names.borrow_mut().push(name.to_string());
+        }
+
+        value
+    }
+
+    fn pcrel_hi_target(&self, address: u32) -> Option<SymbolValue> {
+        self.view.pcrel_hi_target(address)
+    }
+}
+
+// The operand holding the immediate of a base instruction, if it has one that may refer to a label
+fn immediate_operand<'a>(format: &InstructionFormat, operands: &'a [String]) -> Option<&'a str> {
+    let operand = match format.fmt {
+        InstructionType::R => None,
+        InstructionType::B => operands.get(2),
+        InstructionType::J | InstructionType::U => operands.get(1),
+
+        // ecall, ebreak and the CSR instructions
+        InstructionType::I if format.opcode == 0b1110011 => None,
+
+        // Loads and jalr use imm(rs1), the others rd, rs1, imm
+        InstructionType::I if operands.len() == 2 => return offset_immediate(&operands[1]),
+        InstructionType::I => operands.get(2),
+        InstructionType::S => return operands.get(1).and_then(|op| offset_immediate(op))
+    };
+
+    operand.map(String::as_str)
+}
+
+// The immediate of "imm(rs1)"
+fn offset_immediate(operand: &str) -> Option<&str> {
+    let body = operand.trim().strip_suffix(')')?;
+    let immediate = body[..body.rfind('(')?].trim();
+
+    (!immediate.is_empty()).then_some(immediate)
+}
+
+// Split "%lo(expr)" into the operator and expr
+fn split_relocation_operator(operand: &str) -> (Option<&str>, &str) {
+    let operand = operand.trim();
+
+    for operator in ["%hi", "%lo", "%pcrel_hi", "%pcrel_lo"] {
+        let Some(inner) = operand
+            .strip_prefix(operator)
+            .and_then(|rest| rest.trim_start().strip_prefix('('))
+            .and_then(|rest| rest.strip_suffix(')'))
+        else {
+            continue;
+        };
+
+        // The closing parenthesis has to belong to the operator: "%lo(a) + (b)" is not a single call
+        let mut depth = 0;
+        let balanced = inner.chars().all(|c| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+
+            depth >= 0
+        });
+
+        if balanced {
+            return (Some(operator), inner);
+        }
+    }
+
+    (None, operand)
+}
+
+fn lo12_kind(format: &InstructionFormat, i_type: RelocationKind, s_type: RelocationKind) -> RelocationKind {
+    match format.fmt {
+        InstructionType::S => s_type,
+        _ => i_type
+    }
+}
+
+// Numeric and .L labels are local to the assembler and stay out of the symbol table
+fn is_temporary(name: &str) -> bool {
+    name.starts_with(".L") || local_label_reference(name).is_some()
+}
+
+// Signed distance between two 32-bit addresses
+fn offset_from(address: u32, base: u32) -> i64 {
+    address.wrapping_sub(base) as i32 as i64
+}
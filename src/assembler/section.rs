@@ -0,0 +1,237 @@
+//! Defines output sections and their location counters
+
+use crate::assembler::error::AssemblerError;
+use crate::assembler::relocation::Relocation;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SectionKind {
+    Text,
+    Rodata,
+    Data,
+    Bss
+}
+
+impl SectionKind {
+    // Infer the kind from a section name (.text.init -> Text, .sbss -> Bss, ...)
+    pub fn from_name(name: &str) -> Self {
+        let matches = |prefix: &str| name == prefix || name.starts_with(&format!("{}.", prefix));
+
+        if matches(".text") || matches(".init") || matches(".fini") {
+            Self::Text
+        } else if matches(".rodata") || matches(".srodata") {
+            Self::Rodata
+        } else if matches(".bss") || matches(".sbss") || matches(".tbss") {
+            Self::Bss
+        } else {
+            Self::Data
+        }
+    }
+
+    // Data and zero-initialized data are both writable, so they share a segment in executables
+    pub fn permissions(self) -> Self {
+        match self {
+            Self::Bss => Self::Data,
+            kind => kind
+        }
+    }
+
+    // Infer the kind from GNU section flags and type ("ax", "aw", @nobits, ...)
+    pub fn from_flags(flags: &str, section_type: Option<&str>) -> Self {
+        if section_type == Some("@nobits") || section_type == Some("%nobits") {
+            Self::Bss
+        } else if flags.contains('x') {
+            Self::Text
+        } else if flags.contains('w') {
+            Self::Data
+        } else {
+            Self::Rodata
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub kind: SectionKind,
+    pub address: u32,   // Start address in the final image
+    pub align: u32,     // Alignment of the start address in bytes
+    pub data: Vec<u8>,  // Empty for .bss and during the first pass
+    pub relocations: Vec<Relocation>,  // Only recorded for object files
+    size: u32                          // Location counter
+}
+
+impl Section {
+    pub fn new(name: &str, kind: SectionKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            address: 0,
+            align: 4,
+            data: Vec::new(),
+            relocations: Vec::new(),
+            size: 0
+        }
+    }
+
+    // Section-relative offset of the next byte
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    // Absolute address of the next byte
+    pub fn current_address(&self) -> u32 {
+        self.address.wrapping_add(self.size)
+    }
+
+    // Advance the location counter without emitting any bytes (first pass)
+    pub fn reserve(&mut self, len: u32) {
+        self.size += len;
+    }
+
+    // Append bytes to the section and advance the location counter
+    pub fn emit(&mut self, bytes: &[u8]) -> Result<(), AssemblerError> {
+        if self.kind == SectionKind::Bss {
+            if bytes.iter().any(|&b| b != 0) {
+                return Err(AssemblerError::InvalidDirective(format!(
+                    "Cannot emit initialized data into {}",
+                    self.name
+                )));
+            }
+        } else {
+            self.data.extend_from_slice(bytes);
+        }
+
+        self.size += bytes.len() as u32;
+        Ok(())
+    }
+
+    // Clear the contents before a new pass
+    pub(crate) fn reset(&mut self) {
+        self.data.clear();
+        self.relocations.clear();
+        self.size = 0;
+    }
+}
+
+// Assign start addresses: text, read-only data, data, then zero-initialized data
+// With a page size, sections with other permissions than the ones before them start a new page
+pub fn layout(sections: &mut [Section], base_address: u32, page_size: Option<u32>) -> Result<(), AssemblerError> {
+    let mut order: Vec<usize> = (0..sections.len()).collect();
+    order.sort_by_key(|&idx| sections[idx].kind);
+
+    let mut address = base_address;
+    let mut previous: Option<SectionKind> = None;
+
+    for idx in order {
+        let section = &mut sections[idx];
+        let new_page = previous.is_some_and(|kind| kind.permissions() != section.kind.permissions());
+
+        let align = match page_size {
+            Some(page_size) if new_page => section.align.max(page_size),
+            _ => section.align
+        };
+
+        section.address = aligned_start(address, align, &section.name)?;
+        address = section_end(section)?;
+
+        if section.size() > 0 {
+            previous = Some(section.kind);
+        }
+    }
+
+    Ok(())
+}
+
+// First address from `address` on with the alignment, for the section of that name
+pub(crate) fn aligned_start(address: u32, align: u32, name: &str) -> Result<u32, AssemblerError> {
+    let align = align.max(1);
+    address.div_ceil(align).checked_mul(align).ok_or_else(|| past_end(name))
+}
+
+// Address after the last byte of a placed section
+pub(crate) fn section_end(section: &Section) -> Result<u32, AssemblerError> {
+    section.address.checked_add(section.size()).ok_or_else(|| past_end(&section.name))
+}
+
+fn past_end(name: &str) -> AssemblerError {
+    AssemblerError::LayoutError(format!("{} runs past the end of the address space", name))
+}
+
+// Where the flat image of sections placed anywhere in memory starts
+pub fn lowest_address(sections: &[Section]) -> u32 {
+    sections
+        .iter()
+        .filter(|s| s.kind != SectionKind::Bss)
+        .map(|s| s.address)
+        .min()
+        .unwrap_or(0)
+}
+
+// Zero bytes a flat image may pad the gaps between sections with
+// Sections further apart, like ROM at 0 and RAM at 0x8000_0000, need a format with addresses
+pub(crate) const MAX_PADDING: u32 = 0x100_0000;
+
+// Flatten every section with contents into a single image starting at the base address
+pub fn flatten(sections: &[Section], base_address: u32) -> Result<Vec<u8>, AssemblerError> {
+    let loaded = sections.iter().filter(|s| s.kind != SectionKind::Bss);
+    let mut end = 0;
+
+    for section in loaded.clone() {
+        end = end.max(section_end(section)? - base_address);
+    }
+
+    let contents: u32 = loaded.map(|s| s.size()).sum();
+
+    if end.saturating_sub(contents) > MAX_PADDING {
+        return Err(AssemblerError::LayoutError(format!(
+            "Sections span {:#x} bytes, too far apart for a flat binary (use -O ihex or -O srec)",
+            end
+        )));
+    }
+
+    let mut image = vec![0; end as usize];
+
+    for section in sections.iter().filter(|s| s.kind != SectionKind::Bss) {
+        let start = (section.address - base_address) as usize;
+        image[start..start + section.data.len()].copy_from_slice(&section.data);
+    }
+
+    Ok(image)
+}
+
+// Bytes that load at consecutive addresses, for the formats that can leave gaps between them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>
+}
+
+// Contents of the sections in address order, adjacent sections sharing a segment
+pub fn segments(sections: &[Section]) -> Vec<Segment> {
+    let mut loaded: Vec<&Section> = sections
+        .iter()
+        .filter(|s| s.kind != SectionKind::Bss && !s.data.is_empty())
+        .collect();
+    loaded.sort_by_key(|s| s.address);
+
+    let mut segments = Vec::new();
+
+    for section in loaded {
+        append_segment(&mut segments, section.address, &section.data);
+    }
+
+    segments
+}
+
+// Add bytes at an address, extending the last segment when they follow it
+pub(crate) fn append_segment(segments: &mut Vec<Segment>, address: u32, data: &[u8]) {
+    match segments.last_mut() {
+        Some(last) if last.address as u64 + last.data.len() as u64 == address as u64 => {
+            last.data.extend_from_slice(data);
+        }
+        _ => segments.push(Segment {
+            address,
+            data: data.to_vec()
+        })
+    }
+}
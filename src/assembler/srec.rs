@@ -0,0 +1,174 @@
+//! Writes and reads Motorola S-record files (S19, S28 and S37)
+//!
+//! Every record is "S<type><count><address><data><checksum>" where the count covers the
+//! address, data and checksum bytes and the checksum is the ones' complement of their sum.
+//! The variants differ in the size of the addresses: 16 bits (S1/S9), 24 bits (S2/S8)
+//! or 32 bits (S3/S7).
+
+use std::fmt::Write;
+use crate::assembler::error::AssemblerError;
+use crate::assembler::ihex::parse_hex_bytes;
+use crate::assembler::section::{append_segment, Segment};
+
+// Data bytes per record
+const RECORD_SIZE: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SrecFormat {
+    S19,
+    S28,
+    S37
+}
+
+impl SrecFormat {
+    // Bytes in the address field of data and termination records
+    pub fn address_size(self) -> usize {
+        match self {
+            Self::S19 => 2,
+            Self::S28 => 3,
+            Self::S37 => 4
+        }
+    }
+
+    // The smallest format whose addresses reach every byte and the entry point
+    pub fn fitting(segments: &[Segment], entry: u32) -> Self {
+        let end = segments
+            .iter()
+            .map(|s| s.address as u64 + s.data.len().saturating_sub(1) as u64)
+            .chain([entry as u64])
+            .max()
+            .unwrap_or(0);
+
+        match end {
+            0..=0xFFFF => Self::S19,
+            0x10000..=0xFF_FFFF => Self::S28,
+            _ => Self::S37
+        }
+    }
+
+    fn data_type(self) -> u8 {
+        match self {
+            Self::S19 => 1,
+            Self::S28 => 2,
+            Self::S37 => 3
+        }
+    }
+
+    fn termination_type(self) -> u8 {
+        match self {
+            Self::S19 => 9,
+            Self::S28 => 8,
+            Self::S37 => 7
+        }
+    }
+}
+
+// Header, data records, a count of the data records and the termination record with the entry point
+pub fn generate_srec(segments: &[Segment], entry: u32, format: SrecFormat) -> Result<String, AssemblerError> {
+    let size = format.address_size();
+    let mut srec = String::new();
+    let mut count = 0u32;
+
+    if SrecFormat::fitting(segments, entry).address_size() > size {
+        return Err(AssemblerError::LayoutError(format!(
+            "The image doesn't fit in the {}-bit addresses of {:?}",
+            size * 8,
+            format
+        )));
+    }
+
+    write_record(&mut srec, 0, 0, 2, &[]);
+
+    for segment in segments {
+        for (idx, chunk) in segment.data.chunks(RECORD_SIZE).enumerate() {
+            let address = segment.address as u64 + (idx * RECORD_SIZE) as u64;
+            write_record(&mut srec, format.data_type(), address, size, chunk);
+            count += 1;
+        }
+    }
+
+    // S5 holds a 16-bit count, S6 a 24-bit one
+    match count {
+        0..=0xFFFF => write_record(&mut srec, 5, count as u64, 2, &[]),
+        0x10000..=0xFF_FFFF => write_record(&mut srec, 6, count as u64, 3, &[]),
+        _ => {}
+    }
+
+    write_record(&mut srec, format.termination_type(), entry as u64, size, &[]);
+    Ok(srec)
+}
+
+// Segments and entry point of an S-record file
+pub fn parse_srec(srec: &str) -> Result<(Vec<Segment>, Option<u32>), AssemblerError> {
+    let mut segments = Vec::new();
+    let mut entry = None;
+    let mut count = 0u32;
+
+    for (idx, line) in srec.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message: &str| AssemblerError::ParseError(format!("Line {}: {}", idx + 1, message));
+
+        let (record_type, hex) = line
+            .strip_prefix('S')
+            .and_then(|rest| Some((rest.chars().next()?.to_digit(10)?, rest.get(1..)?)))
+            .ok_or_else(|| error("Records start with S and a type digit"))?;
+        let record = parse_hex_bytes(hex).ok_or_else(|| error("Invalid hex digits"))?;
+
+        if record.len() < 2 || record.len() != record[0] as usize + 1 {
+            return Err(error("Record length doesn't match its byte count"));
+        }
+
+        if record.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0xFF {
+            return Err(error("Checksum mismatch"));
+        }
+
+        let address_size = match record_type {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            _ => return Err(error(&format!("Invalid record type S{}", record_type)))
+        };
+
+        if record.len() < address_size + 2 {
+            return Err(error("Record is too short for its address"));
+        }
+
+        let address = record[1..=address_size].iter().fold(0u32, |value, &b| (value << 8) | b as u32);
+        let data = &record[address_size + 1..record.len() - 1];
+
+        match record_type {
+            0 => {}
+            1..=3 => {
+                append_segment(&mut segments, address, data);
+                count += 1;
+            }
+            5 | 6 if address != count => {
+                return Err(error(&format!("Count record says {} data records but there are {}", address, count)));
+            }
+            5 | 6 => {}
+            _ => entry = Some(address)
+        }
+    }
+
+    Ok((segments, entry))
+}
+
+fn write_record(srec: &mut String, record_type: u8, address: u64, address_size: usize, data: &[u8]) {
+    let address_bytes = &address.to_be_bytes()[8 - address_size..];
+    let count = (address_size + data.len() + 1) as u8;
+    let mut checksum = count;
+
+    write!(srec, "S{}{:02X}", record_type, count).unwrap();
+
+    for &byte in address_bytes.iter().chain(data) {
+        write!(srec, "{:02X}", byte).unwrap();
+        checksum = checksum.wrapping_add(byte);
+    }
+
+    writeln!(srec, "{:02X}", !checksum).unwrap();
+}
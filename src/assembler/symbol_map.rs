@@ -0,0 +1,181 @@
+//! Exports the final symbol table for simulators, test benches and other tools
+//!
+//! The same symbols can be written as a GNU ld style map file, as `nm` text
+//! or as JSON. Labels have their final address, constants their value.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use crate::assembler::section::{Section, SectionKind};
+use crate::assembler::symbols::{Binding, Symbol, SymbolKind};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapSymbol {
+    pub name: String,
+    pub value: i64,              // Address of labels, value of constants
+    pub section: Option<usize>,  // None for constants
+    pub binding: Binding
+}
+
+pub struct SymbolMap<'a> {
+    sections: &'a [Section],
+    symbols: Vec<MapSymbol>  // In address order
+}
+
+impl<'a> SymbolMap<'a> {
+    pub fn new(sections: &'a [Section], symbols: &HashMap<String, Symbol>, bindings: &HashMap<String, Binding>) -> Self {
+        let mut symbols: Vec<MapSymbol> = symbols
+            .iter()
+            .map(|(name, symbol)| {
+                let (value, section) = match symbol.kind {
+                    SymbolKind::Label { section } => {
+                        (sections[section].address.wrapping_add(symbol.value as u32) as i64, Some(section))
+                    }
+                    SymbolKind::Constant { .. } => (symbol.value, None)
+                };
+
+                MapSymbol {
+                    name: name.clone(),
+                    value,
+                    section,
+                    binding: bindings.get(name).copied().unwrap_or(Binding::Local)
+                }
+            })
+            .collect();
+
+        symbols.sort_by(|a, b| (a.value, &a.name).cmp(&(b.value, &b.name)));
+        Self { sections, symbols }
+    }
+
+    pub fn symbols(&self) -> &[MapSymbol] {
+        &self.symbols
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MapSymbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    // Every section with its address and size followed by the labels inside it,
+    // then the constants as assignments, like the memory map of `ld -Map`
+    pub fn to_map(&self) -> String {
+        let mut map = String::from("Linker script and memory map\n\n");
+
+        let mut order: Vec<usize> = (0..self.sections.len()).collect();
+        order.sort_by_key(|&idx| self.sections[idx].address);
+
+        for idx in order {
+            let section = &self.sections[idx];
+            writeln!(map, "{:<15} 0x{:08x} {:>#10x}", section.name, section.address, section.size()).unwrap();
+
+            for symbol in self.symbols.iter().filter(|s| s.section == Some(idx)) {
+                writeln!(map, "                0x{:08x}                {}", symbol.value, symbol.name).unwrap();
+            }
+
+            map.push('\n');
+        }
+
+        for symbol in self.symbols.iter().filter(|s| s.section.is_none()) {
+            writeln!(
+                map,
+                "                0x{:08x}                {} = {:#x}",
+                symbol.value,
+                symbol.name,
+                symbol.value
+            ).unwrap();
+        }
+
+        map
+    }
+
+    // "value type name" sorted by name, with the type letters of nm:
+    // T/R/D/B for labels in text, read-only data, data and bss, A for constants,
+    // lowercase for local symbols and W for weak ones
+    pub fn to_nm(&self) -> String {
+        let mut symbols: Vec<&MapSymbol> = self.symbols.iter().collect();
+        symbols.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut nm = String::new();
+
+        for symbol in symbols {
+            let letter = match symbol.section.map(|idx| self.sections[idx].kind) {
+                Some(SectionKind::Text) => 'T',
+                Some(SectionKind::Rodata) => 'R',
+                Some(SectionKind::Data) => 'D',
+                Some(SectionKind::Bss) => 'B',
+                None => 'A'
+            };
+
+            let letter = match symbol.binding {
+                Binding::Local => letter.to_ascii_lowercase(),
+                Binding::Global => letter,
+                Binding::Weak => 'W'
+            };
+
+            writeln!(nm, "{:08x} {} {}", symbol.value, letter, symbol.name).unwrap();
+        }
+
+        nm
+    }
+
+    // {"sections": [{"name", "address", "size"}], "symbols": [{"name", "value", "section", "kind", "binding"}]}
+    // with the symbols in address order and null as the section of constants
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n  \"sections\": [");
+
+        for (idx, section) in self.sections.iter().enumerate() {
+            let separator = if idx == 0 { "" } else { "," };
+            write!(
+                json,
+                "{}\n    {{\"name\": {}, \"address\": {}, \"size\": {}}}",
+                separator,
+                json_string(&section.name),
+                section.address,
+                section.size()
+            ).unwrap();
+        }
+
+        json.push_str("\n  ],\n  \"symbols\": [");
+
+        for (idx, symbol) in self.symbols.iter().enumerate() {
+            let separator = if idx == 0 { "" } else { "," };
+            let (section, kind) = match symbol.section {
+                Some(section) => (json_string(&self.sections[section].name), "label"),
+                None => ("null".to_string(), "constant")
+            };
+            let binding = match symbol.binding {
+                Binding::Local => "local",
+                Binding::Global => "global",
+                Binding::Weak => "weak"
+            };
+
+            write!(
+                json,
+                "{}\n    {{\"name\": {}, \"value\": {}, \"section\": {}, \"kind\": \"{}\", \"binding\": \"{}\"}}",
+                separator,
+                json_string(&symbol.name),
+                symbol.value,
+                section,
+                kind,
+                binding
+            ).unwrap();
+        }
+
+        json.push_str("\n  ]\n}\n");
+        json
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c)
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,53 @@
+//! Defines the entries of the assembler symbol table and how the parser looks them up
+
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SymbolKind {
+    Label { section: usize },       // Value is the section-relative address
+    Constant { redefinable: bool }  // .equ/.set (redefinable) or .eqv
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub value: i64
+}
+
+impl Symbol {
+    pub fn is_label(&self) -> bool {
+        matches!(self.kind, SymbolKind::Label { .. })
+    }
+}
+
+// Visibility of a symbol outside of its object file (.globl, .local, .weak)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Binding {
+    Local,
+    Global,
+    Weak
+}
+
+// What an operand resolves to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SymbolValue {
+    Address(u32),   // Labels: branches and jumps are relative to the current address
+    Constant(i64)   // Literals and .equ values: used as-is
+}
+
+// Lookup interface used by the parser
+pub trait SymbolTable {
+    fn resolve(&self, name: &str) -> Option<SymbolValue>;
+
+    // Target of the %pcrel_hi operand of the auipc at an address (used by %pcrel_lo)
+    fn pcrel_hi_target(&self, _address: u32) -> Option<SymbolValue> {
+        None
+    }
+}
+
+// A plain map of label addresses
+impl SymbolTable for HashMap<String, u32> {
+    fn resolve(&self, name: &str) -> Option<SymbolValue> {
+        self.get(name).map(|&address| SymbolValue::Address(address))
+    }
+}
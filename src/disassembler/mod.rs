@@ -0,0 +1,197 @@
+//! Decodes 32-bit machine words back into RISC-V assembly
+//!
+//! Words are matched against the same instruction table the parser encodes with, and the
+//! operands are printed in the syntax the parser reads, so disassembled text assembles back
+//! into the same word. Registers get their ABI names, CSRs their names, and branch and jump
+//! targets the name of a label when one is known.
+
+pub mod objdump;
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::assembler::csr::CSR_ADDRESSES;
+use crate::assembler::instructions::{InstructionFormat, InstructionSet, InstructionType};
+use crate::assembler::registers::ABI_NAMES;
+use crate::assembler::SymbolMap;
+
+const OP_LOAD: u32 = 0b0000011;
+const OP_IMM_32: u32 = 0b0011011;
+const OP_JALR: u32 = 0b1100111;
+const OP_SYSTEM: u32 = 0b1110011;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub mnemonic: &'static str,
+    pub operands: Vec<String>,
+    pub target: Option<u32>  // Address a branch or jal goes to
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.operands.is_empty() {
+            true => write!(f, "{}", self.mnemonic),
+            false => write!(f, "{} {}", self.mnemonic, self.operands.join(", "))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Disassembler {
+    instructions: InstructionSet,
+    labels: HashMap<u32, String>  // Names of branch and jump targets
+}
+
+impl Disassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Name the targets after the labels of a symbol map, the first one by name when several share an address
+    pub fn with_symbols(symbol_map: &SymbolMap) -> Self {
+        let mut disassembler = Self::new();
+
+        for symbol in symbol_map.symbols().iter().filter(|s| s.section.is_some()) {
+            let address = symbol.value as u32;
+
+            match disassembler.labels.get(&address) {
+                Some(name) if *name <= symbol.name => {}
+                _ => disassembler.add_label(address, &symbol.name)
+            }
+        }
+
+        disassembler
+    }
+
+    pub fn add_label(&mut self, address: u32, name: &str) {
+        self.labels.insert(address, name.to_string());
+    }
+
+    pub fn label(&self, address: u32) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    // The instruction a word at an address encodes, or None if it isn't one of the table
+    pub fn decode(&self, word: u32, address: u32) -> Option<Instruction> {
+        let (mnemonic, fmt) = lookup(&self.instructions, word)?;
+
+        let rd = (word >> 7) & 0x1F;
+        let rs1 = (word >> 15) & 0x1F;
+        let rs2 = (word >> 20) & 0x1F;
+        let register = |number: u32| ABI_NAMES[number as usize].to_string();
+        let mut target = None;
+
+        let operands = match fmt.fmt {
+            InstructionType::R => vec![register(rd), register(rs1), register(rs2)],
+
+            InstructionType::I if fmt.opcode == OP_SYSTEM && fmt.funct3 == Some(0) => Vec::new(),
+
+            // CSR instructions take a register or a 5-bit immediate as their source
+            InstructionType::I if fmt.opcode == OP_SYSTEM => {
+                let source = match fmt.funct3.unwrap_or(0) & 0b100 {
+                    0 => register(rs1),
+                    _ => rs1.to_string()
+                };
+
+                vec![register(rd), csr_name(word >> 20), source]
+            }
+
+            InstructionType::I if fmt.opcode == OP_LOAD || fmt.opcode == OP_JALR => {
+                vec![register(rd), format!("{}({})", i_immediate(word), register(rs1))]
+            }
+
+            // Shifts only show the shift amount below funct7
+            InstructionType::I if fmt.funct7.is_some() => {
+                vec![register(rd), register(rs1), ((word >> 20) & 0x3F).to_string()]
+            }
+
+            InstructionType::I => vec![register(rd), register(rs1), i_immediate(word).to_string()],
+
+            InstructionType::S => vec![register(rs2), format!("{}({})", s_immediate(word), register(rs1))],
+
+            InstructionType::B => {
+                let offset = b_immediate(word);
+                target = Some(address.wrapping_add(offset as u32));
+                vec![register(rs1), register(rs2), self.target_operand(address, offset)]
+            }
+
+            // The parser reads the full 32-bit value and encodes its upper 20 bits
+            InstructionType::U => vec![register(rd), format!("{:#x}", word & 0xFFFFF000)],
+
+            InstructionType::J => {
+                let offset = j_immediate(word);
+                target = Some(address.wrapping_add(offset as u32));
+                vec![register(rd), self.target_operand(address, offset)]
+            }
+        };
+
+        Some(Instruction { mnemonic, operands, target })
+    }
+
+    // Label at the target of a branch or jump, or the offset from the instruction
+    fn target_operand(&self, address: u32, offset: i32) -> String {
+        match self.label(address.wrapping_add(offset as u32)) {
+            Some(label) => label.to_string(),
+            None => offset.to_string()
+        }
+    }
+}
+
+// Entry of the table a word encodes
+pub(crate) fn lookup(instructions: &InstructionSet, word: u32) -> Option<(&'static str, &'static InstructionFormat)> {
+    instructions.iter().find(|(_, fmt)| matches(fmt, word))
+}
+
+// A word encodes an instruction when every fixed field of its format matches
+fn matches(fmt: &InstructionFormat, word: u32) -> bool {
+    let opcode = word & 0x7F;
+    let funct3 = (word >> 12) & 0x7;
+    let funct7 = word >> 25;
+
+    if opcode != fmt.opcode || fmt.funct3.is_some_and(|f| f != funct3) {
+        return false;
+    }
+
+    match (fmt.fmt, fmt.funct7) {
+        (InstructionType::R, Some(f)) => funct7 == f,
+
+        // ecall and ebreak keep their immediate in funct7 of the table, every other field is zero
+        (InstructionType::I, Some(imm)) if fmt.opcode == OP_SYSTEM => word >> 7 == imm << 13,
+
+        // Shift amounts are 6 bits, so funct7 is only compared above imm[5]
+        // The word variants have 5-bit shift amounts
+        (InstructionType::I, Some(f)) => {
+            word >> 26 == f >> 1 && (fmt.opcode != OP_IMM_32 || funct7 & 1 == 0)
+        }
+
+        _ => true
+    }
+}
+
+fn csr_name(address: u32) -> String {
+    CSR_ADDRESSES
+        .entries()
+        .find(|(_, csr)| **csr == address)
+        .map_or_else(|| format!("{:#x}", address), |(name, _)| name.to_string())
+}
+
+pub(crate) fn i_immediate(word: u32) -> i32 {
+    (word as i32) >> 20
+}
+
+pub(crate) fn s_immediate(word: u32) -> i32 {
+    ((word & 0xFE000000) as i32 >> 20) | ((word >> 7) & 0x1F) as i32
+}
+
+pub(crate) fn b_immediate(word: u32) -> i32 {
+    ((word & 0x80000000) as i32 >> 19)
+        | ((word & 0x80) << 4) as i32
+        | ((word >> 20) & 0x7E0) as i32
+        | ((word >> 7) & 0x1E) as i32
+}
+
+pub(crate) fn j_immediate(word: u32) -> i32 {
+    ((word & 0x80000000) as i32 >> 11)
+        | (word & 0xFF000) as i32
+        | ((word >> 9) & 0x800) as i32
+        | ((word >> 20) & 0x7FE) as i32
+}
@@ -0,0 +1,119 @@
+//! Prints disassembly listings of flat binaries and ELF files in the layout of `objdump -d`
+//!
+//! Executable sections are listed a word at a time with the address, the raw word and the
+//! instruction, under a `<label>:` header wherever a symbol is defined. Words that aren't
+//! instructions are shown as `.word` directives.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::assembler::{Binding, SectionKind};
+use crate::elf::{ElfClass, ET_REL};
+use crate::linker::object::{read_elf, ObjectSymbol, SymbolSection};
+use super::Disassembler;
+
+// A raw binary is a single section loaded at the base address, named .data like objdump -b binary does
+pub fn dump_binary(name: &str, bytes: &[u8], base: u32) -> String {
+    let mut dump = format!("\n{}:     file format binary\n", name);
+    let labels = BTreeMap::from([(base, ".data".to_string())]);
+
+    write_section(&mut dump, &Disassembler::new(), ".data", bytes, base, &labels, 8);
+    dump
+}
+
+// Every executable section of a relocatable object or an executable, with its symbols as headers
+pub fn dump_elf(name: &str, bytes: &[u8]) -> Result<String, String> {
+    let file = read_elf(bytes)?;
+
+    let (format, digits) = match file.class {
+        ElfClass::Elf32 => ("elf32-littleriscv", 8),
+        ElfClass::Elf64 => ("elf64-littleriscv", 16)
+    };
+
+    // Labels of every section by address, preferring global symbols and then the first one by name
+    // when several share an address
+    // Section symbols are skipped, the section name only labels its start when nothing else does
+    let mut symbols: Vec<&ObjectSymbol> = file.symbols.iter().collect();
+    symbols.sort_by_key(|symbol| (symbol.binding == Binding::Local, &symbol.name));
+
+    let mut labels = vec![BTreeMap::new(); file.sections.len()];
+
+    for symbol in symbols {
+        let SymbolSection::Section(idx) = symbol.section else {
+            continue;
+        };
+        let section = &file.sections[idx];
+
+        if symbol.name.is_empty() || symbol.name == section.name {
+            continue;
+        }
+
+        // Symbols of objects are section-relative
+        let address = match file.file_type {
+            ET_REL => section.address.wrapping_add(symbol.value) as u32,
+            _ => symbol.value as u32
+        };
+
+        labels[idx].entry(address).or_insert_with(|| symbol.name.clone());
+    }
+
+    let mut dump = format!("\n{}:     file format {}\n", name, format);
+
+    for (section, labels) in file.sections.iter().zip(&mut labels) {
+        if section.kind != SectionKind::Text {
+            continue;
+        }
+
+        let address = section.address as u32;
+        labels.entry(address).or_insert_with(|| section.name.clone());
+
+        let mut disassembler = Disassembler::new();
+        for (&label_address, label) in labels.iter() {
+            disassembler.add_label(label_address, label);
+        }
+
+        write_section(&mut dump, &disassembler, &section.name, &section.data, address, labels, digits);
+    }
+
+    Ok(dump)
+}
+
+// "address: word  instruction" lines, with the target address of branches and jumps as a comment
+fn write_section(
+    dump: &mut String,
+    disassembler: &Disassembler,
+    name: &str,
+    data: &[u8],
+    address: u32,
+    labels: &BTreeMap<u32, String>,
+    digits: usize
+) {
+    write!(dump, "\n\nDisassembly of section {}:\n", name).unwrap();
+
+    for (idx, chunk) in data.chunks(4).enumerate() {
+        let pc = address.wrapping_add(idx as u32 * 4);
+
+        for (label_address, label) in labels.range(pc..pc.saturating_add(4)) {
+            write!(dump, "\n{:0digits$x} <{}>:\n", label_address, label, digits = digits).unwrap();
+        }
+
+        // A tail shorter than a word can't be an instruction
+        if chunk.len() < 4 {
+            let raw: String = chunk.iter().rev().map(|b| format!("{:02x}", b)).collect();
+            let bytes: Vec<String> = chunk.iter().map(|b| format!("{:#04x}", b)).collect();
+            writeln!(dump, "{:>8x}:\t{:<8}\t.byte {}", pc, raw, bytes.join(", ")).unwrap();
+            continue;
+        }
+
+        let word = u32::from_le_bytes(chunk.try_into().unwrap());
+
+        let text = match disassembler.decode(word, pc) {
+            Some(instruction) => match instruction.target {
+                Some(target) => format!("{} # {:#x}", instruction, target),
+                None => instruction.to_string()
+            },
+            None => format!(".word {:#010x}", word)
+        };
+
+        writeln!(dump, "{:>8x}:\t{:08x}\t{}", pc, word, text).unwrap();
+    }
+}
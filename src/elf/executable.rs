@@ -0,0 +1,88 @@
+//! Builds static executables that load at the addresses the assembler laid the sections out at
+
+use crate::assembler::SectionKind;
+use crate::elf::symtab::SymbolTableBuilder;
+use crate::elf::*;
+
+// One PT_LOAD segment per permission: code (r-x), read-only data (r--), then data and .bss (rw-)
+// Loaders map whole pages, so segments that share a page are merged with the permissions of both
+// Sections are written in address order and keep their section headers and symbols for debugging
+pub fn write_executable(contents: &ElfContents, entry: u32) -> Vec<u8> {
+    let class = contents.class;
+    let sections = contents.sections;
+
+    let mut order: Vec<usize> = (0..sections.len()).collect();
+    order.sort_by_key(|&idx| (sections[idx].address, sections[idx].kind));
+
+    let mut elf_sections = vec![0; sections.len()];
+    let mut writer = ElfWriter::new(class, ET_EXEC);
+    writer.entry = entry as u64;
+
+    for &idx in &order {
+        let section = &sections[idx];
+        let (section_type, flags) = match section.kind {
+            SectionKind::Text => (SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR),
+            SectionKind::Rodata => (SHT_PROGBITS, SHF_ALLOC),
+            SectionKind::Data => (SHT_PROGBITS, SHF_ALLOC | SHF_WRITE),
+            SectionKind::Bss => (SHT_NOBITS, SHF_ALLOC | SHF_WRITE)
+        };
+
+        elf_sections[idx] = writer.add_section(ElfSection {
+            name: section.name.clone(),
+            section_type,
+            flags,
+            address: section.address as u64,
+            align: section.align as u64,
+            data: section.data.clone(),
+            size: section.size() as u64,
+            ..Default::default()
+        }) as u16;
+    }
+
+    let permissions = [
+        (PF_R | PF_X, &[SectionKind::Text][..]),
+        (PF_R, &[SectionKind::Rodata][..]),
+        (PF_R | PF_W, &[SectionKind::Data, SectionKind::Bss][..])
+    ];
+
+    let mut segments: Vec<(u32, Vec<usize>)> = permissions
+        .into_iter()
+        .map(|(flags, kinds)| {
+            let members = order
+                .iter()
+                .copied()
+                .filter(|&idx| kinds.contains(&sections[idx].kind) && sections[idx].size() > 0)
+                .collect();
+
+            (flags, members)
+        })
+        .filter(|(_, members): &(u32, Vec<usize>)| !members.is_empty())
+        .collect();
+    segments.sort_by_key(|(_, members)| sections[members[0]].address);
+
+    let first_page = |idx: usize| sections[idx].address as u64 / PAGE_SIZE;
+    let last_page = |idx: usize| (sections[idx].address as u64 + sections[idx].size() as u64 - 1) / PAGE_SIZE;
+
+    let mut merged: Vec<(u32, Vec<usize>)> = Vec::new();
+
+    for (flags, members) in segments {
+        match merged.last_mut() {
+            Some((merged_flags, merged_members))
+                if merged_members.iter().map(|&idx| last_page(idx)).max() >= Some(first_page(members[0])) =>
+            {
+                *merged_flags |= flags;
+                merged_members.extend(members);
+                merged_members.sort_by_key(|&idx| sections[idx].address);
+            }
+            _ => merged.push((flags, members))
+        }
+    }
+
+    for (flags, members) in merged {
+        let sections = members.iter().map(|&idx| elf_sections[idx] as u32).collect();
+        writer.add_segment(ElfSegment { flags, sections });
+    }
+
+    SymbolTableBuilder::build(contents, &elf_sections, true).add_sections(&mut writer, class);
+    writer.write()
+}
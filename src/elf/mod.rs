@@ -0,0 +1,467 @@
+//! Writes RISC-V ELF files: relocatable objects and static executables
+
+pub mod executable;
+pub mod object;
+mod symtab;
+
+use std::collections::HashMap;
+use crate::assembler::{Assembler, Binding, Section, Symbol, Xlen};
+
+pub const EM_RISCV: u16 = 243;
+
+// File types
+pub const ET_REL: u16 = 1;
+pub const ET_EXEC: u16 = 2;
+
+// Section types
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_RELA: u32 = 4;
+pub const SHT_NOBITS: u32 = 8;
+
+// Section flags
+pub const SHF_WRITE: u64 = 0x1;
+pub const SHF_ALLOC: u64 = 0x2;
+pub const SHF_EXECINSTR: u64 = 0x4;
+pub const SHF_INFO_LINK: u64 = 0x40;
+
+// Symbol bindings and types
+pub const STB_LOCAL: u8 = 0;
+pub const STB_GLOBAL: u8 = 1;
+pub const STB_WEAK: u8 = 2;
+pub const STT_NOTYPE: u8 = 0;
+pub const STT_SECTION: u8 = 3;
+
+// Segment types and flags
+pub const PT_LOAD: u32 = 1;
+pub const PF_X: u32 = 0x1;
+pub const PF_W: u32 = 0x2;
+pub const PF_R: u32 = 0x4;
+
+// Loadable sections are placed in the file at the same offset within a page as in memory
+pub const PAGE_SIZE: u64 = 0x1000;
+
+// Special section indices
+pub const SHN_UNDEF: u16 = 0;
+pub const SHN_ABS: u16 = 0xFFF1;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ElfClass {
+    Elf32,
+    Elf64
+}
+
+impl ElfClass {
+    pub fn header_size(self) -> usize {
+        match self {
+            Self::Elf32 => 52,
+            Self::Elf64 => 64
+        }
+    }
+
+    pub fn section_header_size(self) -> usize {
+        match self {
+            Self::Elf32 => 40,
+            Self::Elf64 => 64
+        }
+    }
+
+    pub fn symbol_size(self) -> usize {
+        match self {
+            Self::Elf32 => 16,
+            Self::Elf64 => 24
+        }
+    }
+
+    pub fn program_header_size(self) -> usize {
+        match self {
+            Self::Elf32 => 32,
+            Self::Elf64 => 56
+        }
+    }
+
+    pub fn rela_size(self) -> usize {
+        match self {
+            Self::Elf32 => 12,
+            Self::Elf64 => 24
+        }
+    }
+
+    // Size of addresses and file offsets
+    pub fn word_size(self) -> usize {
+        match self {
+            Self::Elf32 => 4,
+            Self::Elf64 => 8
+        }
+    }
+}
+
+impl From<Xlen> for ElfClass {
+    fn from(xlen: Xlen) -> Self {
+        match xlen {
+            Xlen::Rv32 => Self::Elf32,
+            Xlen::Rv64 => Self::Elf64
+        }
+    }
+}
+
+// What goes into an ELF file: the sections and symbols of an assembled file or of a linked program
+#[derive(Copy, Clone)]
+pub struct ElfContents<'a> {
+    pub class: ElfClass,
+    pub sections: &'a [Section],
+    pub symbols: &'a HashMap<String, Symbol>,
+    pub bindings: &'a HashMap<String, Binding>
+}
+
+impl<'a> From<&'a Assembler> for ElfContents<'a> {
+    fn from(assembler: &'a Assembler) -> Self {
+        Self {
+            class: ElfClass::from(assembler.xlen()),
+            sections: assembler.sections(),
+            symbols: assembler.symbols(),
+            bindings: assembler.bindings()
+        }
+    }
+}
+
+// A section of the file, its index is its position plus one (index 0 is the null section)
+#[derive(Debug, Clone, Default)]
+pub struct ElfSection {
+    pub name: String,
+    pub section_type: u32,
+    pub flags: u64,
+    pub address: u64,
+    pub align: u64,
+    pub data: Vec<u8>,
+    pub size: u64,  // In memory, only differs from data.len() for SHT_NOBITS
+    pub link: u32,
+    pub info: u32,
+    pub entry_size: u64
+}
+
+// A PT_LOAD segment made of consecutive sections, given by their indices
+#[derive(Debug, Clone)]
+pub struct ElfSegment {
+    pub flags: u32,
+    pub sections: Vec<u32>
+}
+
+// Entry of a symbol table section
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ElfSymbol {
+    pub name: u32,  // Offset in the string table
+    pub value: u64,
+    pub size: u64,
+    pub binding: u8,
+    pub symbol_type: u8,
+    pub section: u16
+}
+
+impl ElfSymbol {
+    pub fn write(&self, class: ElfClass, out: &mut Vec<u8>) {
+        let info = (self.binding << 4) | self.symbol_type;
+
+        match class {
+            ElfClass::Elf32 => {
+                put_u32(out, self.name);
+                put_u32(out, self.value as u32);
+                put_u32(out, self.size as u32);
+                out.push(info);
+                out.push(0);
+                put_u16(out, self.section);
+            }
+            ElfClass::Elf64 => {
+                put_u32(out, self.name);
+                out.push(info);
+                out.push(0);
+                put_u16(out, self.section);
+                put_u64(out, self.value);
+                put_u64(out, self.size);
+            }
+        }
+    }
+}
+
+// Entry of a relocation section with explicit addends (SHT_RELA)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ElfRela {
+    pub offset: u64,
+    pub symbol: u32,  // Index in the symbol table
+    pub relocation_type: u32,
+    pub addend: i64
+}
+
+impl ElfRela {
+    pub fn write(&self, class: ElfClass, out: &mut Vec<u8>) {
+        match class {
+            ElfClass::Elf32 => {
+                put_u32(out, self.offset as u32);
+                put_u32(out, (self.symbol << 8) | (self.relocation_type & 0xFF));
+                put_u32(out, self.addend as u32);
+            }
+            ElfClass::Elf64 => {
+                put_u64(out, self.offset);
+                put_u64(out, ((self.symbol as u64) << 32) | self.relocation_type as u64);
+                put_u64(out, self.addend as u64);
+            }
+        }
+    }
+}
+
+// Names of a string table section, which starts with the empty name
+#[derive(Debug, Clone)]
+pub struct StringTable {
+    data: Vec<u8>
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self { data: vec![0] }
+    }
+
+    // Offset of the name in the table
+    pub fn add(&mut self, name: &str) -> u32 {
+        if name.is_empty() {
+            return 0;
+        }
+
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.push(0);
+        offset
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+// Lays out the sections of an ELF file after its header and program headers,
+// followed by .shstrtab and the section headers
+pub struct ElfWriter {
+    class: ElfClass,
+    file_type: u16,
+    pub entry: u64,
+    sections: Vec<ElfSection>,
+    segments: Vec<ElfSegment>
+}
+
+impl ElfWriter {
+    pub fn new(class: ElfClass, file_type: u16) -> Self {
+        Self {
+            class,
+            file_type,
+            entry: 0,
+            sections: Vec::new(),
+            segments: Vec::new()
+        }
+    }
+
+    // Index the next section will get
+    pub fn next_index(&self) -> u32 {
+        self.sections.len() as u32 + 1
+    }
+
+    pub fn add_segment(&mut self, segment: ElfSegment) {
+        self.segments.push(segment);
+    }
+
+    // Add a section and return its index
+    pub fn add_section(&mut self, section: ElfSection) -> u32 {
+        self.sections.push(section);
+        self.sections.len() as u32
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let class = self.class;
+        let mut names = StringTable::new();
+        let name_offsets: Vec<u32> = self.sections.iter().map(|s| names.add(&s.name)).collect();
+
+        let shstrtab = ElfSection {
+            name: ".shstrtab".to_string(),
+            section_type: SHT_STRTAB,
+            align: 1,
+            ..Default::default()
+        };
+        let shstrtab_name = names.add(&shstrtab.name);
+        let shstrtab = ElfSection { data: names.into_bytes(), ..shstrtab };
+
+        let sections: Vec<(&ElfSection, u32)> = self.sections
+            .iter()
+            .zip(name_offsets)
+            .chain([(&shstrtab, shstrtab_name)])
+            .collect();
+
+        // Section contents, after the headers
+        let program_headers = class.header_size() as u64;
+        let mut out = vec![0; class.header_size() + self.segments.len() * class.program_header_size()];
+        let mut offsets = Vec::with_capacity(sections.len());
+
+        for (section, _) in &sections {
+            if self.file_type == ET_EXEC && section.flags & SHF_ALLOC != 0 {
+                // Sections of a segment end up as far apart in the file as in memory
+                let padding = section.address.wrapping_sub(out.len() as u64) % PAGE_SIZE;
+                out.resize(out.len() + padding as usize, 0);
+            } else {
+                pad_to(&mut out, section.align.max(1) as usize);
+            }
+
+            offsets.push(out.len() as u64);
+
+            if section.section_type != SHT_NOBITS {
+                out.extend_from_slice(&section.data);
+            }
+        }
+
+        // Section headers, starting with the null section
+        pad_to(&mut out, class.word_size());
+        let section_headers = out.len() as u64;
+        out.resize(out.len() + class.section_header_size(), 0);
+
+        for ((section, name), offset) in sections.iter().zip(&offsets) {
+            let offset = *offset;
+
+            let size = match section.section_type {
+                SHT_NOBITS => section.size,
+                _ => section.data.len() as u64
+            };
+
+            put_u32(&mut out, *name);
+            put_u32(&mut out, section.section_type);
+            put_word(&mut out, class, section.flags);
+            put_word(&mut out, class, section.address);
+            put_word(&mut out, class, offset);
+            put_word(&mut out, class, size);
+            put_u32(&mut out, section.link);
+            put_u32(&mut out, section.info);
+            put_word(&mut out, class, section.align);
+            put_word(&mut out, class, section.entry_size);
+        }
+
+        let header = self.header(section_headers, sections.len() as u16 + 1);
+        out[..header.len()].copy_from_slice(&header);
+
+        let mut headers = Vec::with_capacity(self.segments.len() * class.program_header_size());
+
+        for segment in &self.segments {
+            self.write_program_header(segment, &offsets, &mut headers);
+        }
+
+        let start = program_headers as usize;
+        out[start..start + headers.len()].copy_from_slice(&headers);
+        out
+    }
+
+    fn write_program_header(&self, segment: &ElfSegment, offsets: &[u64], out: &mut Vec<u8>) {
+        let class = self.class;
+        let section = |index: u32| &self.sections[index as usize - 1];
+
+        let first = segment.sections[0];
+        let address = section(first).address;
+        let offset = offsets[first as usize - 1];
+
+        // Zero-initialized sections only take up memory
+        let file_end = segment.sections
+            .iter()
+            .filter(|&&index| section(index).section_type != SHT_NOBITS)
+            .map(|&index| section(index).address + section(index).data.len() as u64)
+            .max()
+            .unwrap_or(address);
+        let memory_end = segment.sections
+            .iter()
+            .map(|&index| section(index).address + section(index).size)
+            .max()
+            .unwrap_or(address);
+
+        put_u32(out, PT_LOAD);
+
+        if class == ElfClass::Elf64 {
+            put_u32(out, segment.flags);
+        }
+
+        put_word(out, class, offset);
+        put_word(out, class, address);
+        put_word(out, class, address);  // Physical address
+        put_word(out, class, file_end - address);
+        put_word(out, class, memory_end - address);
+
+        if class == ElfClass::Elf32 {
+            put_u32(out, segment.flags);
+        }
+
+        put_word(out, class, PAGE_SIZE);
+    }
+
+    fn header(&self, section_headers: u64, section_count: u16) -> Vec<u8> {
+        let class = self.class;
+        let mut header = Vec::with_capacity(class.header_size());
+
+        // Identification: magic, class, little-endian, version 1, System V ABI
+        header.extend_from_slice(b"\x7FELF");
+        header.push(match class {
+            ElfClass::Elf32 => 1,
+            ElfClass::Elf64 => 2
+        });
+        header.extend_from_slice(&[1, 1, 0]);
+        header.resize(16, 0);
+
+        put_u16(&mut header, self.file_type);
+        put_u16(&mut header, EM_RISCV);
+        put_u32(&mut header, 1);
+        put_word(&mut header, class, self.entry);
+
+        // Program headers follow the file header
+        match self.segments.len() {
+            0 => put_word(&mut header, class, 0),
+            _ => put_word(&mut header, class, class.header_size() as u64)
+        }
+
+        put_word(&mut header, class, section_headers);
+        put_u32(&mut header, 0);  // Flags: soft-float ABI, no compressed instructions
+        put_u16(&mut header, class.header_size() as u16);
+
+        match self.segments.len() {
+            0 => put_u16(&mut header, 0),
+            _ => put_u16(&mut header, class.program_header_size() as u16)
+        }
+
+        put_u16(&mut header, self.segments.len() as u16);
+        put_u16(&mut header, class.section_header_size() as u16);
+        put_u16(&mut header, section_count);
+        put_u16(&mut header, section_count - 1);  // .shstrtab is the last section
+
+        header
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+// An address or file offset, sized by the class
+fn put_word(out: &mut Vec<u8>, class: ElfClass, value: u64) {
+    match class {
+        ElfClass::Elf32 => put_u32(out, value as u32),
+        ElfClass::Elf64 => put_u64(out, value)
+    }
+}
+
+fn pad_to(out: &mut Vec<u8>, align: usize) {
+    out.resize(out.len().next_multiple_of(align), 0);
+}
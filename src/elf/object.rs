@@ -0,0 +1,70 @@
+//! Builds relocatable object files (.o) from the output of the assembler
+
+use crate::assembler::SectionKind;
+use crate::elf::symtab::SymbolTableBuilder;
+use crate::elf::*;
+
+// Sections in the order they were first used, then one .rela section per section with relocations,
+// then .symtab and .strtab
+pub fn write_object(contents: &ElfContents) -> Vec<u8> {
+    let class = contents.class;
+    let sections = contents.sections;
+    let elf_sections: Vec<u16> = (1..=sections.len() as u16).collect();
+    let symbols = SymbolTableBuilder::build(contents, &elf_sections, false);
+
+    let mut writer = ElfWriter::new(class, ET_REL);
+
+    for section in sections {
+        let (section_type, flags) = match section.kind {
+            SectionKind::Text => (SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR),
+            SectionKind::Rodata => (SHT_PROGBITS, SHF_ALLOC),
+            SectionKind::Data => (SHT_PROGBITS, SHF_ALLOC | SHF_WRITE),
+            SectionKind::Bss => (SHT_NOBITS, SHF_ALLOC | SHF_WRITE)
+        };
+
+        writer.add_section(ElfSection {
+            name: section.name.clone(),
+            section_type,
+            flags,
+            align: section.align as u64,
+            data: section.data.clone(),
+            size: section.size() as u64,
+            ..Default::default()
+        });
+    }
+
+    let relocated = sections.iter().filter(|s| !s.relocations.is_empty()).count() as u32;
+    let symtab_index = sections.len() as u32 + relocated + 1;
+
+    for (idx, section) in sections.iter().enumerate() {
+        if section.relocations.is_empty() {
+            continue;
+        }
+
+        let mut data = Vec::with_capacity(section.relocations.len() * class.rela_size());
+
+        for relocation in &section.relocations {
+            ElfRela {
+                offset: relocation.offset as u64,
+                symbol: symbols.index(&relocation.symbol),
+                relocation_type: relocation.kind.elf_type(),
+                addend: relocation.addend
+            }.write(class, &mut data);
+        }
+
+        writer.add_section(ElfSection {
+            name: format!(".rela{}", section.name),
+            section_type: SHT_RELA,
+            flags: SHF_INFO_LINK,
+            align: class.word_size() as u64,
+            data,
+            link: symtab_index,
+            info: idx as u32 + 1,
+            entry_size: class.rela_size() as u64,
+            ..Default::default()
+        });
+    }
+
+    symbols.add_sections(&mut writer, class);
+    writer.write()
+}
@@ -0,0 +1,207 @@
+//! Builds the .symtab and .strtab sections from the assembler symbols
+
+use std::collections::HashMap;
+use crate::assembler::relocation::RelocationSymbol;
+use crate::assembler::{Binding, Symbol, SymbolKind};
+use crate::elf::*;
+
+// Symbol table entries: section symbols, local symbols, then global and weak symbols as ELF requires
+pub(crate) struct SymbolTableBuilder {
+    entries: Vec<(String, ElfSymbol)>,
+    first_global: u32,
+    named: HashMap<String, u32>,
+    pcrel_hi: HashMap<(usize, u32), u32>
+}
+
+impl SymbolTableBuilder {
+    // elf_sections maps assembler sections to ELF section indices
+    // Labels are section-relative in object files and absolute addresses in executables
+    pub(crate) fn build(contents: &ElfContents, elf_sections: &[u16], absolute: bool) -> Self {
+        let mut builder = Self {
+            entries: Vec::new(),
+            first_global: 0,
+            named: HashMap::new(),
+            pcrel_hi: HashMap::new()
+        };
+
+        let sections = contents.sections;
+        let binding = |name: &str| contents.bindings.get(name).copied().unwrap_or(Binding::Local);
+        let base = |section: usize| if absolute { sections[section].address as u64 } else { 0 };
+
+        // Defined symbols by section and address, so that the output doesn't depend on hashing order
+        let mut defined: Vec<(&String, &Symbol)> = contents.symbols
+            .iter()
+            .filter(|(name, _)| !name.starts_with(".L"))
+            .collect();
+        defined.sort_by_key(|(name, symbol)| (section_index(symbol), symbol.value, *name));
+
+        let (locals, globals): (Vec<_>, Vec<_>) = defined
+            .into_iter()
+            .partition(|(name, _)| binding(name) == Binding::Local);
+
+        // Symbols that are used or declared but not defined here, executables can't have any
+        let mut undefined: Vec<String> = sections
+            .iter()
+            .flat_map(|section| &section.relocations)
+            .filter_map(|relocation| match &relocation.symbol {
+                RelocationSymbol::Named(name) => Some(name.clone()),
+                _ => None
+            })
+            .chain(contents.bindings.keys().filter(|_| !absolute).cloned())
+            .filter(|name| !contents.symbols.contains_key(name))
+            .collect();
+        undefined.sort();
+        undefined.dedup();
+
+        for (idx, &elf_section) in elf_sections.iter().enumerate() {
+            builder.push(String::new(), ElfSymbol {
+                name: 0,
+                value: base(idx),
+                size: 0,
+                binding: STB_LOCAL,
+                symbol_type: STT_SECTION,
+                section: elf_section
+            });
+        }
+
+        let symbol_entry = |symbol: &Symbol, binding: u8| match symbol.kind {
+            SymbolKind::Label { section } => ElfSymbol {
+                name: 0,
+                value: base(section) + symbol.value as u64,
+                size: 0,
+                binding,
+                symbol_type: STT_NOTYPE,
+                section: elf_sections[section]
+            },
+            SymbolKind::Constant { .. } => ElfSymbol {
+                name: 0,
+                value: symbol.value as u64,
+                size: 0,
+                binding,
+                symbol_type: STT_NOTYPE,
+                section: SHN_ABS
+            }
+        };
+
+        for (name, symbol) in locals {
+            builder.push_named(name, symbol_entry(symbol, STB_LOCAL));
+        }
+
+        // Labels for the auipc of every relocated %pcrel_lo
+        for section in sections {
+            for relocation in &section.relocations {
+                let RelocationSymbol::PcrelHi { section, offset } = relocation.symbol else {
+                    continue;
+                };
+
+                if builder.pcrel_hi.contains_key(&(section, offset)) {
+                    continue;
+                }
+
+                let index = builder.push(format!(".Lpcrel_hi{}", builder.pcrel_hi.len()), ElfSymbol {
+                    name: 0,
+                    value: base(section) + offset as u64,
+                    size: 0,
+                    binding: STB_LOCAL,
+                    symbol_type: STT_NOTYPE,
+                    section: elf_sections[section]
+                });
+
+                builder.pcrel_hi.insert((section, offset), index);
+            }
+        }
+
+        builder.first_global = builder.entries.len() as u32 + 1;
+
+        for (name, symbol) in globals {
+            builder.push_named(name, symbol_entry(symbol, elf_binding(binding(name))));
+        }
+
+        for name in undefined {
+            let symbol = ElfSymbol {
+                name: 0,
+                value: 0,
+                size: 0,
+                binding: match binding(&name) {
+                    Binding::Weak => STB_WEAK,
+                    _ => STB_GLOBAL
+                },
+                symbol_type: STT_NOTYPE,
+                section: SHN_UNDEF
+            };
+
+            builder.push_named(&name, symbol);
+        }
+
+        builder
+    }
+
+    // Index of the symbol a relocation refers to
+    pub(crate) fn index(&self, symbol: &RelocationSymbol) -> u32 {
+        match symbol {
+            RelocationSymbol::Named(name) => self.named[name],
+            RelocationSymbol::Section(section) => *section as u32 + 1,
+            RelocationSymbol::PcrelHi { section, offset } => self.pcrel_hi[&(*section, *offset)]
+        }
+    }
+
+    // Append .symtab and .strtab to the file
+    pub(crate) fn add_sections(&self, writer: &mut ElfWriter, class: ElfClass) {
+        let mut symtab = Vec::with_capacity((self.entries.len() + 1) * class.symbol_size());
+        let mut names = StringTable::new();
+
+        // Index 0 is the undefined symbol
+        symtab.resize(class.symbol_size(), 0);
+
+        for (name, symbol) in &self.entries {
+            ElfSymbol { name: names.add(name), ..*symbol }.write(class, &mut symtab);
+        }
+
+        let strtab_index = writer.next_index() + 1;
+
+        writer.add_section(ElfSection {
+            name: ".symtab".to_string(),
+            section_type: SHT_SYMTAB,
+            align: class.word_size() as u64,
+            data: symtab,
+            link: strtab_index,
+            info: self.first_global,
+            entry_size: class.symbol_size() as u64,
+            ..Default::default()
+        });
+
+        writer.add_section(ElfSection {
+            name: ".strtab".to_string(),
+            section_type: SHT_STRTAB,
+            align: 1,
+            data: names.into_bytes(),
+            ..Default::default()
+        });
+    }
+
+    fn push(&mut self, name: String, symbol: ElfSymbol) -> u32 {
+        self.entries.push((name, symbol));
+        self.entries.len() as u32
+    }
+
+    fn push_named(&mut self, name: &str, symbol: ElfSymbol) {
+        let index = self.push(name.to_string(), symbol);
+        self.named.insert(name.to_string(), index);
+    }
+}
+
+// Labels come before constants in the symbol table
+fn section_index(symbol: &Symbol) -> usize {
+    match symbol.kind {
+        SymbolKind::Label { section } => section,
+        SymbolKind::Constant { .. } => usize::MAX
+    }
+}
+
+fn elf_binding(binding: Binding) -> u8 {
+    match binding {
+        Binding::Local => STB_LOCAL,
+        Binding::Global => STB_GLOBAL,
+        Binding::Weak => STB_WEAK
+    }
+}
@@ -1 +1,5 @@
-pub mod assembler;
\ No newline at end of file
+pub mod assembler;
+pub mod elf;
+pub mod linker;
+pub mod disassembler;
+pub mod simulator;
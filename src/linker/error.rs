@@ -0,0 +1,51 @@
+//! Implements the LinkerError enum for handling errors in the linker
+
+use std::{fmt, io};
+use crate::assembler::AssemblerError;
+
+#[derive(Debug, PartialEq)]
+pub enum LinkerError {
+    IOError(String),
+    InvalidObject(String, String),             // File and what is wrong with it
+    UndefinedSymbols(Vec<(String, String)>),   // Symbol and the file that refers to it
+    DuplicateSymbol(String, String, String),   // Symbol and the two files that define it
+    RelocationError(String, String),           // File and the relocation that doesn't fit
+    UndefinedEntry(String),
+    Layout(AssemblerError),                    // The memory layout doesn't fit the program
+    Assembler(String, AssemblerError)          // Source file that didn't assemble
+}
+
+impl fmt::Display for LinkerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(e) => write!(f, "IO Error: {}", e),
+            Self::InvalidObject(file, e) => write!(f, "Invalid Object: {}: {}", file, e),
+            Self::UndefinedSymbols(symbols) => {
+                for (idx, (name, file)) in symbols.iter().enumerate() {
+                    if idx > 0 {
+                        writeln!(f)?;
+                    }
+
+                    write!(f, "Undefined Symbol: {} (referenced in {})", name, file)?;
+                }
+
+                Ok(())
+            }
+            Self::DuplicateSymbol(name, first, second) => {
+                write!(f, "Duplicate Symbol: {} (defined in {} and {})", name, first, second)
+            }
+            Self::RelocationError(file, e) => write!(f, "Relocation Error: {}: {}", file, e),
+            Self::UndefinedEntry(name) => write!(f, "Undefined Entry: {}", name),
+            Self::Layout(e) => write!(f, "{}", e),
+            Self::Assembler(file, e) => write!(f, "{}: {}", file, e)
+        }
+    }
+}
+
+impl std::error::Error for LinkerError {}
+
+impl From<io::Error> for LinkerError {
+    fn from(e: io::Error) -> Self {
+        Self::IOError(e.to_string())
+    }
+}
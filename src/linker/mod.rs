@@ -0,0 +1,517 @@
+//! Links assembled files and relocatable objects into a single program
+//!
+//! Sections with the same name are merged in input order, .globl and .weak symbols are resolved
+//! across files and relocations are applied once every section has its final address
+
+pub mod object;
+mod error;
+
+use std::collections::HashMap;
+use std::fs;
+use crate::assembler::expr::{hi20, lo12};
+use crate::assembler::relocation::RelocationKind;
+use crate::assembler::section::{self, Section};
+use crate::assembler::*;
+use crate::elf::{self, ElfClass, ElfContents};
+
+pub use error::LinkerError;
+use object::{read_object, ObjectFile, ObjectRelocation, SymbolSection};
+
+// Output section and offset of every section of every input
+type Placements = Vec<Vec<(usize, u32)>>;
+
+// An input of the link and the name it is reported under
+struct Input {
+    name: String,
+    object: ObjectFile
+}
+
+// Where a global symbol is defined
+#[derive(Debug, Copy, Clone)]
+struct Definition {
+    value: Symbol,  // Label in an output section or constant
+    binding: Binding,
+    input: Option<usize>  // None for symbols of the memory layout
+}
+
+pub struct Linker {
+    base_address: u32,             // Address of the first section
+    layout: Option<MemoryLayout>,  // Places the sections instead of the base address
+    entry: Option<String>,         // Entry symbol of executables
+    inputs: Vec<Input>
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self {
+            base_address: 0,
+            layout: None,
+            entry: None,
+            inputs: Vec::new()
+        }
+    }
+
+    // Address the sections are laid out from, 0 by default
+    pub fn set_base_address(&mut self, address: u32) {
+        self.base_address = address;
+    }
+
+    // Symbol executables start at instead of _start
+    pub fn set_entry(&mut self, name: &str) {
+        self.entry = Some(name.to_string());
+    }
+
+    // Place the sections in memory regions instead of from the base address (-T script)
+    pub fn set_layout(&mut self, layout: MemoryLayout) {
+        self.layout = Some(layout);
+    }
+
+    // Assemble a source file as an object and add it to the link
+    pub fn add_source(&mut self, assembler: &mut Assembler, path: &str) -> Result<(), LinkerError> {
+        let object = assembler
+            .assemble_object(path)
+            .map_err(|e| LinkerError::Assembler(path.to_string(), e))?;
+
+        self.add_object(path, &object)
+    }
+
+    // Add a relocatable object file to the link
+    pub fn add_object_file(&mut self, path: &str) -> Result<(), LinkerError> {
+        let bytes = fs::read(path).map_err(|e| LinkerError::IOError(format!("{}: {}", path, e)))?;
+        self.add_object(path, &bytes)
+    }
+
+    // Add the contents of an object file, name is used in error messages
+    pub fn add_object(&mut self, name: &str, bytes: &[u8]) -> Result<(), LinkerError> {
+        let object = read_object(bytes).map_err(|e| LinkerError::InvalidObject(name.to_string(), e))?;
+
+        if let Some(first) = self.inputs.first().filter(|first| first.object.class != object.class) {
+            return Err(LinkerError::InvalidObject(name.to_string(), format!(
+                "{:?} object can't be linked with the {:?} object {}",
+                object.class,
+                first.object.class,
+                first.name
+            )));
+        }
+
+        self.inputs.push(Input { name: name.to_string(), object });
+        Ok(())
+    }
+
+    pub fn link(&self) -> Result<LinkedProgram, LinkerError> {
+        self.link_pages(None)
+    }
+
+    // Link for a static ELF executable: segments start on their own page
+    pub fn link_executable(&self) -> Result<LinkedProgram, LinkerError> {
+        self.link_pages(Some(elf::PAGE_SIZE as u32))
+    }
+
+    fn link_pages(&self, page_size: Option<u32>) -> Result<LinkedProgram, LinkerError> {
+        let class = self.inputs.first().map_or(ElfClass::Elf32, |input| input.object.class);
+
+        let (mut sections, placements) = self.merge_sections()?;
+
+        let (base_address, layout_symbols) = match &self.layout {
+            Some(layout) => {
+                let symbols = layout
+                    .place(&mut sections, &|name| self.defines(name))
+                    .map_err(LinkerError::Layout)?;
+
+                (section::lowest_address(&sections), symbols)
+            }
+            None => {
+                section::layout(&mut sections, self.base_address, page_size).map_err(LinkerError::Layout)?;
+                (self.base_address, HashMap::new())
+            }
+        };
+
+        let definitions = self.resolve_symbols(&placements, layout_symbols)?;
+
+        for (input_idx, input) in self.inputs.iter().enumerate() {
+            for (section_idx, input_section) in input.object.sections.iter().enumerate() {
+                let (output, offset) = placements[input_idx][section_idx];
+
+                for relocation in &input_section.relocations {
+                    let value = self
+                        .relocate(input_idx, section_idx, relocation, &sections, &placements, &definitions)
+                        .map_err(|e| LinkerError::RelocationError(input.name.clone(), e))?;
+
+                    patch(&mut sections[output], offset + relocation.offset, relocation.kind, value)
+                        .map_err(|e| LinkerError::RelocationError(input.name.clone(), e))?;
+                }
+            }
+        }
+
+        let mut symbols = HashMap::new();
+        let mut bindings = HashMap::new();
+
+        for (name, definition) in &definitions {
+            symbols.insert(name.clone(), definition.value);
+            bindings.insert(name.clone(), definition.binding);
+        }
+
+        let mut program = LinkedProgram {
+            class,
+            base_address,
+            entry: 0,
+            sections,
+            symbols,
+            bindings
+        };
+
+        let entry = self.entry.as_deref().or(self.layout.as_ref().and_then(|l| l.entry()));
+
+        program.entry = match entry {
+            Some(name) => program
+                .symbol_address(name)
+                .ok_or_else(|| LinkerError::UndefinedEntry(name.to_string()))?,
+            None => program.symbol_address("_start").unwrap_or_else(|| {
+                program.sections
+                    .iter()
+                    .find(|s| s.name == ".text")
+                    .map_or(base_address, |s| s.address)
+            })
+        };
+
+        Ok(program)
+    }
+
+    // Concatenate the sections of every input by name, each one aligned as its object asks
+    fn merge_sections(&self) -> Result<(Vec<Section>, Placements), LinkerError> {
+        let mut sections: Vec<Section> = Vec::new();
+        let mut placements = Vec::with_capacity(self.inputs.len());
+
+        for input in &self.inputs {
+            let mut placement = Vec::with_capacity(input.object.sections.len());
+
+            for input_section in &input.object.sections {
+                let output = match sections.iter().position(|s| s.name == input_section.name) {
+                    Some(output) => output,
+                    None => {
+                        let mut section = Section::new(&input_section.name, input_section.kind);
+                        section.align = 1;
+                        sections.push(section);
+                        sections.len() - 1
+                    }
+                };
+
+                let section = &mut sections[output];
+                let offset = align_up(section.size(), input_section.align);
+                let padding = offset - section.size();
+                section.align = section.align.max(input_section.align);
+
+                if section.kind == SectionKind::Bss {
+                    section.reserve(padding + input_section.size);
+                } else {
+                    let mut data = vec![0; padding as usize];
+                    data.extend_from_slice(&input_section.data);
+                    section.emit(&data).map_err(|e| LinkerError::Assembler(input.name.clone(), e))?;
+                }
+
+                placement.push((output, offset));
+            }
+
+            placements.push(placement);
+        }
+
+        Ok((sections, placements))
+    }
+
+    // Some input has a global definition of the symbol
+    fn defines(&self, name: &str) -> bool {
+        self.inputs.iter().any(|input| {
+            input.object.symbols.iter().any(|symbol| {
+                symbol.name == name && symbol.binding != Binding::Local && symbol.section != SymbolSection::Undefined
+            })
+        })
+    }
+
+    // Global definitions by name: a strong definition replaces a weak one, two strong ones clash
+    // The memory layout defines its symbols like another input that comes last
+    // Undefined references are reported all at once, except weak ones which resolve to 0
+    fn resolve_symbols(
+        &self,
+        placements: &Placements,
+        layout_symbols: HashMap<String, u32>
+    ) -> Result<HashMap<String, Definition>, LinkerError> {
+        let mut definitions: HashMap<String, Definition> = HashMap::new();
+
+        for (input_idx, input) in self.inputs.iter().enumerate() {
+            for symbol in &input.object.symbols {
+                if symbol.binding == Binding::Local || symbol.section == SymbolSection::Undefined {
+                    continue;
+                }
+
+                let value = match symbol.section {
+                    SymbolSection::Section(section) => {
+                        let (output, offset) = placements[input_idx][section];
+
+                        Symbol {
+                            kind: SymbolKind::Label { section: output },
+                            value: offset as i64 + symbol.value as i64
+                        }
+                    }
+                    _ => Symbol {
+                        kind: SymbolKind::Constant { redefinable: false },
+                        value: symbol.value as i64
+                    }
+                };
+
+                let definition = Definition { value, binding: symbol.binding, input: Some(input_idx) };
+
+                match definitions.get(&symbol.name) {
+                    Some(existing) if existing.binding == Binding::Global && symbol.binding == Binding::Global => {
+                        return Err(LinkerError::DuplicateSymbol(
+                            symbol.name.clone(),
+                            self.input_name(existing.input),
+                            input.name.clone()
+                        ));
+                    }
+
+                    // The first of several weak definitions wins
+                    Some(existing) if existing.binding == Binding::Global || symbol.binding == Binding::Weak => continue,
+                    _ => {}
+                }
+
+                definitions.insert(symbol.name.clone(), definition);
+            }
+        }
+
+        for (name, value) in layout_symbols {
+            if let Some(existing) = definitions.get(&name).filter(|d| d.binding == Binding::Global) {
+                return Err(LinkerError::DuplicateSymbol(name, self.input_name(existing.input), self.input_name(None)));
+            }
+
+            definitions.insert(name, Definition {
+                value: Symbol {
+                    kind: SymbolKind::Constant { redefinable: false },
+                    value: value as i64
+                },
+                binding: Binding::Global,
+                input: None
+            });
+        }
+
+        let mut undefined: Vec<(String, String)> = Vec::new();
+
+        for input in &self.inputs {
+            let referenced = input.object.sections
+                .iter()
+                .flat_map(|section| &section.relocations)
+                .map(|relocation| &input.object.symbols[relocation.symbol]);
+
+            for symbol in referenced {
+                let missing = symbol.section == SymbolSection::Undefined
+                    && symbol.binding == Binding::Global
+                    && !definitions.contains_key(&symbol.name);
+                let reported = undefined.iter().any(|(name, file)| *name == symbol.name && *file == input.name);
+
+                if missing && !reported {
+                    undefined.push((symbol.name.clone(), input.name.clone()));
+                }
+            }
+        }
+
+        match undefined.is_empty() {
+            true => Ok(definitions),
+            false => Err(LinkerError::UndefinedSymbols(undefined))
+        }
+    }
+
+    fn input_name(&self, input: Option<usize>) -> String {
+        match input {
+            Some(input) => self.inputs[input].name.clone(),
+            None => "the memory layout".to_string()
+        }
+    }
+
+    // Final value of a relocation: the address or offset to put in the instruction or data word
+    fn relocate(
+        &self,
+        input_idx: usize,
+        section_idx: usize,
+        relocation: &ObjectRelocation,
+        sections: &[Section],
+        placements: &Placements,
+        definitions: &HashMap<String, Definition>
+    ) -> Result<i64, String> {
+        let object = &self.inputs[input_idx].object;
+        let address = |section: usize, offset: u32| {
+            let (output, base) = placements[input_idx][section];
+            sections[output].address.wrapping_add(base).wrapping_add(offset)
+        };
+
+        // Symbol plus addend
+        let target = |relocation: &ObjectRelocation| -> i64 {
+            let symbol = &object.symbols[relocation.symbol];
+
+            let value = match symbol.section {
+                _ if symbol.binding != Binding::Local => match definitions.get(&symbol.name) {
+                    Some(Definition { value: Symbol { kind: SymbolKind::Label { section }, value }, .. }) => {
+                        sections[*section].address as i64 + value
+                    }
+                    Some(Definition { value, .. }) => value.value,
+                    None => 0  // Undefined weak symbol
+                },
+                SymbolSection::Section(section) => address(section, symbol.value as u32) as i64,
+                _ => symbol.value as i64
+            };
+
+            value.wrapping_add(relocation.addend)
+        };
+
+        let pc = address(section_idx, relocation.offset) as i64;
+
+        Ok(match relocation.kind {
+            RelocationKind::Abs32 | RelocationKind::Abs64 => target(relocation),
+            RelocationKind::Hi20 | RelocationKind::Lo12I | RelocationKind::Lo12S => target(relocation),
+            RelocationKind::Branch | RelocationKind::Jal | RelocationKind::CallPlt | RelocationKind::PcrelHi20 => {
+                pc_offset(target(relocation), pc)
+            }
+
+            // The symbol is the auipc, whose %pcrel_hi gives the offset
+            RelocationKind::PcrelLo12I | RelocationKind::PcrelLo12S => {
+                let symbol = &object.symbols[relocation.symbol];
+                let SymbolSection::Section(auipc_section) = symbol.section else {
+                    return Err(format!("%pcrel_lo refers to {}, which is not a label", symbol.name));
+                };
+
+                let auipc = object.sections[auipc_section].relocations
+                    .iter()
+                    .find(|r| r.offset as u64 == symbol.value && r.kind == RelocationKind::PcrelHi20)
+                    .ok_or_else(|| format!("No %pcrel_hi relocation for the %pcrel_lo at {:#x}", pc))?;
+
+                pc_offset(target(auipc), address(auipc_section, auipc.offset) as i64)
+            }
+        })
+    }
+}
+
+// The result of a link, laid out from the base address
+pub struct LinkedProgram {
+    class: ElfClass,
+    base_address: u32,
+    entry: u32,
+    sections: Vec<Section>,
+    symbols: HashMap<String, Symbol>,    // Global symbols
+    bindings: HashMap<String, Binding>
+}
+
+impl LinkedProgram {
+    // Merged sections in the order they were first used
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn entry(&self) -> u32 {
+        self.entry
+    }
+
+    // Absolute address of a global label, or the value of an absolute symbol
+    // such as the ones a layout script defines
+    pub fn symbol_address(&self, name: &str) -> Option<u32> {
+        match self.symbols.get(name)? {
+            Symbol { kind: SymbolKind::Label { section }, value } => {
+                Some(self.sections[*section].address.wrapping_add(*value as u32))
+            }
+            Symbol { kind: SymbolKind::Constant { .. }, value } => Some(*value as u32)
+        }
+    }
+
+    // Flat binary starting at the base address
+    pub fn image(&self) -> Result<Vec<u8>, LinkerError> {
+        section::flatten(&self.sections, self.base_address).map_err(LinkerError::Layout)
+    }
+
+    // Contents of each run of adjacent sections, for Intel HEX and S-records
+    pub fn segments(&self) -> Vec<Segment> {
+        section::segments(&self.sections)
+    }
+
+    // Final addresses of the symbols of every input
+    pub fn symbol_map(&self) -> SymbolMap<'_> {
+        SymbolMap::new(&self.sections, &self.symbols, &self.bindings)
+    }
+
+    // Static ELF executable
+    pub fn to_elf(&self) -> Vec<u8> {
+        let contents = ElfContents {
+            class: self.class,
+            sections: &self.sections,
+            symbols: &self.symbols,
+            bindings: &self.bindings
+        };
+
+        elf::executable::write_executable(&contents, self.entry)
+    }
+}
+
+// Signed distance from pc to an address, within the 32-bit address space
+fn pc_offset(target: i64, pc: i64) -> i64 {
+    (target as u32).wrapping_sub(pc as u32) as i32 as i64
+}
+
+// Write the value of a relocation into the section, keeping the rest of the instruction
+fn patch(section: &mut Section, offset: u32, kind: RelocationKind, value: i64) -> Result<(), String> {
+    let location = format!("{}+{:#x}", section.name, offset);
+    let offset = offset as usize;
+    let size = match kind {
+        RelocationKind::Abs64 => 8,
+        RelocationKind::CallPlt => 8,
+        _ => 4
+    };
+
+    let Some(bytes) = section.data.get_mut(offset..offset + size) else {
+        return Err(format!("Relocation at {} is outside of the section", location));
+    };
+
+    let in_range = |bits: u32| value >= -(1 << (bits - 1)) && value < 1 << (bits - 1);
+
+    let words: Vec<u32> = match kind {
+        RelocationKind::Abs64 => {
+            bytes.copy_from_slice(&value.to_le_bytes());
+            return Ok(());
+        }
+        RelocationKind::Abs32 => vec![value as u32],
+        RelocationKind::Branch => {
+            if !in_range(13) || value & 1 != 0 {
+                return Err(format!("Branch offset {} at {} is out of range", value, location));
+            }
+
+            vec![encode_b_type(0, 0, 0, 0, value as i32)]
+        }
+        RelocationKind::Jal => {
+            if !in_range(21) || value & 1 != 0 {
+                return Err(format!("Jump offset {} at {} is out of range", value, location));
+            }
+
+            vec![encode_j_type(0, 0, value as i32)]
+        }
+        RelocationKind::CallPlt => vec![
+            encode_u_type(0, 0, hi20(value) as i32),
+            encode_i_type(0, 0, 0, 0, lo12(value) as i32)
+        ],
+        RelocationKind::PcrelHi20 | RelocationKind::Hi20 => vec![encode_u_type(0, 0, hi20(value) as i32)],
+        RelocationKind::PcrelLo12I | RelocationKind::Lo12I => vec![encode_i_type(0, 0, 0, 0, lo12(value) as i32)],
+        RelocationKind::PcrelLo12S | RelocationKind::Lo12S => vec![encode_s_type(0, 0, 0, 0, lo12(value) as i32)]
+    };
+
+    let masks = match kind {
+        RelocationKind::Abs32 => &[u32::MAX][..],
+        _ => kind.masks()
+    };
+
+    for ((chunk, word), mask) in bytes.chunks_exact_mut(4).zip(words).zip(masks) {
+        let current = u32::from_le_bytes((&*chunk).try_into().unwrap());
+        chunk.copy_from_slice(&((current & !mask) | (word & mask)).to_le_bytes());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,348 @@
+//! Reads the parts of RISC-V ELF files the linker and the disassembler need
+//!
+//! The linker reads relocatable objects (.o), the disassembler executables as well.
+
+use crate::assembler::relocation::RelocationKind;
+use crate::assembler::{Binding, SectionKind};
+use crate::elf::*;
+
+// R_RISCV_RELAX only allows the linker to shorten code, which is never done here
+const R_RISCV_RELAX: u32 = 51;
+
+#[derive(Debug, Clone)]
+pub struct ObjectFile {
+    pub class: ElfClass,
+    pub file_type: u16,                // ET_REL or ET_EXEC
+    pub entry: u64,                    // Zero in objects
+    pub sections: Vec<ObjectSection>,  // Loadable sections only
+    pub symbols: Vec<ObjectSymbol>     // By symbol table index, including the null symbol
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectSection {
+    pub name: String,
+    pub kind: SectionKind,
+    pub address: u64,   // Zero in objects
+    pub align: u32,
+    pub data: Vec<u8>,  // Empty for .bss
+    pub size: u32,
+    pub relocations: Vec<ObjectRelocation>
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectRelocation {
+    pub offset: u32,   // Section-relative
+    pub kind: RelocationKind,
+    pub symbol: usize, // Symbol table index
+    pub addend: i64
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectSymbol {
+    pub name: String,
+    pub value: u64,
+    pub section: SymbolSection,
+    pub binding: Binding
+}
+
+// Where a symbol is defined
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SymbolSection {
+    Undefined,
+    Absolute,
+    Section(usize)  // Index in ObjectFile::sections
+}
+
+// Section header fields
+struct Header {
+    name: u32,
+    section_type: u32,
+    flags: u64,
+    address: u64,
+    offset: usize,
+    size: usize,
+    link: u32,
+    info: u32,
+    align: u64
+}
+
+// Little-endian fields of the file, with bounds checks
+struct Reader<'a> {
+    bytes: &'a [u8],
+    class: ElfClass
+}
+
+impl Reader<'_> {
+    fn slice(&self, offset: usize, len: usize) -> Result<&[u8], String> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| format!("Truncated file, expected {} bytes at offset {}", len, offset))
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8, String> {
+        Ok(self.slice(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.slice(offset, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.slice(offset, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.slice(offset, 8)?.try_into().unwrap()))
+    }
+
+    // An address, size or offset, sized by the class
+    fn word(&self, offset: usize) -> Result<u64, String> {
+        match self.class {
+            ElfClass::Elf32 => self.u32(offset).map(u64::from),
+            ElfClass::Elf64 => self.u64(offset)
+        }
+    }
+
+    // Null-terminated string at offset in a string table section
+    fn string(&self, table: &Header, offset: u32) -> Result<String, String> {
+        let bytes = self.slice(table.offset, table.size)?;
+        let start = (offset as usize).min(bytes.len());
+        let end = bytes[start..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| format!("Unterminated string at offset {}", offset))?;
+
+        Ok(String::from_utf8_lossy(&bytes[start..start + end]).into_owned())
+    }
+
+    fn header(&self, offset: usize) -> Result<Header, String> {
+        let word = self.class.word_size();
+
+        Ok(Header {
+            name: self.u32(offset)?,
+            section_type: self.u32(offset + 4)?,
+            flags: self.word(offset + 8)?,
+            address: self.word(offset + 8 + word)?,
+            offset: self.word(offset + 8 + 2 * word)? as usize,
+            size: self.word(offset + 8 + 3 * word)? as usize,
+            link: self.u32(offset + 8 + 4 * word)?,
+            info: self.u32(offset + 12 + 4 * word)?,
+            align: self.word(offset + 16 + 4 * word)?
+        })
+    }
+}
+
+// Parse an ELF32 or ELF64 little-endian RISC-V relocatable object
+pub fn read_object(bytes: &[u8]) -> Result<ObjectFile, String> {
+    let object = read_elf(bytes)?;
+
+    if object.file_type != ET_REL {
+        return Err("Not a relocatable object".to_string());
+    }
+
+    Ok(object)
+}
+
+// Parse an ELF32 or ELF64 little-endian RISC-V relocatable object or executable
+pub fn read_elf(bytes: &[u8]) -> Result<ObjectFile, String> {
+    if bytes.get(..4) != Some(b"\x7FELF") {
+        return Err("Not an ELF file".to_string());
+    }
+
+    let class = match bytes.get(4) {
+        Some(1) => ElfClass::Elf32,
+        Some(2) => ElfClass::Elf64,
+        _ => return Err("Unknown ELF class".to_string())
+    };
+
+    let reader = Reader { bytes, class };
+
+    if reader.u8(5)? != 1 {
+        return Err("Only little-endian files are supported".to_string());
+    }
+
+    let file_type = reader.u16(16)?;
+
+    if file_type != ET_REL && file_type != ET_EXEC {
+        return Err("Not a relocatable object or executable".to_string());
+    }
+
+    if reader.u16(18)? != EM_RISCV {
+        return Err("Not a RISC-V object".to_string());
+    }
+
+    // Section header table, from the fields after the entry point and program header offset
+    let word = class.word_size();
+    let entry = reader.word(24)?;
+    let section_headers = reader.word(24 + 2 * word)? as usize;
+    let fields = 24 + 3 * word + 4;
+    let entry_size = reader.u16(fields + 6)? as usize;
+    let count = reader.u16(fields + 8)? as usize;
+
+    if entry_size != class.section_header_size() {
+        return Err(format!("Unexpected section header size {}", entry_size));
+    }
+
+    let headers = (0..count)
+        .map(|idx| reader.header(section_headers + idx * entry_size))
+        .collect::<Result<Vec<Header>, String>>()?;
+    let names = headers
+        .get(reader.u16(fields + 10)? as usize)
+        .ok_or("Missing section name table")?;
+
+    // Loadable sections, by ELF section index
+    let mut loaded = vec![None; headers.len()];
+    let mut sections = Vec::new();
+
+    for (idx, header) in headers.iter().enumerate() {
+        if header.flags & SHF_ALLOC == 0 {
+            continue;
+        }
+
+        let kind = if header.section_type == SHT_NOBITS {
+            SectionKind::Bss
+        } else if header.flags & SHF_EXECINSTR != 0 {
+            SectionKind::Text
+        } else if header.flags & SHF_WRITE != 0 {
+            SectionKind::Data
+        } else {
+            SectionKind::Rodata
+        };
+
+        let data = match kind {
+            SectionKind::Bss => Vec::new(),
+            _ => reader.slice(header.offset, header.size)?.to_vec()
+        };
+
+        loaded[idx] = Some(sections.len());
+        sections.push(ObjectSection {
+            name: reader.string(names, header.name)?,
+            kind,
+            address: header.address,
+            align: header.align.max(1) as u32,
+            data,
+            size: header.size as u32,
+            relocations: Vec::new()
+        });
+    }
+
+    let symbols = match headers.iter().find(|h| h.section_type == SHT_SYMTAB) {
+        Some(symtab) => read_symbols(&reader, &headers, symtab, &loaded)?,
+        None => Vec::new()
+    };
+
+    for header in &headers {
+        if header.section_type != SHT_RELA {
+            continue;
+        }
+
+        // Relocations of sections that aren't loaded (debug information) don't matter
+        let Some(Some(target)) = loaded.get(header.info as usize) else {
+            continue;
+        };
+
+        let relocations = read_relocations(&reader, header, symbols.len())?;
+        sections[*target].relocations.extend(relocations);
+    }
+
+    Ok(ObjectFile { class, file_type, entry, sections, symbols })
+}
+
+fn read_symbols(
+    reader: &Reader,
+    headers: &[Header],
+    symtab: &Header,
+    loaded: &[Option<usize>]
+) -> Result<Vec<ObjectSymbol>, String> {
+    let names = headers.get(symtab.link as usize).ok_or("Missing symbol name table")?;
+    let size = reader.class.symbol_size();
+    let mut symbols = Vec::with_capacity(symtab.size / size);
+
+    for idx in 0..symtab.size / size {
+        let offset = symtab.offset + idx * size;
+
+        let (name, value, info, section) = match reader.class {
+            ElfClass::Elf32 => (
+                reader.u32(offset)?,
+                reader.u32(offset + 4)? as u64,
+                reader.u8(offset + 12)?,
+                reader.u16(offset + 14)?
+            ),
+            ElfClass::Elf64 => (
+                reader.u32(offset)?,
+                reader.u64(offset + 8)?,
+                reader.u8(offset + 4)?,
+                reader.u16(offset + 6)?
+            )
+        };
+
+        let binding = match info >> 4 {
+            STB_LOCAL => Binding::Local,
+            STB_GLOBAL => Binding::Global,
+            STB_WEAK => Binding::Weak,
+            other => return Err(format!("Unsupported symbol binding {}", other))
+        };
+
+        // Symbols of sections that aren't loaded can't be referred to by the program
+        let section = match section {
+            SHN_UNDEF => SymbolSection::Undefined,
+            SHN_ABS => SymbolSection::Absolute,
+            index => match loaded.get(index as usize) {
+                Some(Some(section)) => SymbolSection::Section(*section),
+                _ => SymbolSection::Absolute
+            }
+        };
+
+        symbols.push(ObjectSymbol {
+            name: reader.string(names, name)?,
+            value,
+            section,
+            binding
+        });
+    }
+
+    Ok(symbols)
+}
+
+fn read_relocations(reader: &Reader, rela: &Header, symbol_count: usize) -> Result<Vec<ObjectRelocation>, String> {
+    let size = reader.class.rela_size();
+    let mut relocations = Vec::with_capacity(rela.size / size);
+
+    for idx in 0..rela.size / size {
+        let offset = rela.offset + idx * size;
+
+        let (address, symbol, elf_type, addend) = match reader.class {
+            ElfClass::Elf32 => {
+                let info = reader.u32(offset + 4)?;
+                let addend = reader.u32(offset + 8)? as i32 as i64;
+                (reader.u32(offset)? as u64, (info >> 8) as usize, info & 0xFF, addend)
+            }
+            ElfClass::Elf64 => {
+                let info = reader.u64(offset + 8)?;
+                let addend = reader.u64(offset + 16)? as i64;
+                (reader.u64(offset)?, (info >> 32) as usize, info as u32, addend)
+            }
+        };
+
+        if elf_type == R_RISCV_RELAX {
+            continue;
+        }
+
+        let kind = RelocationKind::from_elf_type(elf_type)
+            .ok_or_else(|| format!("Unsupported relocation type {}", elf_type))?;
+
+        if symbol >= symbol_count {
+            return Err(format!("Relocation refers to symbol {} which doesn't exist", symbol));
+        }
+
+        relocations.push(ObjectRelocation {
+            offset: address as u32,
+            kind,
+            symbol,
+            addend
+        });
+    }
+
+    Ok(relocations)
+}
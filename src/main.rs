@@ -1,41 +1,530 @@
-use std::{env, fs, time::Instant};
+use std::{env, fs, io};
+use std::io::{BufRead, BufReader, Write};
 use std::error::Error;
 use std::path::Path;
 use std::process::exit;
-use riscv_assembler::assembler::{Assembler, hexdump};
+use riscv_assembler::assembler::{Assembler, AssemblerError, MemoryLayout, Segment, Xlen, embed, hexdump, ihex, meminit, srec};
+use riscv_assembler::assembler::srec::SrecFormat;
+use riscv_assembler::assembler::parser::parse_integer;
+use riscv_assembler::disassembler::objdump;
+use riscv_assembler::linker::Linker;
+use riscv_assembler::simulator::{Debugger, Environment, Simulator, Stop, SyscallTable};
+
+const FORMATS: [&str; 12] = [
+    "bin", "ihex", "srec", "s19", "s28", "s37", "readmemh", "readmemb", "coe", "mif", "c", "rust"
+];
+const MEMORY_FORMATS: [&str; 4] = ["readmemh", "readmemb", "coe", "mif"];
+
+// Instructions a program can run for before it is stopped
+const DEFAULT_LIMIT: u64 = 100_000_000;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
 
-    // Check for assembly file
-    if args.len() != 2 {
-        eprintln!("Usage: {} <asm_file>", args[0]);
-        exit(1);
+    // disasm lists the instructions of a binary or ELF file instead of assembling
+    if args.get(1).is_some_and(|arg| arg == "disasm") {
+        return disasm(&args);
+    }
+
+    // run assembles a program and executes it with emulated environment calls, debug does it step by step
+    if let Some(mode @ ("run" | "debug")) = args.get(1).map(String::as_str) {
+        return run(&args, mode == "debug");
     }
 
-    let asm_file = &args[1];
+    let mut assembler = Assembler::new();
+    let mut linker = Linker::new();
+    let mut inputs: Vec<&str> = Vec::new();
+    let mut output = None;
+    let mut object = false;
+    let mut executable = false;
+    let mut format = "bin";
+    let mut word_width = 4;
+    let mut addresses = false;
+    let mut byte_lanes = false;
+    let mut array_name = None;
+    let mut exports = Vec::new();
+    let mut listing = false;
+    let mut symbol_formats = Vec::new();
+    let mut idx = 1;
+
+    while idx < args.len() {
+        // -D NAME[=VALUE] defines a constant (1 by default)
+        if let Some(define) = option_value(&args, &mut idx, "-D") {
+            let (name, value) = define.split_once('=').unwrap_or((define, "1"));
+            assembler.define(name, parse_integer(value)?);
+        }
+        // -I DIR adds an include path
+        else if let Some(dir) = option_value(&args, &mut idx, "-I") {
+            assembler.add_include_path(dir);
+        }
+        // -march=rv32... or -march=rv64... picks the XLEN
+        else if let Some(arch) = args[idx].strip_prefix("-march=") {
+            assembler.set_xlen(match arch.get(..4) {
+                Some("rv32") => Xlen::Rv32,
+                Some("rv64") => Xlen::Rv64,
+                _ => usage(&args[0])
+            });
+            idx += 1;
+        }
+        // -c writes a relocatable object file instead of a flat binary
+        else if args[idx] == "-c" {
+            object = true;
+            idx += 1;
+        }
+        // --elf writes a static executable
+        else if args[idx] == "--elf" {
+            executable = true;
+            idx += 1;
+        }
+        // --base ADDR lays the sections out from ADDR instead of 0
+        else if let Some(address) = option_value(&args, &mut idx, "--base") {
+            let address = u32::try_from(parse_integer(address)?)?;
+            assembler.set_base_address(address);
+            linker.set_base_address(address);
+        }
+        // --entry SYMBOL sets the entry point of executables
+        else if let Some(symbol) = option_value(&args, &mut idx, "--entry") {
+            assembler.set_entry(symbol);
+            linker.set_entry(symbol);
+        }
+        // -T FILE places the sections with a memory layout script
+        else if let Some(path) = option_value(&args, &mut idx, "-T") {
+            let layout = MemoryLayout::from_file(path)?;
+            assembler.set_layout(layout.clone());
+            linker.set_layout(layout);
+        }
+        // -O FORMAT picks the output format of the image
+        else if let Some(name) = option_value(&args, &mut idx, "-O") {
+            if !FORMATS.contains(&name) {
+                usage(&args[0]);
+            }
+            format = name;
+        }
+        // --word-width BITS sets the width of the words in memory initialization files
+        else if let Some(bits) = option_value(&args, &mut idx, "--word-width") {
+            word_width = match parse_integer(bits)? {
+                bits @ (8 | 16 | 32 | 64) => bits as usize / 8,
+                _ => usage(&args[0])
+            };
+        }
+        // --addresses starts every run of words with an @index marker in $readmem files
+        else if args[idx] == "--addresses" {
+            addresses = true;
+            idx += 1;
+        }
+        // --byte-lanes writes a file per byte of the word for 8-bit wide memories
+        else if args[idx] == "--byte-lanes" {
+            byte_lanes = true;
+            idx += 1;
+        }
+        // --array-name NAME names the C or Rust array instead of the input file
+        else if let Some(name) = option_value(&args, &mut idx, "--array-name") {
+            array_name = Some(name);
+        }
+        // --export LABEL adds a constant with the address of LABEL next to the C or Rust array
+        else if let Some(label) = option_value(&args, &mut idx, "--export") {
+            exports.push(label);
+        }
+        // --listing writes a .lst listing of the source next to the output
+        else if args[idx] == "--listing" {
+            assembler.set_listing(true);
+            listing = true;
+            idx += 1;
+        }
+        // --symbols map|nm|json writes the final symbol table as a map file, nm text or JSON
+        else if let Some(symbols_format) = option_value(&args, &mut idx, "--symbols") {
+            if !["map", "nm", "json"].contains(&symbols_format) {
+                usage(&args[0]);
+            }
+            symbol_formats.push(symbols_format);
+        }
+        // -o FILE names the output instead of deriving it from the first input
+        else if let Some(path) = option_value(&args, &mut idx, "-o") {
+            output = Some(Path::new(path));
+        } else if !args[idx].starts_with('-') {
+            inputs.push(args[idx].as_str());
+            idx += 1;
+        } else {
+            usage(&args[0]);
+        }
+    }
+
+    // Check for assembly file
+    let Some(&asm_file) = inputs.first() else {
+        usage(&args[0]);
+    };
 
     // Set the output paths
     let asm_file_path = Path::new(&asm_file);
-    let bin_out_path = asm_file_path.with_extension("bin");
-    let hex_out_path = asm_file_path.with_extension("hex");
+    let out_path = |extension: &str| output.map_or_else(|| asm_file_path.with_extension(extension), Path::to_path_buf);
 
-    // let assemble_time = Instant::now();
+    // Several files, or object files, are linked together
+    let link = inputs.len() > 1 || inputs.iter().any(|input| input.ends_with(".o"));
 
-    // Assemble the file and generate the hexdump
-    let mut assembler = Assembler::new();
-    let bin_out = assembler.assemble(asm_file)?;
-    let hex_out = hexdump::generate_hexdump(&bin_out);
+    // An object, an executable and a -O format are different outputs,
+    // only memory files have byte lanes, only C and Rust arrays have names and exported labels,
+    // and objects and listings are made from a single source file
+    let embedded = format == "c" || format == "rust";
+    if (object || executable) && format != "bin"
+        || byte_lanes && !MEMORY_FORMATS.contains(&format)
+        || (array_name.is_some() || !exports.is_empty()) && !embedded
+        || link && (object || listing)
+    {
+        usage(&args[0]);
+    }
+
+    let program = match link {
+        true => {
+            for input in &inputs {
+                match input.ends_with(".o") {
+                    true => linker.add_object_file(input)?,
+                    false => linker.add_source(&mut assembler, input)?
+                }
+            }
+
+            Some(match executable {
+                true => linker.link_executable()?,
+                false => linker.link()?
+            })
+        }
+        false => None
+    };
+
+    // Every output breaks out of this block once it is written, followed by the listing and symbol maps
+    'output: {
+        if object {
+            let obj_out_path = out_path("o");
+            fs::write(&obj_out_path, assembler.assemble_object(asm_file)?)?;
+            println!("Wrote object file to: {}", obj_out_path.display());
+            break 'output;
+        }
+
+        if executable {
+            let elf_out_path = out_path("elf");
+            let elf_out = match &program {
+                Some(program) => program.to_elf(),
+                None => assembler.assemble_executable(asm_file)?
+            };
+
+            fs::write(&elf_out_path, elf_out)?;
+            println!("Wrote executable to: {}", elf_out_path.display());
+            break 'output;
+        }
+
+        // C and Rust arrays embed the flat image, with the addresses of the exported labels next to it
+        if format == "c" || format == "rust" {
+            let image = match &program {
+                Some(program) => program.image()?,
+                None => assembler.assemble(asm_file)?
+            };
+
+            let mut labels = Vec::new();
+
+            for &label in &exports {
+                let address = match &program {
+                    Some(program) => program.symbol_address(label),
+                    None => assembler.symbol_address(label)
+                };
+                labels.push((label, address.ok_or_else(|| AssemblerError::UndefinedLabel(label.to_string()))?));
+            }
+
+            let name = array_name.unwrap_or_else(|| asm_file_path.file_stem().and_then(|s| s.to_str()).unwrap_or("program"));
+            let (extension, contents) = match format {
+                "c" => ("h", embed::generate_c_header(&image, name, &labels)),
+                _ => ("rs", embed::generate_rust_array(&image, name, &labels))
+            };
+
+            let out_path = out_path(extension);
+            fs::write(&out_path, contents)?;
+            println!("Wrote {} file to: {}", format, out_path.display());
+            break 'output;
+        }
+
+        // The other formats only hold the bytes of the sections, so gaps between them can stay empty
+        if format != "bin" {
+            let (segments, entry) = match &program {
+                Some(program) => (program.segments(), program.entry()),
+                None => (assembler.assemble_segments(asm_file)?, assembler.entry_address()?)
+            };
+            let origin = segments.first().map_or(0, |s| s.address);
+
+            // Memories built from 8-bit block RAMs get a file per byte of the word
+            if byte_lanes {
+                let lanes = meminit::split_byte_lanes(&segments, origin, word_width);
+
+                for (lane, segments) in lanes.iter().enumerate() {
+                    let (extension, contents) = format_image(format, segments, origin, entry, 1, addresses)?;
+                    let lane_out_path = out_path(extension).with_extension(format!("lane{}.{}", lane, extension));
+                    fs::write(&lane_out_path, contents)?;
+                    println!("Wrote {} file to: {}", format, lane_out_path.display());
+                }
+
+                break 'output;
+            }
+
+            let (extension, contents) = format_image(format, &segments, origin, entry, word_width, addresses)?;
+            let out_path = out_path(extension);
+            fs::write(&out_path, contents)?;
+            println!("Wrote {} file to: {}", format, out_path.display());
+            break 'output;
+        }
+
+        let bin_out_path = out_path("bin");
+        let hex_out_path = bin_out_path.with_extension("hex");
+
+        // let assemble_time = Instant::now();
+
+        // Assemble the file and generate the hexdump
+        let bin_out = match &program {
+            Some(program) => program.image()?,
+            None => assembler.assemble(asm_file)?
+        };
+        let hex_out = hexdump::generate_hexdump(&bin_out);
+
+        // Write the output files
+        fs::write(&bin_out_path, &bin_out)?;
+        fs::write(&hex_out_path, &hex_out)?;
+
+        // println!("Assembled in {:?}", assemble_time.elapsed());
+
+        // Determine the output directory for printing and set a fallback
+        let output_dir = bin_out_path.parent().unwrap_or(Path::new("."));
+        println!("Wrote bin and hex files to: {}", output_dir.display());
+    }
+
+    if listing {
+        let listing_out_path = out_path("lst").with_extension("lst");
+        fs::write(&listing_out_path, assembler.listing().unwrap_or_default())?;
+        println!("Wrote listing to: {}", listing_out_path.display());
+    }
+
+    for &symbols_format in &symbol_formats {
+        let symbol_map = match &program {
+            Some(program) => program.symbol_map(),
+            None => assembler.symbol_map()
+        };
+
+        let (extension, contents) = match symbols_format {
+            "map" => ("map", symbol_map.to_map()),
+            "nm" => ("sym", symbol_map.to_nm()),
+            _ => ("json", symbol_map.to_json())
+        };
+
+        let symbols_out_path = out_path(extension).with_extension(extension);
+        fs::write(&symbols_out_path, contents)?;
+        println!("Wrote {} symbols to: {}", symbols_format, symbols_out_path.display());
+    }
 
-    // Write the output files
-    fs::write(&bin_out_path, &bin_out)?;
-    fs::write(&hex_out_path, &hex_out)?;
+    Ok(())
+}
+
+// disasm [--base ADDR] [-o FILE] <bin_file | elf_file>
+// ELF files are told apart from raw binaries by their magic number
+fn disasm(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut base = 0;
+    let mut output = None;
+    let mut input = None;
+    let mut idx = 2;
+
+    while idx < args.len() {
+        // --base ADDR is the address of the first byte of a raw binary
+        if let Some(address) = option_value(args, &mut idx, "--base") {
+            base = u32::try_from(parse_integer(address)?)?;
+        }
+        // -o FILE writes the listing to FILE instead of stdout
+        else if let Some(path) = option_value(args, &mut idx, "-o") {
+            output = Some(path);
+        } else if !args[idx].starts_with('-') && input.is_none() {
+            input = Some(args[idx].as_str());
+            idx += 1;
+        } else {
+            usage(&args[0]);
+        }
+    }
 
-    // println!("Assembled in {:?}", assemble_time.elapsed());
+    let Some(input) = input else {
+        usage(&args[0]);
+    };
 
-    // Determine the output directory for printing and set a fallback
-    let output_dir = bin_out_path.parent().unwrap_or(Path::new("."));
-    println!("Wrote bin and hex files to: {}", output_dir.display());
+    let bytes = fs::read(input)?;
+    let dump = match bytes.starts_with(b"\x7FELF") {
+        true => objdump::dump_elf(input, &bytes)?,
+        false => objdump::dump_binary(input, &bytes, base)
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(path, dump)?;
+            println!("Wrote disassembly to: {}", path);
+        }
+        None => print!("{}", dump)
+    }
 
     Ok(())
 }
+
+// run|debug [--syscalls rars|venus|linux] [--limit N] [-march=rv32i|rv64i] [--base ADDR] [-D NAME[=VALUE]]... [-I DIR]... <asm_file>
+// The exit code of the program becomes the exit code of the process, a program that stops
+// at an ebreak or the instruction limit gets its registers dumped instead
+// In debug mode the limit applies to every command that runs the program
+fn run(args: &[String], debug: bool) -> Result<(), Box<dyn Error>> {
+    let mut assembler = Assembler::new();
+    assembler.set_listing(debug);
+    let mut table = SyscallTable::Rars;
+    let mut limit = DEFAULT_LIMIT;
+    let mut input = None;
+    let mut idx = 2;
+
+    while idx < args.len() {
+        // --syscalls picks the numbers and registers of environment calls
+        if let Some(name) = option_value(args, &mut idx, "--syscalls") {
+            table = SyscallTable::from_name(name).unwrap_or_else(|| usage(&args[0]));
+        }
+        // --limit N stops the program after N instructions
+        else if let Some(count) = option_value(args, &mut idx, "--limit") {
+            limit = u64::try_from(parse_integer(count)?)?;
+        } else if let Some(define) = option_value(args, &mut idx, "-D") {
+            let (name, value) = define.split_once('=').unwrap_or((define, "1"));
+            assembler.define(name, parse_integer(value)?);
+        } else if let Some(dir) = option_value(args, &mut idx, "-I") {
+            assembler.add_include_path(dir);
+        } else if let Some(arch) = args[idx].strip_prefix("-march=") {
+            assembler.set_xlen(match arch.get(..4) {
+                Some("rv32") => Xlen::Rv32,
+                Some("rv64") => Xlen::Rv64,
+                _ => usage(&args[0])
+            });
+            idx += 1;
+        } else if let Some(address) = option_value(args, &mut idx, "--base") {
+            assembler.set_base_address(u32::try_from(parse_integer(address)?)?);
+        } else if !args[idx].starts_with('-') && input.is_none() {
+            input = Some(args[idx].as_str());
+            idx += 1;
+        } else {
+            usage(&args[0]);
+        }
+    }
+
+    let Some(input) = input else {
+        usage(&args[0]);
+    };
+
+    let segments = assembler.assemble_segments(input)?;
+
+    // The heap starts after the last section, .bss included
+    let end = assembler
+        .sections()
+        .iter()
+        .map(|section| section.address as u64 + section.size() as u64)
+        .max()
+        .unwrap_or(0);
+
+    let mut simulator = Simulator::new(assembler.xlen());
+    simulator.load_segments(&segments);
+    simulator.set_pc(assembler.entry_address()? as u64);
+
+    // Commands and the program's input share stdin, so the program doesn't buffer ahead of it
+    let stdin = BufReader::with_capacity(1, io::stdin());
+    let mut environment = Environment::new(table, stdin, io::stdout(), end.next_multiple_of(16));
+
+    if debug {
+        let lines = assembler.line_map().unwrap_or_default();
+        let debugger = Debugger::new(simulator, environment, &assembler.symbol_map(), lines, limit);
+        return debug_commands(debugger);
+    }
+
+    let stop = environment.run(&mut simulator, limit);
+
+    match stop {
+        Ok(Stop::Exit(code)) => exit(code as i32),
+        Ok(stop) => {
+            let reason = match stop {
+                Stop::Limit => "the instruction limit",
+                _ => "ebreak"
+            };
+
+            eprintln!("Stopped at {} after {} instructions", reason, simulator.instructions_retired());
+            eprint!("{}", simulator.dump_registers());
+            Ok(())
+        }
+        Err(e) => {
+            eprint!("{}", simulator.dump_registers());
+            Err(e.into())
+        }
+    }
+}
+
+// Read commands until quit or the end of the input
+fn debug_commands<R: BufRead, W: Write>(mut debugger: Debugger<R, W>) -> Result<(), Box<dyn Error>> {
+    println!("{}", debugger.location());
+    println!("Type help for the commands");
+
+    loop {
+        print!("(debug) ");
+        io::stdout().flush()?;
+
+        let mut command = String::new();
+        if io::stdin().read_line(&mut command)? == 0 {
+            println!();
+            return Ok(());
+        }
+
+        if ["quit", "q"].contains(&command.trim()) {
+            return Ok(());
+        }
+
+        match debugger.execute(&command) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(e) => eprintln!("{}", e)
+        }
+    }
+}
+
+// Extension and contents of the segments in a -O format other than bin
+fn format_image(
+    format: &str,
+    segments: &[Segment],
+    origin: u32,
+    entry: u32,
+    width: usize,
+    addresses: bool
+) -> Result<(&'static str, String), AssemblerError> {
+    let srec_format = match format {
+        "s19" => SrecFormat::S19,
+        "s28" => SrecFormat::S28,
+        "s37" => SrecFormat::S37,
+        _ => SrecFormat::fitting(segments, entry)
+    };
+
+    Ok(match format {
+        "ihex" => ("ihex", ihex::generate_ihex(segments, Some(entry))),
+        "readmemh" => ("mem", meminit::generate_readmemh(segments, origin, width, addresses)?),
+        "readmemb" => ("mem", meminit::generate_readmemb(segments, origin, width, addresses)?),
+        "coe" => ("coe", meminit::generate_coe(segments, origin, width)?),
+        "mif" => ("mif", meminit::generate_mif(segments, origin, width)?),
+        _ => ("srec", srec::generate_srec(segments, entry, srec_format)?)
+    })
+}
+
+// Value of an option given as "-Xvalue" or "-X value"
+fn option_value<'a>(args: &'a [String], idx: &mut usize, option: &str) -> Option<&'a str> {
+    let value = args[*idx].strip_prefix(option)?;
+    *idx += 1;
+
+    if !value.is_empty() {
+        return Some(value);
+    }
+
+    let value = args.get(*idx).unwrap_or_else(|| usage(&args[0]));
+    *idx += 1;
+    Some(value)
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} [-c | --elf | -O FORMAT] [-o FILE] [--word-width BITS] [--addresses] [--byte-lanes] [--array-name NAME] [--export LABEL]... [--listing] [--symbols map|nm|json]... [-march=rv32i|rv64i] [--base ADDR | -T SCRIPT] [--entry SYMBOL] [-D NAME[=VALUE]]... [-I DIR]... <asm_file | obj_file>...", program);
+    eprintln!("       {} disasm [--base ADDR] [-o FILE] <bin_file | elf_file>", program);
+    eprintln!("       {} run|debug [--syscalls rars|venus|linux] [--limit N] [-march=rv32i|rv64i] [--base ADDR] [-D NAME[=VALUE]]... [-I DIR]... <asm_file>", program);
+    eprintln!("Formats: {}", FORMATS.join(", "));
+    exit(1);
+}
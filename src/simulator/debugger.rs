@@ -0,0 +1,426 @@
+//! Runs a program under the control of text commands, for the debug mode of the command line
+//!
+//! The commands follow gdb: breakpoints on labels or addresses, step, next and continue,
+//! registers by ABI name, memory words and watchpoints on them, and a source view of the
+//! lines around the pc from the address to line map of the assembler. Environment calls are
+//! handled by an Environment while the program runs.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, Write};
+use crate::assembler::parser::{parse_integer, parse_register};
+use crate::assembler::registers::ABI_NAMES;
+use crate::assembler::{SourceLine, SymbolMap, Xlen};
+use crate::simulator::{Environment, Simulator, Stop};
+
+// Lines shown before and after the current one by list
+const LIST_CONTEXT: usize = 4;
+
+// Words shown by x without a count, and per row
+const MEMORY_WORDS: u64 = 4;
+
+pub const HELP: &str = "\
+break LOCATION     (b)  stop before the instruction at a label or address
+delete LOCATION    (d)  remove a breakpoint
+watch LOCATION          stop when the word at a label or address changes
+step [N]           (s)  execute N instructions, 1 by default
+next               (n)  execute a source line, running the calls it makes until they return
+continue           (c)  run until a breakpoint, a watchpoint, an ebreak or the end
+print REGISTER     (p)  value of a register by ABI name, xN or pc, or the address of a label
+x LOCATION [N]          N memory words from a label, register or address, 4 by default
+registers          (r)  every register
+list               (l)  source lines around the pc
+info                    breakpoints and watchpoints
+quit               (q)  leave the debugger";
+
+// How far a command runs the program
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Until {
+    Steps(u64),
+    Next(u64, u64),  // Leaving the addresses of a source line, running the calls it makes
+    Stopped          // A breakpoint, watchpoint or the end of the program
+}
+
+#[derive(Debug, Clone)]
+struct Watchpoint {
+    address: u64,
+    value: u32
+}
+
+pub struct Debugger<R: BufRead, W: Write> {
+    simulator: Simulator,
+    environment: Environment<R, W>,
+    symbols: HashMap<String, u64>,
+    labels: Vec<(u64, String)>,  // One per address in address order, for <label+offset>
+    lines: Vec<SourceLine>,
+    files: HashMap<String, Vec<String>>,  // Source files read by list
+    breakpoints: BTreeSet<u64>,
+    watchpoints: Vec<Watchpoint>,
+    limit: u64,                  // Instructions a command can run before it stops
+    at_ebreak: bool,             // Resuming steps over the ebreak the program stopped at
+    finished: Option<String>     // Why the program can't run any more
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    pub fn new(
+        simulator: Simulator,
+        environment: Environment<R, W>,
+        symbol_map: &SymbolMap,
+        lines: Vec<SourceLine>,
+        limit: u64
+    ) -> Self {
+        let labels: Vec<&_> = symbol_map.symbols().iter().filter(|s| s.section.is_some()).collect();
+
+        let symbols = labels.iter().map(|s| (s.name.clone(), s.value as u64)).collect();
+
+        // The symbol map is sorted by address and name, so the first label of an address wins
+        let mut by_address: Vec<(u64, String)> = Vec::new();
+        for symbol in labels {
+            if by_address.last().is_none_or(|(address, _)| *address != symbol.value as u64) {
+                by_address.push((symbol.value as u64, symbol.name.clone()));
+            }
+        }
+
+        Self {
+            simulator,
+            environment,
+            symbols,
+            labels: by_address,
+            lines,
+            files: HashMap::new(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            limit,
+            at_ebreak: false,
+            finished: None
+        }
+    }
+
+    pub fn simulator(&self) -> &Simulator {
+        &self.simulator
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished.is_some()
+    }
+
+    // Run a command, returns what it prints or an error message
+    pub fn execute(&mut self, command: &str) -> Result<String, String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let arg = |idx: usize| args.get(idx).copied().ok_or_else(|| format!("{} needs an argument", name));
+
+        match name {
+            "break" | "b" => {
+                let address = self.address(arg(0)?)?;
+                self.breakpoints.insert(address);
+                Ok(format!("Breakpoint at {}", self.describe(address)))
+            }
+            "delete" | "d" => {
+                let address = self.address(arg(0)?)?;
+                match self.breakpoints.remove(&address) {
+                    true => Ok(format!("Deleted breakpoint at {}", self.describe(address))),
+                    false => Err(format!("No breakpoint at {}", self.describe(address)))
+                }
+            }
+            "watch" => {
+                let address = self.address(arg(0)?)?;
+                let value = self.word(address);
+                self.watchpoints.push(Watchpoint { address, value });
+                Ok(format!("Watchpoint at {} = {:#010x}", self.describe(address), value))
+            }
+            "step" | "s" => {
+                let count = match args.first() {
+                    Some(count) => parse_count(count)?,
+                    None => 1
+                };
+                self.resume(Until::Steps(count))
+            }
+            "next" | "n" => {
+                let pc = self.simulator.pc();
+                let (start, end) = match self.source_line(pc) {
+                    Some(line) => (line.address as u64, line.address as u64 + line.size as u64),
+                    None => (pc, pc.wrapping_add(4))
+                };
+                self.resume(Until::Next(start, end))
+            }
+            "continue" | "c" => self.resume(Until::Stopped),
+            "print" | "p" => self.print(arg(0)?),
+            "x" => {
+                let address = self.address(arg(0)?)?;
+                let count = match args.get(1) {
+                    Some(count) => parse_count(count)?,
+                    None => MEMORY_WORDS
+                };
+                Ok(self.examine(address, count))
+            }
+            "registers" | "r" => Ok(self.simulator.dump_registers().trim_end().to_string()),
+            "list" | "l" => Ok(self.list()),
+            "info" => Ok(self.info()),
+            "help" | "h" => Ok(HELP.to_string()),
+            "" => Ok(String::new()),
+            _ => Err(format!("Unknown command {}, try help", name))
+        }
+    }
+
+    // Where the program is: the pc, its label and source line
+    pub fn location(&self) -> String {
+        let pc = self.simulator.pc();
+
+        match self.source_line(pc) {
+            Some(line) => format!("{}  {}:{}  {}", self.describe(pc), line.file, line.line, line.source.trim()),
+            None => self.describe(pc)
+        }
+    }
+
+    // Run until the command's condition, a breakpoint or watchpoint, or the end of the program
+    fn resume(&mut self, until: Until) -> Result<String, String> {
+        if let Some(reason) = &self.finished {
+            return Err(format!("The program isn't running, {}", reason));
+        }
+
+        let mut report = String::new();
+        let mut count = 0;
+        let mut returns = Vec::new();  // Return addresses of the calls next runs through
+
+        // Stepping over the ebreak counts as its execution
+        if std::mem::take(&mut self.at_ebreak) {
+            self.simulator.set_pc(self.simulator.pc().wrapping_add(4));
+            count = 1;
+        }
+
+        loop {
+            let pc = self.simulator.pc();
+
+            if until == Until::Steps(count) {
+                break;
+            }
+
+            if count == self.limit {
+                writeln!(report, "Stopped after {} instructions", self.limit).unwrap();
+                break;
+            }
+
+            // The instruction the program stopped at runs even if it has a breakpoint
+            if count > 0 {
+                if let Until::Next(start, end) = until
+                    && returns.is_empty()
+                    && !(start..end).contains(&pc)
+                {
+                    break;
+                }
+
+                if self.breakpoints.contains(&pc) {
+                    report.push_str("Breakpoint at ");
+                    break;
+                }
+            }
+
+            if matches!(until, Until::Next(..)) && self.is_call() {
+                returns.push(pc.wrapping_add(4));
+            }
+
+            let stop = match self.simulator.step() {
+                Ok(Some(Stop::Ecall)) => self.environment.ecall(&mut self.simulator).map(|code| code.map(Stop::Exit)),
+                result => result
+            };
+
+            match stop {
+                Ok(Some(Stop::Exit(code))) => {
+                    let reason = format!("exited with code {}", code);
+                    writeln!(report, "Program {}", reason).unwrap();
+                    self.finished = Some(reason);
+                    return Ok(report.trim_end().to_string());
+                }
+                Ok(Some(Stop::Ebreak)) => {
+                    report.push_str("Stopped at ebreak\n");
+                    self.at_ebreak = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    writeln!(report, "{}", e).unwrap();
+                    self.finished = Some(e.to_string());
+                    break;
+                }
+            }
+
+            if returns.last() == Some(&self.simulator.pc()) {
+                returns.pop();
+            }
+
+            if self.check_watchpoints(&mut report) {
+                break;
+            }
+
+            count += 1;
+        }
+
+        report.push_str(&self.location());
+        Ok(report)
+    }
+
+    // Report the watched words that changed, returns whether any did
+    fn check_watchpoints(&mut self, report: &mut String) -> bool {
+        let mut changed = false;
+
+        for idx in 0..self.watchpoints.len() {
+            let Watchpoint { address, value } = self.watchpoints[idx];
+            let current = self.word(address);
+
+            if current != value {
+                writeln!(report, "Watchpoint at {}: {:#010x} -> {:#010x}", self.describe(address), value, current).unwrap();
+                self.watchpoints[idx].value = current;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    // jal and jalr that save a return address are calls
+    fn is_call(&self) -> bool {
+        let word = self.word(self.simulator.pc());
+        let opcode = word & 0x7F;
+        let rd = (word >> 7) & 0x1F;
+
+        (opcode == 0b1101111 || opcode == 0b1100111) && rd != 0
+    }
+
+    fn print(&self, name: &str) -> Result<String, String> {
+        if name == "pc" {
+            return Ok(format!("pc = {}", self.describe(self.simulator.pc())));
+        }
+
+        if let Ok(number) = parse_register(name) {
+            let value = self.simulator.register(number as usize);
+            let name = ABI_NAMES[number as usize];
+            return Ok(format!("{} = {} ({})", name, self.hex(self.simulator.unsigned_register(number as usize)), value as i64));
+        }
+
+        match self.symbols.get(name) {
+            Some(&address) => Ok(format!("{} = {}", name, self.hex(address))),
+            None => Err(format!("No register or label {}", name))
+        }
+    }
+
+    // Rows of words with the address of the first one
+    fn examine(&self, address: u64, count: u64) -> String {
+        let mut rows = Vec::new();
+
+        for row in 0..count.div_ceil(MEMORY_WORDS) {
+            let start = address.wrapping_add(row * MEMORY_WORDS * 4);
+            let words: Vec<String> = (0..MEMORY_WORDS.min(count - row * MEMORY_WORDS))
+                .map(|idx| format!("{:#010x}", self.word(start.wrapping_add(idx * 4))))
+                .collect();
+
+            rows.push(format!("{}:  {}", self.describe(start), words.join(" ")));
+        }
+
+        rows.join("\n")
+    }
+
+    // The lines of the file around the current one, which is marked with =>
+    fn list(&mut self) -> String {
+        let pc = self.simulator.pc();
+        let Some(current) = self.source_line(pc).cloned() else {
+            return format!("No source line for {}", self.describe(pc));
+        };
+
+        let file = self.files
+            .entry(current.file.clone())
+            .or_insert_with(|| match fs::read_to_string(&current.file) {
+                Ok(text) => text.lines().map(str::to_string).collect(),
+                Err(_) => Vec::new()
+            });
+
+        // Without the file there is still the text of the line itself
+        if current.line > file.len() {
+            return format!("=> {:>4}  {}", current.line, current.source);
+        }
+
+        let first = current.line.saturating_sub(LIST_CONTEXT).max(1);
+        let last = (current.line + LIST_CONTEXT).min(file.len());
+
+        (first..=last)
+            .map(|number| {
+                let marker = if number == current.line { "=>" } else { "  " };
+                format!("{} {:>4}  {}", marker, number, file[number - 1]).trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn info(&self) -> String {
+        let mut info = String::from("Breakpoints:");
+
+        for &address in &self.breakpoints {
+            write!(info, "\n  {}", self.describe(address)).unwrap();
+        }
+
+        info.push_str("\nWatchpoints:");
+
+        for watchpoint in &self.watchpoints {
+            write!(info, "\n  {} = {:#010x}", self.describe(watchpoint.address), watchpoint.value).unwrap();
+        }
+
+        info
+    }
+
+    // A label, the value of a register or a number
+    fn address(&self, location: &str) -> Result<u64, String> {
+        if let Some(&address) = self.symbols.get(location) {
+            return Ok(address);
+        }
+
+        if let Ok(number) = parse_register(location) {
+            return Ok(self.simulator.unsigned_register(number as usize));
+        }
+
+        parse_integer(location)
+            .map(|address| address as u64)
+            .map_err(|_| format!("No label, register or address {}", location))
+    }
+
+    // An address with the closest label before it, such as 0x00001008 <loop+8>
+    // Addresses outside the code and data of the program, like the stack, only get a label of their own
+    fn describe(&self, address: u64) -> String {
+        let idx = self.labels.partition_point(|(label_address, _)| *label_address <= address);
+
+        match idx.checked_sub(1).map(|idx| &self.labels[idx]) {
+            Some((label_address, name)) if *label_address == address => format!("{} <{}>", self.hex(address), name),
+            Some((label_address, name)) if self.source_line(address).is_some() => {
+                format!("{} <{}+{}>", self.hex(address), name, address - label_address)
+            }
+            _ => self.hex(address)
+        }
+    }
+
+    fn source_line(&self, address: u64) -> Option<&SourceLine> {
+        let idx = self.lines.partition_point(|line| line.address as u64 <= address);
+        let line = &self.lines[idx.checked_sub(1)?];
+
+        (address < line.address as u64 + line.size as u64).then_some(line)
+    }
+
+    fn word(&self, address: u64) -> u32 {
+        self.simulator.memory().read(address, 4) as u32
+    }
+
+    fn hex(&self, value: u64) -> String {
+        match self.simulator.xlen() {
+            Xlen::Rv32 => format!("{:#010x}", value),
+            Xlen::Rv64 => format!("{:#018x}", value)
+        }
+    }
+}
+
+// The number of steps or words of a command, at least 1
+fn parse_count(count: &str) -> Result<u64, String> {
+    match parse_integer(count).map_err(|e| e.to_string())? {
+        value if value > 0 => Ok(value as u64),
+        _ => Err(format!("Count must be positive: {}", count))
+    }
+}
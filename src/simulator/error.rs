@@ -0,0 +1,32 @@
+//! Implements the SimulatorError enum for handling errors while running a program
+
+use std::{fmt, io};
+
+#[derive(Debug, PartialEq)]
+pub enum SimulatorError {
+    IllegalInstruction(u64, u32),  // Address and the word that isn't an instruction of the target
+    MisalignedFetch(u64),          // Jump or branch target that isn't a multiple of 4
+    UnknownSyscall(u64, u64),      // Address of the ecall and the call number
+    InvalidInput(String),          // Input that isn't what the environment call reads
+    IOError(String)
+}
+
+impl fmt::Display for SimulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IllegalInstruction(pc, word) => write!(f, "Illegal Instruction: {:#010x} at {:#x}", word, pc),
+            Self::MisalignedFetch(pc) => write!(f, "Misaligned Fetch: {:#x}", pc),
+            Self::UnknownSyscall(pc, number) => write!(f, "Unknown Syscall: {} at {:#x}", number, pc),
+            Self::InvalidInput(input) => write!(f, "Invalid Input: {:?}", input),
+            Self::IOError(e) => write!(f, "IO Error: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for SimulatorError {}
+
+impl From<io::Error> for SimulatorError {
+    fn from(e: io::Error) -> Self {
+        Self::IOError(e.to_string())
+    }
+}
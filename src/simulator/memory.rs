@@ -0,0 +1,56 @@
+//! Byte-addressed little-endian memory of the simulator
+//!
+//! Memory is allocated a page at a time on the first write, so programs can use any address
+//! for their code, data and stack. Bytes that were never written read as zero.
+
+use std::collections::HashMap;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    pages: HashMap<u64, Box<[u8; PAGE_SIZE as usize]>>  // By page number
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read_u8(&self, address: u64) -> u8 {
+        self.pages
+            .get(&(address / PAGE_SIZE))
+            .map_or(0, |page| page[(address % PAGE_SIZE) as usize])
+    }
+
+    pub fn write_u8(&mut self, address: u64, value: u8) {
+        let page = self.pages
+            .entry(address / PAGE_SIZE)
+            .or_insert_with(|| Box::new([0; PAGE_SIZE as usize]));
+
+        page[(address % PAGE_SIZE) as usize] = value;
+    }
+
+    // Little-endian value of 1 to 8 bytes, which don't have to be aligned
+    pub fn read(&self, address: u64, size: usize) -> u64 {
+        (0..size as u64)
+            .rev()
+            .fold(0, |value, idx| (value << 8) | self.read_u8(address.wrapping_add(idx)) as u64)
+    }
+
+    pub fn write(&mut self, address: u64, size: usize, value: u64) {
+        for idx in 0..size as u64 {
+            self.write_u8(address.wrapping_add(idx), (value >> (idx * 8)) as u8);
+        }
+    }
+
+    pub fn read_bytes(&self, address: u64, len: usize) -> Vec<u8> {
+        (0..len as u64).map(|idx| self.read_u8(address.wrapping_add(idx))).collect()
+    }
+
+    pub fn load(&mut self, address: u64, bytes: &[u8]) {
+        for (idx, &byte) in bytes.iter().enumerate() {
+            self.write_u8(address.wrapping_add(idx as u64), byte);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Runs assembled RV32I/RV64I programs with the M extension
+//!
+//! Instructions are decoded with the same table the assembler encodes with. Registers hold
+//! 64-bit values, and on RV32 every result is sign-extended from 32 bits, so most instructions
+//! share their implementation between the two widths. Execution stops at an `ebreak`, at an
+//! `ecall` for the caller or an Environment to handle, or after a number of instructions.
+
+pub mod memory;
+pub mod syscalls;
+pub mod debugger;
+mod error;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use crate::assembler::instructions::InstructionSet;
+use crate::assembler::registers::ABI_NAMES;
+use crate::assembler::{Segment, Xlen};
+use crate::disassembler::{b_immediate, i_immediate, j_immediate, lookup, s_immediate};
+
+pub use error::SimulatorError;
+pub use memory::Memory;
+pub use syscalls::{Environment, SyscallTable};
+pub use debugger::Debugger;
+
+// Initial stack pointer, the top of the lower 2 GiB so it is positive on RV32 too
+pub const STACK_TOP: u64 = 0x7FFF_FFF0;
+
+// Counters that read the number of retired instructions, one cycle per instruction
+const CSR_CYCLE: u32 = 0xC00;
+const CSR_TIME: u32 = 0xC01;
+const CSR_INSTRET: u32 = 0xC02;
+const CSR_CYCLEH: u32 = 0xC80;
+const CSR_TIMEH: u32 = 0xC81;
+const CSR_INSTRETH: u32 = 0xC82;
+const CSR_MCYCLE: u32 = 0xB00;
+const CSR_MINSTRET: u32 = 0xB02;
+const CSR_MCYCLEH: u32 = 0xB80;
+const CSR_MINSTRETH: u32 = 0xB82;
+
+// Why a program stopped running
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Stop {
+    Ebreak,     // The pc is left on the ebreak
+    Ecall,      // The pc is past the ecall, so execution can go on once it is handled
+    Limit,      // The instruction limit was reached
+    Exit(i64)   // The program exited through an environment call with this code
+}
+
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    xlen: Xlen,
+    registers: [u64; 32],
+    pc: u64,
+    memory: Memory,
+    csrs: HashMap<u32, u64>,
+    instret: u64,
+    instructions: InstructionSet
+}
+
+impl Simulator {
+    pub fn new(xlen: Xlen) -> Self {
+        let mut registers = [0; 32];
+        registers[2] = STACK_TOP;
+
+        Self {
+            xlen,
+            registers,
+            pc: 0,
+            memory: Memory::new(),
+            csrs: HashMap::new(),
+            instret: 0,
+            instructions: InstructionSet::new()
+        }
+    }
+
+    pub fn xlen(&self) -> Xlen {
+        self.xlen
+    }
+
+    // Copy an image such as the output of Assembler::assemble into memory
+    pub fn load(&mut self, address: u64, image: &[u8]) {
+        self.memory.load(address, image);
+    }
+
+    pub fn load_segments(&mut self, segments: &[Segment]) {
+        for segment in segments {
+            self.memory.load(segment.address as u64, &segment.data);
+        }
+    }
+
+    pub fn pc(&self) -> u64 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u64) {
+        self.pc = self.address(pc);
+    }
+
+    // Register values are sign-extended from 32 bits on RV32
+    pub fn register(&self, number: usize) -> u64 {
+        self.registers[number]
+    }
+
+    // Register value as an unsigned number of the target width, such as an address
+    pub fn unsigned_register(&self, number: usize) -> u64 {
+        self.unsigned(self.registers[number])
+    }
+
+    pub fn set_register(&mut self, number: usize, value: u64) {
+        if number != 0 {
+            self.registers[number] = self.truncate(value);
+        }
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub fn instructions_retired(&self) -> u64 {
+        self.instret
+    }
+
+    // Run until the program stops or `limit` instructions have been executed
+    pub fn run(&mut self, limit: u64) -> Result<Stop, SimulatorError> {
+        for _ in 0..limit {
+            if let Some(stop) = self.step()? {
+                return Ok(stop);
+            }
+        }
+
+        Ok(Stop::Limit)
+    }
+
+    // Execute the instruction at the pc, returns why the program stopped if it did
+    pub fn step(&mut self) -> Result<Option<Stop>, SimulatorError> {
+        let pc = self.pc;
+
+        if !pc.is_multiple_of(4) {
+            return Err(SimulatorError::MisalignedFetch(pc));
+        }
+
+        let word = self.memory.read(pc, 4) as u32;
+        let illegal = SimulatorError::IllegalInstruction(pc, word);
+        let Some((mnemonic, fmt)) = lookup(&self.instructions, word) else {
+            return Err(illegal);
+        };
+
+        // The word variants, ld, sd and lwu only exist on RV64
+        let rv64_only = fmt.opcode == 0b0111011 || fmt.opcode == 0b0011011 || ["ld", "sd", "lwu"].contains(&mnemonic);
+        if rv64_only && self.xlen == Xlen::Rv32 {
+            return Err(illegal);
+        }
+
+        let rd = ((word >> 7) & 0x1F) as usize;
+        let a = self.registers[((word >> 15) & 0x1F) as usize];
+        let b = self.registers[((word >> 20) & 0x1F) as usize];
+        let imm = i_immediate(word) as i64 as u64;
+        let shamt = (word >> 20) & (self.bits() - 1);
+        let mut next = pc.wrapping_add(4);
+
+        let branch = |taken: bool| match taken {
+            true => pc.wrapping_add(b_immediate(word) as i64 as u64),
+            false => pc.wrapping_add(4)
+        };
+
+        match mnemonic {
+            "add" => self.set_register(rd, a.wrapping_add(b)),
+            "sub" => self.set_register(rd, a.wrapping_sub(b)),
+            "xor" => self.set_register(rd, a ^ b),
+            "or" => self.set_register(rd, a | b),
+            "and" => self.set_register(rd, a & b),
+            "sll" => self.set_register(rd, a << (b as u32 & (self.bits() - 1))),
+            "srl" => self.set_register(rd, self.unsigned(a) >> (b as u32 & (self.bits() - 1))),
+            "sra" => self.set_register(rd, (a as i64 >> (b as u32 & (self.bits() - 1))) as u64),
+            "slt" => self.set_register(rd, ((a as i64) < (b as i64)) as u64),
+            "sltu" => self.set_register(rd, (a < b) as u64),
+
+            "addw" => self.set_register(rd, sign_extend_word(a.wrapping_add(b))),
+            "subw" => self.set_register(rd, sign_extend_word(a.wrapping_sub(b))),
+            "sllw" => self.set_register(rd, sign_extend_word(((a as u32) << (b & 0x1F)) as u64)),
+            "srlw" => self.set_register(rd, sign_extend_word(((a as u32) >> (b & 0x1F)) as u64)),
+            "sraw" => self.set_register(rd, ((a as i32) >> (b & 0x1F)) as i64 as u64),
+
+            "addi" => self.set_register(rd, a.wrapping_add(imm)),
+            "xori" => self.set_register(rd, a ^ imm),
+            "ori" => self.set_register(rd, a | imm),
+            "andi" => self.set_register(rd, a & imm),
+            "slli" => self.set_register(rd, a << shamt),
+            "srli" => self.set_register(rd, self.unsigned(a) >> shamt),
+            "srai" => self.set_register(rd, (a as i64 >> shamt) as u64),
+            "slti" => self.set_register(rd, ((a as i64) < (imm as i64)) as u64),
+            "sltiu" => self.set_register(rd, (a < imm) as u64),
+
+            "addiw" => self.set_register(rd, sign_extend_word(a.wrapping_add(imm))),
+            "slliw" => self.set_register(rd, sign_extend_word(((a as u32) << (shamt & 0x1F)) as u64)),
+            "srliw" => self.set_register(rd, sign_extend_word(((a as u32) >> (shamt & 0x1F)) as u64)),
+            "sraiw" => self.set_register(rd, ((a as i32) >> (shamt & 0x1F)) as i64 as u64),
+
+            "lb" => self.set_register(rd, self.read(a, imm, 1) as i8 as i64 as u64),
+            "lh" => self.set_register(rd, self.read(a, imm, 2) as i16 as i64 as u64),
+            "lw" => self.set_register(rd, self.read(a, imm, 4) as i32 as i64 as u64),
+            "ld" => self.set_register(rd, self.read(a, imm, 8)),
+            "lbu" => self.set_register(rd, self.read(a, imm, 1)),
+            "lhu" => self.set_register(rd, self.read(a, imm, 2)),
+            "lwu" => self.set_register(rd, self.read(a, imm, 4)),
+
+            "sb" => self.write(a, s_immediate(word), 1, b),
+            "sh" => self.write(a, s_immediate(word), 2, b),
+            "sw" => self.write(a, s_immediate(word), 4, b),
+            "sd" => self.write(a, s_immediate(word), 8, b),
+
+            "beq" => next = branch(a == b),
+            "bne" => next = branch(a != b),
+            "blt" => next = branch((a as i64) < (b as i64)),
+            "bge" => next = branch((a as i64) >= (b as i64)),
+            "bltu" => next = branch(a < b),
+            "bgeu" => next = branch(a >= b),
+
+            "lui" => self.set_register(rd, (word & 0xFFFFF000) as i32 as i64 as u64),
+            "auipc" => self.set_register(rd, pc.wrapping_add((word & 0xFFFFF000) as i32 as i64 as u64)),
+
+            "jal" => {
+                next = pc.wrapping_add(j_immediate(word) as i64 as u64);
+                self.set_register(rd, pc.wrapping_add(4));
+            }
+
+            // The target is computed before rd is written, which may be the same register
+            "jalr" => {
+                next = a.wrapping_add(imm) & !1;
+                self.set_register(rd, pc.wrapping_add(4));
+            }
+
+            "ecall" => {
+                self.retire(next);
+                return Ok(Some(Stop::Ecall));
+            }
+
+            "ebreak" => return Ok(Some(Stop::Ebreak)),
+
+            // Register forms write rs1, set and clear only write when rs1 isn't zero,
+            // immediate forms take the rs1 field as the value
+            "csrrw" | "csrrs" | "csrrc" | "csrrwi" | "csrrsi" | "csrrci" => {
+                let csr = word >> 20;
+                let source = match mnemonic.ends_with('i') {
+                    true => ((word >> 15) & 0x1F) as u64,
+                    false => a
+                };
+                let old = self.read_csr(csr);
+
+                match &mnemonic[..5] {
+                    "csrrw" => self.write_csr(csr, source),
+                    "csrrs" if (word >> 15) & 0x1F != 0 => self.write_csr(csr, old | source),
+                    "csrrc" if (word >> 15) & 0x1F != 0 => self.write_csr(csr, old & !source),
+                    _ => {}
+                }
+
+                self.set_register(rd, old);
+            }
+
+            "mul" => self.set_register(rd, a.wrapping_mul(b)),
+            "mulh" => self.set_register(rd, (((a as i64 as i128) * (b as i64 as i128)) >> self.bits()) as u64),
+            "mulsu" => self.set_register(rd, (((a as i64 as i128) * (self.unsigned(b) as i128)) >> self.bits()) as u64),
+            "mulu" => self.set_register(rd, (((self.unsigned(a) as u128) * (self.unsigned(b) as u128)) >> self.bits()) as u64),
+
+            // Division by zero gives all ones and the dividend as the remainder,
+            // and the most negative value divided by -1 overflows back to itself
+            "div" => self.set_register(rd, match b {
+                0 => u64::MAX,
+                _ => (a as i64).wrapping_div(b as i64) as u64
+            }),
+            "divu" => self.set_register(rd, match b {
+                0 => u64::MAX,
+                _ => self.unsigned(a) / self.unsigned(b)
+            }),
+            "rem" => self.set_register(rd, match b {
+                0 => a,
+                _ => (a as i64).wrapping_rem(b as i64) as u64
+            }),
+            "remu" => self.set_register(rd, match b {
+                0 => a,
+                _ => self.unsigned(a) % self.unsigned(b)
+            }),
+
+            _ => return Err(illegal)
+        }
+
+        self.retire(next);
+        Ok(None)
+    }
+
+    // pc and the registers by ABI name, four to a line
+    pub fn dump_registers(&self) -> String {
+        let digits = self.bits() as usize / 4;
+        let mut dump = format!("pc   0x{:0digits$x}\n", self.unsigned(self.pc), digits = digits);
+
+        for (number, name) in ABI_NAMES.iter().enumerate() {
+            let separator = if number % 4 == 3 { "\n" } else { "  " };
+            write!(dump, "{:<4} 0x{:0digits$x}{}", name, self.unsigned(self.registers[number]), separator, digits = digits).unwrap();
+        }
+
+        dump
+    }
+
+    fn retire(&mut self, next: u64) {
+        self.pc = self.address(next);
+        self.instret += 1;
+    }
+
+    fn read(&self, base: u64, offset: u64, size: usize) -> u64 {
+        self.memory.read(self.address(base.wrapping_add(offset)), size)
+    }
+
+    fn write(&mut self, base: u64, offset: i32, size: usize, value: u64) {
+        let address = self.address(base.wrapping_add(offset as i64 as u64));
+        self.memory.write(address, size, value);
+    }
+
+    // The counters are read-only, every other CSR reads back what was written
+    fn read_csr(&self, csr: u32) -> u64 {
+        match csr {
+            CSR_CYCLE | CSR_TIME | CSR_INSTRET | CSR_MCYCLE | CSR_MINSTRET => self.truncate(self.instret),
+            CSR_CYCLEH | CSR_TIMEH | CSR_INSTRETH | CSR_MCYCLEH | CSR_MINSTRETH => self.truncate(self.instret >> 32),
+            _ => self.csrs.get(&csr).copied().unwrap_or(0)
+        }
+    }
+
+    fn write_csr(&mut self, csr: u32, value: u64) {
+        self.csrs.insert(csr, value);
+    }
+
+    fn bits(&self) -> u32 {
+        match self.xlen {
+            Xlen::Rv32 => 32,
+            Xlen::Rv64 => 64
+        }
+    }
+
+    // Register value as it is stored on the target
+    fn truncate(&self, value: u64) -> u64 {
+        match self.xlen {
+            Xlen::Rv32 => sign_extend_word(value),
+            Xlen::Rv64 => value
+        }
+    }
+
+    // Register value as an unsigned number of the target width
+    fn unsigned(&self, value: u64) -> u64 {
+        match self.xlen {
+            Xlen::Rv32 => value as u32 as u64,
+            Xlen::Rv64 => value
+        }
+    }
+
+    // Addresses wrap around at 4 GiB on RV32
+    fn address(&self, value: u64) -> u64 {
+        self.unsigned(value)
+    }
+}
+
+fn sign_extend_word(value: u64) -> u64 {
+    value as i32 as i64 as u64
+}
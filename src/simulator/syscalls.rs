@@ -0,0 +1,199 @@
+//! Emulates the environment calls of RARS, Venus and Linux on top of the host's stdin and stdout
+//!
+//! RARS takes the call number in a7 and the arguments from a0, Venus the number in a0 and the
+//! arguments from a1, with the same numbers except for exit2. Linux calls take the number in a7, the arguments
+//! from a0 and return their result in a0. The heap grows from a break address given by the caller.
+
+use std::io::{self, BufRead, Write};
+use crate::simulator::{Simulator, SimulatorError, Stop};
+
+const A0: usize = 10;
+const A1: usize = 11;
+const A2: usize = 12;
+const A7: usize = 17;
+
+// RARS and Venus call numbers
+const PRINT_INT: u64 = 1;
+const PRINT_STRING: u64 = 4;
+const READ_INT: u64 = 5;
+const SBRK: u64 = 9;
+const EXIT: u64 = 10;
+const PRINT_CHAR: u64 = 11;
+const RARS_EXIT2: u64 = 93;
+const VENUS_EXIT2: u64 = 17;  // The MARS number, which RARS replaced
+
+// Linux call numbers
+const LINUX_WRITE: u64 = 64;
+const LINUX_EXIT: u64 = 93;
+const LINUX_EXIT_GROUP: u64 = 94;
+const LINUX_BRK: u64 = 214;
+
+const EBADF: i64 = 9;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SyscallTable {
+    Rars,
+    Venus,
+    Linux
+}
+
+impl SyscallTable {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rars" => Some(Self::Rars),
+            "venus" => Some(Self::Venus),
+            "linux" => Some(Self::Linux),
+            _ => None
+        }
+    }
+}
+
+pub struct Environment<R: BufRead, W: Write> {
+    table: SyscallTable,
+    input: R,
+    output: W,
+    start: u64,       // Lowest address of the heap
+    break_address: u64
+}
+
+impl<R: BufRead, W: Write> Environment<R, W> {
+    pub fn new(table: SyscallTable, input: R, output: W, break_address: u64) -> Self {
+        Self { table, input, output, start: break_address, break_address }
+    }
+
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    pub fn break_address(&self) -> u64 {
+        self.break_address
+    }
+
+    // Run a program, handling its environment calls until it exits,
+    // reaches an ebreak or has executed `limit` instructions
+    pub fn run(&mut self, simulator: &mut Simulator, limit: u64) -> Result<Stop, SimulatorError> {
+        let end = simulator.instructions_retired().saturating_add(limit);
+
+        loop {
+            let remaining = end.saturating_sub(simulator.instructions_retired());
+
+            match simulator.run(remaining)? {
+                Stop::Ecall => {
+                    if let Some(code) = self.ecall(simulator)? {
+                        return Ok(Stop::Exit(code));
+                    }
+                }
+                stop => return Ok(stop)
+            }
+        }
+    }
+
+    // Handle the environment call a program stopped at, returns the exit code if it exited
+    pub fn ecall(&mut self, simulator: &mut Simulator) -> Result<Option<i64>, SimulatorError> {
+        match self.table {
+            SyscallTable::Rars => self.rars(simulator, A7, A0, RARS_EXIT2),
+            SyscallTable::Venus => self.rars(simulator, A0, A1, VENUS_EXIT2),
+            SyscallTable::Linux => self.linux(simulator)
+        }
+    }
+
+    fn rars(
+        &mut self,
+        simulator: &mut Simulator,
+        number: usize,
+        argument: usize,
+        exit2: u64
+    ) -> Result<Option<i64>, SimulatorError> {
+        let value = simulator.register(argument);
+        let address = simulator.unsigned_register(argument);
+
+        match simulator.register(number) {
+            PRINT_INT => self.print(format!("{}", value as i64).as_bytes())?,
+            PRINT_STRING => {
+                let string = read_string(simulator, address);
+                self.print(&string)?;
+            }
+            PRINT_CHAR => self.print(&[value as u8])?,
+            READ_INT => {
+                let value = self.read_int()?;
+                simulator.set_register(A0, value as u64);
+            }
+
+            // Returns the address of the new block
+            SBRK => {
+                let block = self.break_address;
+                self.break_address = block.wrapping_add(value);
+                simulator.set_register(A0, block);
+            }
+
+            EXIT => return Ok(Some(0)),
+            call if call == exit2 => return Ok(Some(value as i64)),
+            other => return Err(self.unknown(simulator, other))
+        }
+
+        Ok(None)
+    }
+
+    fn linux(&mut self, simulator: &mut Simulator) -> Result<Option<i64>, SimulatorError> {
+        let a0 = simulator.unsigned_register(A0);
+
+        match simulator.register(A7) {
+            // Standard output and error are the only open files
+            LINUX_WRITE => {
+                let bytes = simulator.memory().read_bytes(simulator.unsigned_register(A1), simulator.unsigned_register(A2) as usize);
+                let written = match a0 {
+                    1 => self.print(&bytes).map(|_| bytes.len() as i64)?,
+                    2 => io::stderr().write_all(&bytes).map(|_| bytes.len() as i64)?,
+                    _ => -EBADF
+                };
+                simulator.set_register(A0, written as u64);
+            }
+
+            // Moves the break if the address is inside the heap and returns the break
+            LINUX_BRK => {
+                if a0 >= self.start {
+                    self.break_address = a0;
+                }
+                simulator.set_register(A0, self.break_address);
+            }
+
+            LINUX_EXIT | LINUX_EXIT_GROUP => return Ok(Some(simulator.register(A0) as i64)),
+            other => return Err(self.unknown(simulator, other))
+        }
+
+        Ok(None)
+    }
+
+    fn print(&mut self, bytes: &[u8]) -> Result<(), SimulatorError> {
+        self.output.write_all(bytes)?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    // A line with a decimal number, like RARS reads it
+    fn read_int(&mut self) -> Result<i64, SimulatorError> {
+        let mut line = String::new();
+        self.input.read_line(&mut line)?;
+
+        line.trim()
+            .parse()
+            .map_err(|_| SimulatorError::InvalidInput(line.trim().to_string()))
+    }
+
+    // The pc is already past the ecall
+    fn unknown(&self, simulator: &Simulator, number: u64) -> SimulatorError {
+        SimulatorError::UnknownSyscall(simulator.pc().wrapping_sub(4), number)
+    }
+}
+
+// Bytes of a null-terminated string
+fn read_string(simulator: &Simulator, address: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    loop {
+        match simulator.memory().read_u8(address.wrapping_add(bytes.len() as u64)) {
+            0 => return bytes,
+            byte => bytes.push(byte)
+        }
+    }
+}
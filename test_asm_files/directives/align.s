@@ -0,0 +1,29 @@
+.text
+start:
+    addi a0, x0, 1
+    .align 4                # Padded with nops up to 16 bytes
+aligned:
+    ret
+
+.data
+    .byte 1
+    .balign 4               # Padded with zeros
+word:
+    .word 0x11223344
+    .byte 2
+    .p2align 3, 0xEE        # Explicit fill byte
+dword:
+    .skip 3
+    .space 2, 0xAA
+    .zero 1
+    .fill 2, 2, 0x1234
+    .byte 3
+    .balign 8, 0, 2         # Needs 5 bytes so it is skipped
+unaligned:
+    .byte 4
+    .p2align 4              # Raises the alignment of .data
+
+.bss
+buffer:
+    .skip 64
+end:
@@ -0,0 +1,51 @@
+.equ BOARD, 2
+.equ HAS_UART, 1
+
+.text
+start:
+.if BOARD == 1
+    li a0, 1
+.elseif BOARD == 2
+    li a0, 2
+    .ifdef HAS_UART
+        li a1, 3
+    .else
+        li a1, 4
+    .endif
+.else
+    li a0, 5
+.endif
+
+# Defaults that can be overridden with -D
+.ifndef STACK_SIZE
+    .equ STACK_SIZE, 64
+.endif
+
+.ifdef DEBUG
+    ebreak
+.endif
+
+# Labels defined further down aren't defined yet
+.ifdef later
+    nop
+.endif
+
+.rept 3
+    addi t0, t0, 1
+.endr
+
+.irp reg, t1, t2
+    mv \reg, zero
+.endr
+
+later:
+    ret
+
+.data
+.irpc c, 123
+    .byte '\c'
+.endr
+
+.rept STACK_SIZE / 32
+    .byte 0xAA
+.endr
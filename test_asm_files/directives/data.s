@@ -0,0 +1,17 @@
+.text
+first:
+    addi a0, x0, 1
+second:
+    ret
+
+.data
+bytes:  .byte 1, -1, 0x80, 127
+        .half 0x1234, -1
+        .word 0xDEADBEEF, 16
+        .dword 0x1122334455667788
+        .asciz "Hi#,\n"       # '#' and ',' inside strings are kept
+        .ascii "abc"
+        .string "x", "y"
+
+.section .rodata
+table:  .word first, second
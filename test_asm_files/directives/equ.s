@@ -0,0 +1,23 @@
+.equ UART_BASE, 0x10000000
+.equ UART_TX, 4
+.eqv SYS_EXIT, 93
+.set COUNT, 3
+.equ BIG, 0x12345
+.equ CSR_MSCRATCH, 0x340
+.equ ENTRY, start           # Alias of a label
+
+.text
+start:
+    li a0, UART_BASE
+    sw a1, UART_TX(a0)
+    addi a7, x0, SYS_EXIT
+    csrrw t0, CSR_MSCRATCH, t1
+    csrrwi t0, mscratch, COUNT
+    li a2, BIG
+    beq x0, x0, ENTRY
+.set COUNT, 7               # .set may be redefined
+    addi a3, x0, COUNT
+
+.data
+    .word ENTRY, BIG
+    .skip COUNT
@@ -0,0 +1,2 @@
+loop:
+.equ loop, 4
@@ -0,0 +1,2 @@
+.eqv LIMIT, 1
+.eqv LIMIT, 2
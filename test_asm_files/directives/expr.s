@@ -0,0 +1,20 @@
+.equ BUF, 16
+.equ WORDS, (end - start) / 4   # Resolved on the second pass
+
+.text
+start:
+    addi t0, t0, 4*8
+    lw a0, BUF+4(sp)
+    sw a0, (sp)
+    beq a0, a1, .+8
+    li a1, LATE               # Forward reference, sized for lui + addi
+    li a2, 'A' + 1
+    jal x0, start + 4
+end:
+    ret
+
+.equ LATE, 0x10
+
+.data
+table:
+    .word end - start, WORDS, table + 4, . - table
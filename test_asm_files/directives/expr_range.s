@@ -0,0 +1,5 @@
+.text
+  beq a0, a1, far
+  .space 0x2000
+far:
+  ret
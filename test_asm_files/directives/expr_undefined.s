@@ -0,0 +1,2 @@
+.text
+  .word missing + 4
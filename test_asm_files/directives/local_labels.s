@@ -0,0 +1,21 @@
+.text
+start:
+    li t0, 4
+1:
+    addi t0, t0, -1
+    bnez t0, 1b
+    j 1f
+    nop
+1:
+    beq a0, a1, 2f
+2:  auipc a0, %pcrel_hi(msg)
+    addi a0, a0, %pcrel_lo(2b)
+1:  auipc a1, %pcrel_hi(1f)
+    lw a1, %pcrel_lo(1b)(a1)
+    jal x0, 1b
+
+.data
+msg:
+    .word 1b - start
+1:
+    .word 1b - start
@@ -0,0 +1,19 @@
+.text
+start:
+    lui a0, %hi(value)
+    addi a0, a0, %lo(value)
+    lui a1, %hi(BIG)
+    lw a1, %lo(BIG)(a1)
+    sw a0, %lo(value)(a1)
+target_hi:
+    auipc t0, %pcrel_hi(value)
+    addi t0, t0, %pcrel_lo(target_hi)
+    lw t1, %pcrel_lo(target_hi)(t0)
+    ret
+
+.equ BIG, 0x12345FFC
+
+.data
+    .space 0x820
+value:
+    .word 1
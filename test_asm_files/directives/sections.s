@@ -0,0 +1,20 @@
+.data
+value:
+    .word 42
+
+.text
+start:
+    jal ra, func
+    beq x0, x0, start
+
+.section .rodata
+table:
+    addi x0, x0, 1
+
+.text
+func:
+    lui a0, value
+    ret
+
+.bss
+buffer:
@@ -0,0 +1,2 @@
+# A plain label in lui needs %hi to be relocated
+    lui a0, external
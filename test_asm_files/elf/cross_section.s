@@ -0,0 +1,8 @@
+# The distance between sections is only known after linking
+.text
+start:
+    nop
+.data
+    .word value - start
+value:
+    .word 0
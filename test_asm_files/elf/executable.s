@@ -0,0 +1,22 @@
+# Loads at the base address given to the assembler
+.globl _start
+
+.text
+_start:
+    la a0, message
+    lw a1, counter
+    addi a1, a1, 1
+    sw a1, counter, t0
+    j _start
+
+.section .rodata
+message:
+    .string "hi"
+
+.data
+counter:
+    .word 5
+
+.bss
+buffer:
+    .zero 64
@@ -0,0 +1,7 @@
+.data
+value:
+    .word 1
+.text
+    nop
+main:
+    ret
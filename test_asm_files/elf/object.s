@@ -0,0 +1,36 @@
+# Exercises every kind of relocation in an object file
+.equ UART_BASE, 0x10000000
+.globl _start, counter
+.weak handler
+
+.text
+_start:
+    la a0, message          # PC-relative to .rodata
+    call puts               # Undefined function
+    lui t0, %hi(counter)    # Absolute
+    lw t1, %lo(counter)(t0)
+    addi t1, t1, 1
+    sw t1, %lo(counter)(t0)
+1:  auipc t2, %pcrel_hi(buffer + 4)
+    sb zero, %pcrel_lo(1b)(t2)
+    beq a0, zero, done      # Resolved in place
+    bnez a0, handler        # Weak and undefined
+    j exit
+done:
+    li a0, UART_BASE
+    tail exit
+
+.section .rodata
+message:
+    .string "hello"
+
+.data
+.align 2
+counter:
+    .word 0
+table:
+    .word _start, done + 8, puts
+
+.bss
+buffer:
+    .zero 16
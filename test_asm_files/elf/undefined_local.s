@@ -0,0 +1,2 @@
+.local helper
+    nop
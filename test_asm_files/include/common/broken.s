@@ -0,0 +1,2 @@
+    nop
+    addi a0, a0, q1
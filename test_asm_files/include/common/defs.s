@@ -0,0 +1,4 @@
+.equ UART_BASE, 0x10000000
+
+# Relative to this file
+.include "nested.s"
@@ -0,0 +1 @@
+.equ NESTED, 5
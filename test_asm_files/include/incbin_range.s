@@ -0,0 +1 @@
+.incbin "common/blob.bin", 4, 8
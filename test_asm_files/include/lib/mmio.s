@@ -0,0 +1,4 @@
+.macro store_byte base, value
+    li t0, \value
+    sb t0, 0(\base)
+.endm
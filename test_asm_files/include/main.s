@@ -0,0 +1,14 @@
+.include "common/defs.s"
+.include "mmio.s"    # Found through the include path
+
+.text
+start:
+    li a0, UART_BASE
+    store_byte a0, 'A'
+    ret
+
+.data
+font:
+    .incbin "common/blob.bin", 2, 4
+rest:
+    .incbin "common/blob.bin", 6
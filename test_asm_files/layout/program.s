@@ -0,0 +1,31 @@
+# Startup code placed by fpga.ld
+.globl reset
+
+.section .text.init, "ax"
+reset:
+    la sp, _stack_top
+    lui t0, %hi(_bss_end)
+    j main
+
+.text
+main:
+    la a0, message
+    la a1, counter
+    lw a2, 0(a1)
+    ebreak
+
+.section .rodata
+message:
+    .string "fpga"
+
+.data
+counter:
+    .word 1
+
+.bss
+buffer:
+    .zero 64
+
+.section .sdata, "aw"
+small:
+    .word 2
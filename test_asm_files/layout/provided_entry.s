@@ -0,0 +1,5 @@
+# No reset label, so fpga.ld provides it at the start of ROM
+.text
+main:
+    la sp, _stack_top
+    j main
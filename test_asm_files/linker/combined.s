@@ -0,0 +1,3 @@
+# The same program as main.s and lib.s in a single file
+.include "main.s"
+.include "lib.s"
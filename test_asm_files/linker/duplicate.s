@@ -0,0 +1,6 @@
+# Defines print again
+.globl print
+
+.text
+print:
+    ret
@@ -0,0 +1,3 @@
+# Branches to a label more than 4 KiB away in another file
+.text
+    beqz a0, far_away
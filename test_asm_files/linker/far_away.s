@@ -0,0 +1,6 @@
+.globl far_away
+
+.text
+    .zero 8192
+far_away:
+    ret
@@ -0,0 +1,22 @@
+# Definitions used by main.s
+.globl print, exit, counter, greeting, buffer
+
+.text
+print:
+    mv t0, a0
+    ret
+exit:
+    li a7, 93
+    ecall
+
+.section .rodata
+greeting:
+    .string "hi"
+
+.data
+counter:
+    .word 7
+
+.bss
+buffer:
+    .zero 8
@@ -0,0 +1,19 @@
+# Refers to code and data defined in lib.s
+.globl _start
+
+.text
+_start:
+    la a0, greeting             # PC-relative to another file
+    call print
+    lui t0, %hi(counter)        # Absolute
+    lw t1, %lo(counter)(t0)
+    addi t1, t1, 1
+    sw t1, %lo(counter)(t0)
+1:  auipc t2, %pcrel_hi(buffer)
+    sw t1, %pcrel_lo(1b)(t2)
+    beqz t1, exit
+    j exit
+
+.data
+pointers:
+    .word print, counter + 4
@@ -0,0 +1,14 @@
+# A weak exit that lib.s overrides and a weak handler nobody defines
+.globl _start
+.weak exit, handler
+
+.text
+_start:
+    call handler
+    ebreak
+exit:
+    ebreak
+
+.data
+handlers:
+    .word exit, handler
@@ -0,0 +1,3 @@
+helper:
+    addi a0, a0, COUNT
+    ret
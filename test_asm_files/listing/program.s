@@ -0,0 +1,27 @@
+# Listing of expansions, data, includes and macros
+.equ COUNT, 3
+
+.macro push reg
+    addi sp, sp, -4
+    sw \reg, 0(sp)
+.endm
+
+.text
+main:
+    li a0, 0x12345
+    call helper
+    push ra
+.rept 2
+    nop
+.endr
+.include "helper.s"
+
+.data
+message: .asciz "Listing test: more than 32 bytes long!"
+.align 2
+value:   .word 0x12345678
+
+.macro countdown reg
+1:  addi \reg, \reg, -1
+    bnez \reg, 1b
+.endm
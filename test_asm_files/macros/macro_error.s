@@ -0,0 +1,7 @@
+.macro load_pair dst, src
+    lw \dst, 0(\src)
+    lw \dst, 4(\src)
+.endm
+
+    nop
+    load_pair a0, q9
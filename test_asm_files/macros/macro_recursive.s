@@ -0,0 +1,6 @@
+.macro forever
+    nop
+    forever
+.endm
+
+    forever
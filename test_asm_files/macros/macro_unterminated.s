@@ -0,0 +1,3 @@
+.macro push reg
+    addi sp, sp, -4
+    sw \reg, 0(sp)
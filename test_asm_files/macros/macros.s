@@ -0,0 +1,44 @@
+.equ UART, 0x10000000
+
+.macro push reg
+    addi sp, sp, -4
+    sw \reg, 0(sp)
+.endm
+
+.macro pop reg
+    lw \reg, 0(sp)
+    addi sp, sp, 4
+.endm
+
+# Defaults and named arguments
+.macro write_mmio base, value=0, offset=0
+    li t0, \base
+    li t1, \value
+    sw t1, \offset(t0)
+.endm
+
+# Variable argument lists
+.macro words first, rest:vararg
+    .word \first
+    .word \rest
+.endm
+
+# Unique labels per expansion
+.macro wait count:req
+loop\@:
+    addi \count, \count, -1
+    bnez \count, loop\@
+.endm
+
+start:
+    push ra
+    write_mmio UART, 65
+    write_mmio UART, offset=4
+    wait a0
+    wait a1
+    pop ra
+    ret
+
+.data
+table:
+    words 1, 2, 3, 4
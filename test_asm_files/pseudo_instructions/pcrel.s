@@ -0,0 +1,17 @@
+.text
+start:
+    la a0, message
+    call func
+    lw a1, counter
+    sw a1, counter, t0
+    tail func
+    .skip 0x7F0
+func:
+    ret
+
+.data
+message:
+    .asciz "hi"
+    .align 2
+counter:
+    .word 5
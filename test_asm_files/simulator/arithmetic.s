@@ -0,0 +1,21 @@
+# Edge cases of the M extension, shifts and comparisons
+.text
+    li t0, -7
+    li t1, 2
+    div s0, t0, t1          # -3, rounded towards zero
+    rem s1, t0, t1          # -1, with the sign of the dividend
+    divu s2, t0, zero       # all ones
+    rem s3, t0, zero        # the dividend
+    li t2, 0x80000000
+    li t3, -1
+    div s4, t2, t3          # overflows back to 0x80000000
+    rem s5, t2, t3          # 0
+    mulh s6, t2, t2         # 0x40000000
+    mulu s7, t3, t3         # 0xfffffffe
+    mulsu s8, t3, t3        # 0xffffffff
+    srl s9, t3, t1          # 0x3fffffff
+    sra s10, t3, t1         # -1
+    sltu s11, zero, t3      # 1
+    slt a0, t3, zero        # 1
+    srai a1, t2, 31         # -1
+    ebreak
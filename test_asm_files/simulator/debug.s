@@ -0,0 +1,29 @@
+# Adds the values to total with a call per value and exits with the sum
+.text
+_start:
+    la s0, values
+    li s1, 3
+loop:
+    lw a0, 0(s0)
+    call add_total
+    addi s0, s0, 4
+    addi s1, s1, -1
+    bnez s1, loop
+
+    la t0, total
+    lw a0, 0(t0)
+    li a7, 93               # exit2
+    ecall
+
+add_total:
+    la t0, total
+    lw t1, 0(t0)
+    add t1, t1, a0
+    sw t1, 0(t0)
+    ret
+
+.data
+values:
+    .word 5, 7, 9
+total:
+    .word 0
@@ -0,0 +1,6 @@
+# exit2 is 17 in Venus, like MARS, but 93 in RARS
+.text
+    li a7, 17
+    li a0, 17               # exit2 in Venus
+    li a1, 7
+    ecall
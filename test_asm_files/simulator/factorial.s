@@ -0,0 +1,25 @@
+# Recursive factorial of 10, the result ends up in a0
+.text
+_start:
+    li a0, 10
+    call factorial
+    ebreak
+
+# a0 = a0!
+factorial:
+    addi sp, sp, -8
+    sw ra, 4(sp)
+    sw a0, 0(sp)
+    li t0, 1
+    ble a0, t0, base
+    addi a0, a0, -1
+    call factorial
+    lw t0, 0(sp)
+    mul a0, a0, t0
+    j done
+base:
+    li a0, 1
+done:
+    lw ra, 4(sp)
+    addi sp, sp, 8
+    ret
@@ -0,0 +1,31 @@
+# Writes a message with the Linux calls, grows the heap and exits with code 3
+.text
+    li a0, 1                # stdout
+    la a1, message
+    li a2, 6
+    li a7, 64               # write
+    ecall
+    mv s0, a0
+
+    li a0, 0                # brk(0) returns the current break
+    li a7, 214
+    ecall
+    mv s1, a0
+    addi a0, a0, 64
+    ecall
+    mv s2, a0
+
+    li a0, 7                # a file that isn't open
+    la a1, message
+    li a2, 6
+    li a7, 64
+    ecall
+    mv s3, a0
+
+    li a0, 3
+    li a7, 93               # exit
+    ecall
+
+.data
+message:
+    .ascii "hello\n"
@@ -0,0 +1,25 @@
+# Loads and stores of every width, including sign and zero extension
+.text
+    la t0, values
+    lb a0, 0(t0)            # -1
+    lbu a1, 0(t0)           # 0xff
+    lh a2, 2(t0)            # -32768
+    lhu a3, 2(t0)           # 0x8000
+    lw a4, 4(t0)
+    la t1, buffer
+    sb a1, 0(t1)
+    sh a3, 2(t1)
+    sw a4, 4(t1)
+    lw a5, 0(t1)            # 0x800000ff
+    csrrs a6, minstret, zero  # instructions retired before this one
+    ebreak
+
+.data
+values:
+    .byte 0xff, 0
+    .half 0x8000
+    .word 0x12345678
+
+.bss
+buffer:
+    .zero 8
@@ -0,0 +1,35 @@
+# Reads two numbers, prints their sum and exits with it as the code
+.text
+    li a7, 5                # read_int
+    ecall
+    mv s0, a0
+    li a7, 5
+    ecall
+    add s0, s0, a0
+
+    la a0, message
+    li a7, 4                # print_string
+    ecall
+    mv a0, s0
+    li a7, 1                # print_int
+    ecall
+    li a0, '\n'
+    li a7, 11               # print_char
+    ecall
+
+    # Two blocks of heap, the second one right after the first
+    li a0, 16
+    li a7, 9                # sbrk
+    ecall
+    mv s1, a0
+    li a0, 16
+    ecall
+    sub s2, a0, s1
+
+    mv a0, s0
+    li a7, 93               # exit2
+    ecall
+
+.data
+message:
+    .string "sum = "
@@ -0,0 +1,21 @@
+# Word variants, which work on the lower 32 bits and sign-extend the result
+.text
+    li t0, 0x7fffffff
+    addiw a0, t0, 1         # -0x80000000
+    addw a1, t0, t0         # -2
+    slli t1, t0, 32         # 0x7fffffff00000000
+    srli a2, t1, 63         # 0
+    srai a3, t1, 32         # 0x7fffffff
+    sraiw a4, a0, 4         # -0x8000000
+    srliw a5, a0, 4         # 0x8000000
+    la t2, slot
+    sd t1, 0(t2)
+    ld a6, 0(t2)
+    lwu a7, 4(t2)           # 0x7fffffff
+    subw s0, zero, t0       # -0x7fffffff
+    sllw s1, t0, t0         # shifts by 31, -0x80000000
+    ebreak
+
+.data
+slot:
+    .dword 0
@@ -0,0 +1,7 @@
+# Venus takes the call number in a0 and the argument in a1
+.text
+    li a0, 1                # print_int
+    li a1, -42
+    ecall
+    li a0, 10               # exit
+    ecall
@@ -0,0 +1,300 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{Assembler, AssemblerError, SectionKind, SymbolKind};
+
+    fn words(binary: &[u8]) -> Vec<u32> {
+        binary
+            .chunks(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_sections() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/directives/sections.s").unwrap();
+
+        // .text is placed first, followed by .rodata, .data and .bss
+        let names: Vec<(&str, SectionKind, u32, u32)> = assembler
+            .sections()
+            .iter()
+            .map(|s| (s.name.as_str(), s.kind, s.address, s.size()))
+            .collect();
+
+        assert_eq!(names, vec![
+            (".text", SectionKind::Text, 0, 16),
+            (".data", SectionKind::Data, 20, 4),
+            (".rodata", SectionKind::Rodata, 16, 4),
+            (".bss", SectionKind::Bss, 24, 0)
+        ]);
+
+        // Labels are section-relative until the sections are placed
+        assert_eq!(assembler.symbols()["value"].kind, SymbolKind::Label { section: 1 });
+        assert_eq!(assembler.symbols()["value"].value, 0);
+        assert_eq!(assembler.symbols()["func"].value, 8);
+        assert_eq!(assembler.symbol_address("start"), Some(0));
+        assert_eq!(assembler.symbol_address("func"), Some(8));
+        assert_eq!(assembler.symbol_address("table"), Some(16));
+        assert_eq!(assembler.symbol_address("value"), Some(20));
+        assert_eq!(assembler.symbol_address("buffer"), Some(24));
+
+        assert_eq!(words(&binary), vec![
+            0x008000EF,  // jal ra, func
+            0xFE000EE3,  // beq x0, x0, start
+            0x00000537,  // lui a0, value
+            0x00008067,  // ret
+            0x00100013,  // addi x0, x0, 1 (.rodata)
+            42           // .word 42 (.data)
+        ]);
+    }
+
+    #[test]
+    fn test_data_directives() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/directives/data.s").unwrap();
+        let data = &binary[assembler.symbol_address("bytes").unwrap() as usize..];
+
+        assert_eq!(&data[..4], &[0x01, 0xFF, 0x80, 0x7F]);                   // .byte
+        assert_eq!(&data[4..8], &[0x34, 0x12, 0xFF, 0xFF]);                  // .half
+        assert_eq!(&data[8..16], &[0xEF, 0xBE, 0xAD, 0xDE, 0x10, 0, 0, 0]);  // .word
+        assert_eq!(&data[16..24], &[0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11]);  // .dword
+        assert_eq!(&data[24..30], b"Hi#,\n\0");                             // .asciz
+        assert_eq!(&data[30..33], b"abc");                                  // .ascii
+        assert_eq!(&data[33..37], b"x\0y\0");                              // .string
+
+        // Jump table entries resolve to label addresses
+        let table = assembler.symbol_address("table").unwrap() as usize;
+        let entry = |i: usize| u32::from_le_bytes(binary[table + i * 4..table + i * 4 + 4].try_into().unwrap());
+        assert_eq!(entry(0), assembler.symbol_address("first").unwrap());
+        assert_eq!(entry(1), assembler.symbol_address("second").unwrap());
+    }
+
+    #[test]
+    fn test_data_out_of_range() {
+        let mut assembler = Assembler::new();
+        assert!(assembler.assemble("test_asm_files/directives/data_range.s").is_err());
+    }
+
+    #[test]
+    fn test_alignment_directives() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/directives/align.s").unwrap();
+
+        assert_eq!(assembler.symbol_address("aligned"), Some(16));
+        assert_eq!(words(&binary[..20]), vec![
+            0x00100513,  // addi a0, x0, 1
+            0x00000013,  // nop
+            0x00000013,  // nop
+            0x00000013,  // nop
+            0x00008067   // ret
+        ]);
+
+        // .data is aligned to the largest alignment it requested
+        let data = assembler.symbol_address("word").unwrap() - 4;
+        assert_eq!(data, 32);
+        assert_eq!(assembler.symbol_address("dword"), Some(data + 16));
+        assert_eq!(assembler.symbol_address("unaligned"), Some(data + 27));
+
+        let data = &binary[data as usize..];
+        assert_eq!(&data[..8], &[1, 0, 0, 0, 0x44, 0x33, 0x22, 0x11]);
+        assert_eq!(&data[8..16], &[2, 0xEE, 0xEE, 0xEE, 0xEE, 0xEE, 0xEE, 0xEE]);
+        assert_eq!(&data[16..32], &[0, 0, 0, 0xAA, 0xAA, 0, 0x34, 0x12, 0x34, 0x12, 3, 4, 0, 0, 0, 0]);
+
+        // .bss only reserves space
+        let buffer = assembler.symbol_address("buffer").unwrap();
+        assert_eq!(buffer % 4, 0);
+        assert_eq!(assembler.symbol_address("end"), Some(buffer + 64));
+        assert_eq!(binary.len(), 64);
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/directives/equ.s").unwrap();
+
+        assert_eq!(words(&binary[..44]), vec![
+            0x10000537,  // lui a0, 0x10000
+            0x00B52223,  // sw a1, 4(a0)
+            0x05D00893,  // addi a7, x0, 93
+            0x340312F3,  // csrrw t0, mscratch, t1
+            0x3401D2F3,  // csrrwi t0, mscratch, 3
+            0x00012637,  // lui a2, 0x12
+            0x34560613,  // addi a2, a2, 0x345
+            0xFE0002E3,  // beq x0, x0, start
+            0x00700693,  // addi a3, x0, 7
+            0x00000000,  // .word start
+            0x00012345   // .word BIG
+        ]);
+
+        // .set keeps the last value, .skip sees the redefined value
+        assert_eq!(assembler.symbols()["COUNT"].value, 7);
+        assert_eq!(binary.len(), 44 + 7);
+        assert_eq!(assembler.symbol_address("ENTRY"), Some(0));
+        // Constants are absolute symbols
+        assert_eq!(assembler.symbol_address("UART_BASE"), Some(0x1000_0000));
+        assert_eq!(assembler.symbol_address("MISSING"), None);
+    }
+
+    #[test]
+    fn test_constant_redefinition() {
+        let mut assembler = Assembler::new();
+
+        assert_eq!(
+            assembler.assemble("test_asm_files/directives/eqv_redefined.s"),
+            Err(AssemblerError::DuplicateSymbol("LIMIT".to_string()))
+        );
+        assert_eq!(
+            assembler.assemble("test_asm_files/directives/equ_label.s"),
+            Err(AssemblerError::DuplicateSymbol("loop".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_expressions() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/directives/expr.s").unwrap();
+
+        assert_eq!(words(&binary), vec![
+            0x02028293,  // addi t0, t0, 32
+            0x01412503,  // lw a0, 20(sp)
+            0x00A12023,  // sw a0, 0(sp)
+            0x00B50463,  // beq a0, a1, 8
+            0x01000593,  // li a1, 16
+            0x00000013,  // nop (padding for the forward reference)
+            0x04200613,  // li a2, 'A' + 1
+            0xFE9FF06F,  // jal x0, -24 (start + 4)
+            0x00008067,  // ret
+            32,          // end - start
+            8,           // WORDS
+            0x28,        // table + 4
+            12           // . - table
+        ]);
+    }
+
+    #[test]
+    fn test_relocation_operators() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/directives/reloc.s").unwrap();
+
+        assert_eq!(assembler.symbol_address("value"), Some(0x844));
+        assert_eq!(words(&binary[..36]), vec![
+            0x00001537,  // lui a0, %hi(value)
+            0x84450513,  // addi a0, a0, -0x7BC
+            0x123465B7,  // lui a1, %hi(BIG) (rounded up)
+            0xFFC5A583,  // lw a1, -4(a1)
+            0x84A5A223,  // sw a0, -0x7BC(a1)
+            0x00001297,  // auipc t0, %pcrel_hi(value)
+            0x83028293,  // addi t0, t0, -0x7D0
+            0x8302A303,  // lw t1, -0x7D0(t0)
+            0x00008067   // ret
+        ]);
+    }
+
+    #[test]
+    fn test_pcrel_pseudo_instructions() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/pseudo_instructions/pcrel.s").unwrap();
+
+        assert_eq!(assembler.symbol_address("func"), Some(0x818));
+        assert_eq!(assembler.symbol_address("message"), Some(0x81C));
+        assert_eq!(assembler.symbol_address("counter"), Some(0x820));
+
+        // Each pair is relative to its own auipc, with %hi rounded up for a negative %lo
+        assert_eq!(words(&binary[..40]), vec![
+            0x00001517,  // auipc a0, 1
+            0x81C50513,  // addi a0, a0, -0x7E4 (message)
+            0x00001097,  // auipc ra, 1
+            0x810080E7,  // jalr ra, -0x7F0(ra) (func)
+            0x00001597,  // auipc a1, 1
+            0x8105A583,  // lw a1, -0x7F0(a1) (counter)
+            0x00001297,  // auipc t0, 1
+            0x80B2A423,  // sw a1, -0x7F8(t0) (counter)
+            0x00000317,  // auipc t1, 0
+            0x7F830067   // jalr x0, 0x7F8(t1) (func)
+        ]);
+    }
+
+    #[test]
+    fn test_local_labels() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/directives/local_labels.s").unwrap();
+
+        assert_eq!(words(&binary), vec![
+            0x00400293,  // li t0, 4
+            0xFFF28293,  // 1: addi t0, t0, -1
+            0xFE029EE3,  // bnez t0, 1b
+            0x0080006F,  // j 1f
+            0x00000013,  // nop
+            0x00B50263,  // 1: beq a0, a1, 2f
+            0x00000517,  // 2: auipc a0, %pcrel_hi(msg)
+            0x01450513,  // addi a0, a0, %pcrel_lo(2b)
+            0x00000597,  // 1: auipc a1, %pcrel_hi(1f)
+            0x0105A583,  // lw a1, %pcrel_lo(1b)(a1)
+            0xFF9FF06F,  // jal x0, 1b
+            32,          // msg: .word 1b - start
+            48           // 1: .word 1b - start
+        ]);
+
+        // Numeric labels don't enter the symbol table
+        assert!(!assembler.symbols().contains_key("1"));
+    }
+
+    #[test]
+    fn test_conditionals_and_repetition() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/directives/conditional.s").unwrap();
+
+        assert_eq!(words(&binary[..32]), vec![
+            0x00200513,  // li a0, 2 (BOARD == 2)
+            0x00300593,  // li a1, 3 (HAS_UART)
+            0x00128293,  // .rept 3: addi t0, t0, 1
+            0x00128293,
+            0x00128293,
+            0x00000313,  // .irp: mv t1, zero
+            0x00000393,  //       mv t2, zero
+            0x00008067   // ret
+        ]);
+        assert_eq!(&binary[32..], &[b'1', b'2', b'3', 0xAA, 0xAA]);
+        assert_eq!(assembler.symbol_address("later"), Some(28));
+
+        // Defines take the place of the defaults
+        let mut assembler = Assembler::new();
+        assembler.define("DEBUG", 1);
+        assembler.define("STACK_SIZE", 128);
+        let binary = assembler.assemble("test_asm_files/directives/conditional.s").unwrap();
+
+        assert_eq!(words(&binary[8..12]), vec![0x00100073]);  // ebreak
+        assert_eq!(&binary[36..], &[b'1', b'2', b'3', 0xAA, 0xAA, 0xAA, 0xAA]);
+
+        // Defines can't be redefined by the source
+        let mut assembler = Assembler::new();
+        assembler.define("BOARD", 1);
+        assert_eq!(
+            assembler.assemble("test_asm_files/directives/conditional.s"),
+            Err(AssemblerError::DuplicateSymbol("BOARD".to_string()))
+        );
+
+        assert!(matches!(
+            Assembler::new().assemble("test_asm_files/directives/conditional_unterminated.s"),
+            Err(AssemblerError::InvalidDirective(_))
+        ));
+    }
+
+    #[test]
+    fn test_undefined_symbol_in_expression() {
+        let mut assembler = Assembler::new();
+
+        assert_eq!(
+            assembler.assemble("test_asm_files/directives/expr_undefined.s"),
+            Err(AssemblerError::UndefinedLabel("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_branch() {
+        assert!(matches!(
+            Assembler::new().assemble("test_asm_files/directives/expr_range.s"),
+            Err(AssemblerError::InvalidOperand(_))
+        ));
+    }
+}
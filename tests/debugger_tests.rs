@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use riscv_assembler::assembler::{Assembler, Xlen};
+    use riscv_assembler::simulator::{Debugger, Environment, Simulator, SyscallTable};
+
+    type TestDebugger = Debugger<Cursor<&'static [u8]>, Vec<u8>>;
+
+    fn debugger(path: &str) -> TestDebugger {
+        let mut assembler = Assembler::new();
+        assembler.set_listing(true);
+        let segments = assembler.assemble_segments(path).unwrap();
+
+        let mut simulator = Simulator::new(Xlen::Rv32);
+        simulator.load_segments(&segments);
+        simulator.set_pc(assembler.entry_address().unwrap() as u64);
+
+        let environment = Environment::new(SyscallTable::Rars, Cursor::new(&b""[..]), Vec::new(), 0x10000);
+        Debugger::new(simulator, environment, &assembler.symbol_map(), assembler.line_map().unwrap(), 1000)
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = debugger("test_asm_files/simulator/debug.s");
+
+        assert_eq!(debugger.location(), "0x00000000 <_start>  test_asm_files/simulator/debug.s:4  la s0, values");
+        assert_eq!(debugger.execute("break loop"), Ok("Breakpoint at 0x0000000c <loop>".to_string()));
+        assert_eq!(debugger.execute("b 0x44"), Ok("Breakpoint at 0x00000044 <add_total+12>".to_string()));
+
+        assert_eq!(
+            debugger.execute("continue").unwrap(),
+            "Breakpoint at 0x0000000c <loop>  test_asm_files/simulator/debug.s:7  lw a0, 0(s0)"
+        );
+        assert_eq!(
+            debugger.execute("c").unwrap(),
+            "Breakpoint at 0x00000044 <add_total+12>  test_asm_files/simulator/debug.s:21  add t1, t1, a0"
+        );
+
+        assert!(debugger.execute("delete 0x44").is_ok());
+        assert!(debugger.execute("delete 0x44").is_err());
+        assert_eq!(debugger.execute("info").unwrap(), "Breakpoints:\n  0x0000000c <loop>\nWatchpoints:");
+
+        // The breakpoint at loop stops the second and third iteration, then the program exits
+        assert!(debugger.execute("c").unwrap().starts_with("Breakpoint at 0x0000000c <loop>"));
+        assert!(debugger.execute("c").unwrap().starts_with("Breakpoint at 0x0000000c <loop>"));
+        assert_eq!(debugger.execute("c"), Ok("Program exited with code 21".to_string()));
+        assert!(debugger.finished());
+        assert_eq!(debugger.execute("step"), Err("The program isn't running, exited with code 21".to_string()));
+    }
+
+    #[test]
+    fn test_step_and_next() {
+        let mut debugger = debugger("test_asm_files/simulator/debug.s");
+
+        // step runs single instructions, so the two words of la take two steps
+        assert!(debugger.execute("step").unwrap().starts_with("0x00000004 <_start+4>  test_asm_files/simulator/debug.s:4"));
+        assert!(debugger.execute("s 2").unwrap().starts_with("0x0000000c <loop>"));
+
+        // next runs a whole source line, including the function it calls
+        assert!(debugger.execute("next").unwrap().ends_with("debug.s:8  call add_total"));
+        assert!(debugger.execute("next").unwrap().ends_with("debug.s:9  addi s0, s0, 4"));
+        assert_eq!(debugger.simulator().register(10), 5);
+        assert_eq!(debugger.execute("x total 1"), Ok("0x0000005c <total>:  0x00000005".to_string()));
+
+        // Stepping into the call instead
+        debugger.execute("b loop").unwrap();
+        debugger.execute("c").unwrap();
+        debugger.execute("n").unwrap();
+        assert!(debugger.execute("s 2").unwrap().starts_with("0x00000038 <add_total>"));
+
+        // Counts below 1 are rejected instead of stepping forever
+        assert_eq!(debugger.execute("s 0"), Err("Count must be positive: 0".to_string()));
+        assert_eq!(debugger.execute("step -1"), Err("Count must be positive: -1".to_string()));
+        assert_eq!(debugger.execute("x total -1"), Err("Count must be positive: -1".to_string()));
+        assert!(debugger.execute("x total 0").is_err());
+    }
+
+    #[test]
+    fn test_inspection() {
+        let mut debugger = debugger("test_asm_files/simulator/debug.s");
+        debugger.execute("s 4").unwrap();
+
+        assert_eq!(debugger.execute("print a0"), Ok("a0 = 0x00000005 (5)".to_string()));
+        assert_eq!(debugger.execute("p x9"), Ok("s1 = 0x00000003 (3)".to_string()));
+        assert_eq!(debugger.execute("p pc"), Ok("pc = 0x00000010 <loop+4>".to_string()));
+        assert_eq!(debugger.execute("p values"), Ok("values = 0x00000050".to_string()));
+        assert!(debugger.execute("p nothing").is_err());
+
+        // Memory by label, register or address, four words per row
+        assert_eq!(
+            debugger.execute("x values 5"),
+            Ok("0x00000050 <values>:  0x00000005 0x00000007 0x00000009 0x00000000\n0x00000060:  0x00000000".to_string())
+        );
+        assert_eq!(debugger.execute("x s0 1"), Ok("0x00000050 <values>:  0x00000005".to_string()));
+        assert!(debugger.execute("x").is_err());
+
+        let registers = debugger.execute("registers").unwrap();
+        assert!(registers.starts_with("pc   0x00000010\n"));
+        assert!(registers.contains("a0   0x00000005"));
+
+        assert_eq!(debugger.execute("list").unwrap(), concat!(
+            "      4      la s0, values\n",
+            "      5      li s1, 3\n",
+            "      6  loop:\n",
+            "      7      lw a0, 0(s0)\n",
+            "=>    8      call add_total\n",
+            "      9      addi s0, s0, 4\n",
+            "     10      addi s1, s1, -1\n",
+            "     11      bnez s1, loop\n",
+            "     12"
+        ));
+
+        assert!(debugger.execute("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = debugger("test_asm_files/simulator/debug.s");
+
+        assert_eq!(debugger.execute("watch total"), Ok("Watchpoint at 0x0000005c <total> = 0x00000000".to_string()));
+        assert_eq!(debugger.execute("c").unwrap(), concat!(
+            "Watchpoint at 0x0000005c <total>: 0x00000000 -> 0x00000005\n",
+            "0x0000004c <add_total+20>  test_asm_files/simulator/debug.s:23  ret"
+        ));
+        assert!(debugger.execute("c").unwrap().starts_with("Watchpoint at 0x0000005c <total>: 0x00000005 -> 0x0000000c\n"));
+    }
+
+    #[test]
+    fn test_ebreak_and_limit() {
+        // The program stops at its ebreak and falls through into factorial when continued
+        let mut debugger = debugger("test_asm_files/simulator/factorial.s");
+        assert!(debugger.execute("c").unwrap().starts_with("Stopped at ebreak\n0x0000000c <_start+12>"));
+        assert!(debugger.execute("s").unwrap().starts_with("0x00000010 <factorial>"));
+
+        // Endless recursion runs into the instruction limit of a command
+        assert!(debugger.execute("c").unwrap().starts_with("Stopped after 1000 instructions\n"));
+        assert!(!debugger.finished());
+    }
+}
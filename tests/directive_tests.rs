@@ -0,0 +1,101 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::directives::*;
+    use riscv_assembler::assembler::parser::split_operands;
+    use riscv_assembler::assembler::{Binding, SectionKind};
+
+    #[test]
+    fn test_parse_string() {
+        assert_eq!(parse_string("\"abc\""), Ok(b"abc".to_vec()));
+        assert_eq!(parse_string("\"\""), Ok(vec![]));
+        assert_eq!(parse_string(r#""a\tb\n""#), Ok(b"a\tb\n".to_vec()));
+        assert_eq!(parse_string(r#""\"\\\'""#), Ok(b"\"\\'".to_vec()));
+        assert_eq!(parse_string(r#""\x41\x4a""#), Ok(b"AJ".to_vec()));
+        assert_eq!(parse_string(r#""\101\0\12""#), Ok(vec![b'A', 0, b'\n']));
+
+        // Invalid strings
+        assert!(parse_string("abc").is_err());
+        assert!(parse_string("\"abc").is_err());
+        assert!(parse_string(r#""\q""#).is_err());
+        assert!(parse_string(r#""\x""#).is_err());
+    }
+
+    #[test]
+    fn test_split_operands() {
+        assert_eq!(split_operands(""), Vec::<&str>::new());
+        assert_eq!(split_operands("x1, x2,x3"), vec!["x1", "x2", "x3"]);
+        assert_eq!(split_operands(r#""a,b", "c\",d""#), vec![r#""a,b""#, r#""c\",d""#]);
+        assert_eq!(split_operands("',', 1"), vec!["','", "1"]);
+        assert_eq!(split_operands("1, ,2"), vec!["1", "", "2"]);
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        assert_eq!(
+            Directive::parse(".section .text.init, \"ax\", @progbits"),
+            Ok(Directive::Section { name: ".text.init".to_string(), kind: SectionKind::Text })
+        );
+        assert_eq!(
+            Directive::parse(".section .sbss"),
+            Ok(Directive::Section { name: ".sbss".to_string(), kind: SectionKind::Bss })
+        );
+        assert_eq!(
+            Directive::parse(".half 1, label"),
+            Ok(Directive::Data { size: 2, values: vec!["1".to_string(), "label".to_string()] })
+        );
+        assert_eq!(
+            Directive::parse(".asciz \"a\", \"b\""),
+            Ok(Directive::Ascii { bytes: vec![b'a', 0, b'b', 0] })
+        );
+
+        assert_eq!(
+            Directive::parse(".p2align 2,,8"),
+            Ok(Directive::Align {
+                alignment: "2".to_string(),
+                power_of_two: true,
+                fill: None,
+                max_skip: Some("8".to_string())
+            })
+        );
+        assert_eq!(
+            Directive::parse(".space 16, 0xFF"),
+            Ok(Directive::Fill { repeat: "16".to_string(), size: None, value: Some("0xFF".to_string()) })
+        );
+
+        assert_eq!(
+            Directive::parse(".eqv LIMIT, 0x10"),
+            Ok(Directive::Equ { name: "LIMIT".to_string(), value: "0x10".to_string(), redefinable: false })
+        );
+
+        assert_eq!(
+            Directive::parse(".ifndef BOARD"),
+            Ok(Directive::If { condition: Condition::Undefined("BOARD".to_string()) })
+        );
+        assert_eq!(
+            Directive::parse(".ifb"),
+            Ok(Directive::If { condition: Condition::Blank(String::new()) })
+        );
+        assert_eq!(
+            Directive::parse(".irpc digit, 0123"),
+            Ok(Directive::Repeat {
+                param: "digit".to_string(),
+                values: vec!["0".to_string(), "1".to_string(), "2".to_string(), "3".to_string()]
+            })
+        );
+
+        assert_eq!(
+            Directive::parse(".globl _start, main"),
+            Ok(Directive::Binding { binding: Binding::Global, names: vec!["_start".to_string(), "main".to_string()] })
+        );
+
+        assert!(Directive::parse(".equ 1abc, 2").is_err());
+        assert!(Directive::parse(".weak").is_err());
+        assert!(Directive::parse(".if").is_err());
+        assert!(Directive::parse(".irp 1, a, b").is_err());
+        assert!(Directive::parse(".set X").is_err());
+        assert!(Directive::parse(".balign").is_err());
+        assert!(Directive::parse(".word 1,").is_err());
+        assert!(Directive::parse(".text extra").is_err());
+        assert!(Directive::parse(".bogus").is_err());
+    }
+}
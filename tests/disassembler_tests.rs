@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use riscv_assembler::assembler::Assembler;
+    use riscv_assembler::assembler::instructions::{InstructionSet, InstructionType};
+    use riscv_assembler::assembler::parser::Parser;
+    use riscv_assembler::disassembler::Disassembler;
+
+    // An instruction with sample operands for every entry of the table
+    fn sample(mnemonic: &str, fmt: InstructionType, opcode: u32, funct3: Option<u32>, funct7: Option<u32>) -> String {
+        match (fmt, opcode) {
+            (InstructionType::R, _) => format!("{} a0, a1, t6", mnemonic),
+            (InstructionType::I, 0b1110011) => match funct3 {
+                Some(0) => mnemonic.to_string(),
+                Some(f3) if f3 & 0b100 == 0 => format!("{} a0, mstatus, s11", mnemonic),
+                _ => format!("{} t0, mepc, 17", mnemonic)
+            },
+            (InstructionType::I, 0b0000011 | 0b1100111) => format!("{} s0, -16(sp)", mnemonic),
+            (InstructionType::I, 0b0011011) if funct7.is_some() => format!("{} a0, a1, 31", mnemonic),
+            (InstructionType::I, _) if funct7.is_some() => format!("{} a0, a1, 63", mnemonic),
+            (InstructionType::I, _) => format!("{} a0, zero, -2048", mnemonic),
+            (InstructionType::S, _) => format!("{} a7, 2047(tp)", mnemonic),
+            (InstructionType::B, _) => format!("{} t1, t2, -4096", mnemonic),
+            (InstructionType::U, _) => format!("{} gp, 0xfffff000", mnemonic),
+            (InstructionType::J, _) => format!("{} ra, 1048574", mnemonic)
+        }
+    }
+
+    #[test]
+    fn test_round_trip_table() {
+        let parser = Parser::new();
+        let disassembler = Disassembler::new();
+        let symbols: HashMap<String, u32> = HashMap::new();
+        let mut count = 0;
+
+        for (mnemonic, fmt) in InstructionSet::new().iter() {
+            let source = sample(mnemonic, fmt.fmt, fmt.opcode, fmt.funct3, fmt.funct7);
+            let words = parser.parse_line(&source, 0x1000, &symbols).unwrap();
+            assert_eq!(words.len(), 1, "{}", source);
+
+            let instruction = disassembler.decode(words[0], 0x1000).unwrap_or_else(|| panic!("{}", source));
+            assert_eq!(instruction.mnemonic, mnemonic);
+            assert_eq!(instruction.to_string(), source);
+
+            let reassembled = parser.parse_line(&instruction.to_string(), 0x1000, &symbols).unwrap();
+            assert_eq!(reassembled, words, "{}", source);
+            count += 1;
+        }
+
+        assert_eq!(count, 65);
+    }
+
+    #[test]
+    fn test_decode() {
+        let disassembler = Disassembler::new();
+        let decode = |word: u32| disassembler.decode(word, 0x100).map(|i| i.to_string());
+
+        assert_eq!(decode(0x00800513), Some("addi a0, zero, 8".to_string()));
+        assert_eq!(decode(0x40355513), Some("srai a0, a0, 3".to_string()));
+        assert_eq!(decode(0x00355513), Some("srli a0, a0, 3".to_string()));
+        assert_eq!(decode(0x4035551b), Some("sraiw a0, a0, 3".to_string()));
+        assert_eq!(decode(0x00012537), Some("lui a0, 0x12000".to_string()));
+        assert_eq!(decode(0x34102573), Some("csrrs a0, mepc, zero".to_string()));
+        assert_eq!(decode(0x7c002573), Some("csrrs a0, 0x7c0, zero".to_string()));
+        assert_eq!(decode(0x00100073), Some("ebreak".to_string()));
+
+        // Branch targets are relative to the instruction
+        let branch = disassembler.decode(0xfeb50ce3, 0x100).unwrap();
+        assert_eq!(branch.to_string(), "beq a0, a1, -8");
+        assert_eq!(branch.target, Some(0xF8));
+
+        // Data and instructions outside of the table
+        assert_eq!(decode(0x00000000), None);
+        assert_eq!(decode(0xFFFFFFFF), None);
+        assert_eq!(decode(0x30200073), None);  // mret
+        assert_eq!(decode(0x0200551b), None);  // srliw with a 6-bit shift amount
+        assert_eq!(decode(0x00200073), None);  // ecall with an immediate
+    }
+
+    #[test]
+    fn test_round_trip_labels() {
+        let mut assembler = Assembler::new();
+        let image = assembler.assemble("test_asm_files/linker/combined.s").unwrap();
+        let text = &assembler.sections()[0];
+        assert_eq!(text.name, ".text");
+
+        let symbol_map = assembler.symbol_map();
+        let disassembler = Disassembler::with_symbols(&symbol_map);
+        let labels: HashMap<String, u32> = symbol_map
+            .symbols()
+            .iter()
+            .map(|s| (s.name.clone(), s.value as u32))
+            .collect();
+
+        let parser = Parser::new();
+        let mut targets = Vec::new();
+
+        for (idx, word) in image[..text.data.len()].chunks(4).enumerate() {
+            let address = text.address + idx as u32 * 4;
+            let word = u32::from_le_bytes(word.try_into().unwrap());
+            let instruction = disassembler.decode(word, address).unwrap();
+
+            if let Some(target) = instruction.target {
+                targets.push((instruction.to_string(), target));
+            }
+
+            let reassembled = parser.parse_line(&instruction.to_string(), address, &labels).unwrap();
+            assert_eq!(reassembled, vec![word], "{}", instruction);
+        }
+
+        // Branches and jumps to a label are named after it
+        assert_eq!(targets, vec![
+            ("beq t1, zero, exit".to_string(), 0x38),
+            ("jal zero, exit".to_string(), 0x38)
+        ]);
+    }
+}
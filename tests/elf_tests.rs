@@ -0,0 +1,199 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use riscv_assembler::assembler::{Assembler, AssemblerError, MemoryLayout, Xlen};
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    // Section name and (offset, size) of every ELF32 section header
+    fn sections32(object: &[u8]) -> Vec<(String, usize, usize)> {
+        let headers = u32_at(object, 32) as usize;
+        let count = u16_at(object, 48) as usize;
+        let names = headers + u16_at(object, 50) as usize * 40;
+        let names = u32_at(object, names + 16) as usize;
+
+        (0..count)
+            .map(|idx| {
+                let header = headers + idx * 40;
+                let name = names + u32_at(object, header) as usize;
+                let end = object[name..].iter().position(|&b| b == 0).unwrap();
+
+                (
+                    String::from_utf8(object[name..name + end].to_vec()).unwrap(),
+                    u32_at(object, header + 16) as usize,
+                    u32_at(object, header + 20) as usize
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_object_reference() {
+        let mut assembler = Assembler::new();
+        let object = assembler.assemble_object("test_asm_files/elf/object.s").unwrap();
+        assert_eq!(object, fs::read("test_asm_files/elf/object32.o").unwrap());
+
+        assembler.set_xlen(Xlen::Rv64);
+        let object = assembler.assemble_object("test_asm_files/elf/object.s").unwrap();
+        assert_eq!(object, fs::read("test_asm_files/elf/object64.o").unwrap());
+    }
+
+    #[test]
+    fn test_object_contents() {
+        let mut assembler = Assembler::new();
+        let object = assembler.assemble_object("test_asm_files/elf/object.s").unwrap();
+
+        // ELF32, little-endian, relocatable, RISC-V
+        assert_eq!(&object[..6], b"\x7FELF\x01\x01");
+        assert_eq!(u16_at(&object, 16), 1);
+        assert_eq!(u16_at(&object, 18), 243);
+
+        let sections = sections32(&object);
+        let names: Vec<&str> = sections.iter().map(|(name, ..)| name.as_str()).collect();
+        assert_eq!(names, vec![
+            "", ".text", ".rodata", ".data", ".bss", ".rela.text", ".rela.data", ".symtab", ".strtab", ".shstrtab"
+        ]);
+
+        // Relocated immediates are left as zero
+        let (_, text, _) = sections[1];
+        assert_eq!(u32_at(&object, text), 0x00000517);  // auipc a0, 0
+        assert_eq!(u32_at(&object, text + 0x28), 0x00050663);  // beqz a0, done is resolved in place
+
+        // (offset, type, addend) of the .rela.text entries
+        let (_, rela, size) = sections[5];
+        let relocations: Vec<(u32, u32, i32)> = (rela..rela + size)
+            .step_by(12)
+            .map(|entry| (u32_at(&object, entry), u32_at(&object, entry + 4) & 0xFF, u32_at(&object, entry + 8) as i32))
+            .collect();
+
+        assert_eq!(relocations, vec![
+            (0x00, 23, 0),  // la a0, message: PCREL_HI20
+            (0x04, 24, 0),  //                 PCREL_LO12_I
+            (0x08, 19, 0),  // call puts: CALL_PLT
+            (0x10, 26, 0),  // %hi(counter): HI20
+            (0x14, 27, 0),  // %lo(counter): LO12_I
+            (0x1C, 28, 0),  // %lo(counter): LO12_S
+            (0x20, 23, 4),  // %pcrel_hi(buffer + 4): PCREL_HI20
+            (0x24, 25, 0),  // %pcrel_lo(1b): PCREL_LO12_S
+            (0x2C, 16, 0),  // bnez a0, handler: BRANCH
+            (0x30, 17, 0),  // j exit: JAL
+            (0x38, 19, 0)   // tail exit: CALL_PLT
+        ]);
+
+        // Labels keep their section-relative value
+        assert_eq!(assembler.symbols()["done"].value, 0x34);
+    }
+
+    #[test]
+    fn test_object_errors() {
+        let mut assembler = Assembler::new();
+
+        assert!(matches!(
+            assembler.assemble_object("test_asm_files/elf/bad_relocation.s"),
+            Err(AssemblerError::InvalidOperand(_))
+        ));
+        assert!(matches!(
+            assembler.assemble_object("test_asm_files/elf/cross_section.s"),
+            Err(AssemblerError::InvalidOperand(_))
+        ));
+        assert!(matches!(
+            assembler.assemble_object("test_asm_files/elf/undefined_local.s"),
+            Err(AssemblerError::UndefinedLabel(_))
+        ));
+
+        // Undefined symbols are only allowed in object files
+        assert!(matches!(
+            assembler.assemble("test_asm_files/elf/object.s"),
+            Err(AssemblerError::UndefinedLabel(_))
+        ));
+    }
+
+    // (flags, offset, address, file size, memory size) of every ELF32 program header
+    fn segments32(elf: &[u8]) -> Vec<(u32, u32, u32, u32, u32)> {
+        let headers = u32_at(elf, 28) as usize;
+        let count = u16_at(elf, 44) as usize;
+
+        (0..count)
+            .map(|idx| {
+                let header = headers + idx * 32;
+                assert_eq!(u32_at(elf, header), 1);  // PT_LOAD
+
+                (
+                    u32_at(elf, header + 24),
+                    u32_at(elf, header + 4),
+                    u32_at(elf, header + 8),
+                    u32_at(elf, header + 16),
+                    u32_at(elf, header + 20)
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_executable() {
+        let mut assembler = Assembler::new();
+        assembler.set_base_address(0x8000_0000);
+        let elf = assembler.assemble_executable("test_asm_files/elf/executable.s").unwrap();
+
+        // ELF32 executable entered at _start
+        assert_eq!(&elf[..5], b"\x7FELF\x01");
+        assert_eq!(u16_at(&elf, 16), 2);
+        assert_eq!(u32_at(&elf, 24), 0x8000_0000);
+
+        // Text (r-x), read-only data (r--), then data with .bss (rw-), each on its own page
+        assert_eq!(segments32(&elf), vec![
+            (5, 0x1000, 0x8000_0000, 0x20, 0x20),
+            (4, 0x2000, 0x8000_1000, 3, 3),
+            (6, 0x3000, 0x8000_2000, 4, 0x44)
+        ]);
+
+        // The loaded bytes are the contents of the sections
+        assert_eq!(&elf[0x1000..0x1020], &assembler.sections()[0].data[..]);
+        assert_eq!(&elf[0x2000..0x2003], b"hi\0");
+        assert_eq!(&elf[0x3000..0x3004], &5u32.to_le_bytes());
+
+        // ELF64 program headers
+        assembler.set_xlen(Xlen::Rv64);
+        let elf = assembler.assemble_executable("test_asm_files/elf/executable.s").unwrap();
+        assert_eq!(elf[4], 2);
+        assert_eq!(u16_at(&elf, 56), 3);
+        assert_eq!(u16_at(&elf, 54), 56);
+    }
+
+    #[test]
+    fn test_executable_shared_page() {
+        // A layout script that puts code and data on the same page gets a single rwx segment
+        let mut assembler = Assembler::new();
+        assembler.set_layout(MemoryLayout::from_file("test_asm_files/layout/flat.ld").unwrap());
+        let elf = assembler.assemble_executable("test_asm_files/layout/program.s").unwrap();
+
+        let segments = segments32(&elf);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].0, 7);
+        assert_eq!(segments[0].2, 0x1000);
+    }
+
+    #[test]
+    fn test_executable_entry() {
+        // Without _start, execution starts at the first byte of .text
+        let mut assembler = Assembler::new();
+        let elf = assembler.assemble_executable("test_asm_files/elf/no_start.s").unwrap();
+        assert_eq!(u32_at(&elf, 24), 0);
+
+        assembler.set_entry("main");
+        let elf = assembler.assemble_executable("test_asm_files/elf/no_start.s").unwrap();
+        assert_eq!(u32_at(&elf, 24), 4);
+
+        assembler.set_entry("missing");
+        assert!(matches!(
+            assembler.assemble_executable("test_asm_files/elf/no_start.s"),
+            Err(AssemblerError::UndefinedLabel(_))
+        ));
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::Assembler;
+    use riscv_assembler::assembler::embed::*;
+
+    // addi a0, x0, 8
+    // addi a1, x0, 8
+    // and a trailing byte that is padded to a word
+    const IMAGE: [u8; 9] = [0x13, 0x05, 0x80, 0x00, 0x93, 0x05, 0x80, 0x00, 0xFF];
+
+    #[test]
+    fn test_c_header() {
+        assert_eq!(generate_c_header(&IMAGE, "boot_stub", &[("main", 0x1000), (".loop", 0x1004)]), "\
+#ifndef BOOT_STUB_H
+#define BOOT_STUB_H
+
+#include <stdint.h>
+
+#define BOOT_STUB_LEN 3
+#define BOOT_STUB_MAIN 0x00001000u
+#define BOOT_STUB__LOOP 0x00001004u
+
+static const uint32_t boot_stub[BOOT_STUB_LEN] = {
+    0x00800513, 0x00800593, 0x000000ff,
+};
+
+#endif /* BOOT_STUB_H */
+");
+    }
+
+    #[test]
+    fn test_rust_array() {
+        assert_eq!(generate_rust_array(&IMAGE[..8], "boot-stub", &[]), "\
+pub const BOOT_STUB: [u32; 2] = [
+    0x00800513, 0x00800593,
+];
+");
+
+        let source = generate_rust_array(&[0; 20], "1st", &[("main", 0x10)]);
+        assert!(source.starts_with("pub const _1ST_MAIN: u32 = 0x00000010;\n\npub const _1ST: [u32; 5] = [\n"));
+        assert!(source.ends_with("    0x00000000, 0x00000000, 0x00000000, 0x00000000,\n    0x00000000,\n];\n"));
+    }
+
+    #[test]
+    fn test_embed_labels() {
+        let mut assembler = Assembler::new();
+        let image = assembler.assemble("test_asm_files/linker/combined.s").unwrap();
+        let print = assembler.symbol_address("print").unwrap();
+
+        let header = generate_c_header(&image, "combined", &[("print", print)]);
+        assert!(header.contains("#define COMBINED_LEN 20\n#define COMBINED_PRINT 0x00000030u\n"));
+        assert!(header.contains("    0x00000517, 0x04050513, 0x00000097, 0x028080e7,\n"));
+    }
+}
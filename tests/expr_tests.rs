@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::expr::*;
+    use riscv_assembler::assembler::{AssemblerError, SymbolValue};
+    use std::collections::HashMap;
+
+    fn eval(expr: &str) -> Result<i64, AssemblerError> {
+        let mut symbols = HashMap::<String, u32>::new();
+        symbols.insert("start".to_string(), 0x100);
+        symbols.insert("end".to_string(), 0x140);
+
+        evaluate_constant(expr, 0x120, &symbols)
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("1 << 4 + 1"), Ok(32));
+        assert_eq!(eval("0xF0 | 0x0F & 0x3C"), Ok(0xFC));
+        assert_eq!(eval("6 ^ 3 | 8"), Ok(13));
+        assert_eq!(eval("-8 >> 1"), Ok(-4));
+        assert_eq!(eval("~0 & 0xFF"), Ok(0xFF));
+        assert_eq!(eval("-(2 + 3) * 2"), Ok(-10));
+        assert_eq!(eval("17 % 5 + 17 / 5"), Ok(5));
+        assert_eq!(eval("1 + 2 == 3 && 4 > 5 || !0"), Ok(1));
+        assert_eq!(eval("0b1010_1010 + 0o17"), Ok(185));
+    }
+
+    #[test]
+    fn test_character_literals() {
+        assert_eq!(eval("'a'"), Ok(97));
+        assert_eq!(eval("'\\n'"), Ok(10));
+        assert_eq!(eval("'\\''"), Ok(39));
+        assert_eq!(eval("'#' + 1"), Ok(36));
+        assert_eq!(eval("'0' + 9"), Ok(57));
+        assert!(eval("'ab'").is_err());
+    }
+
+    #[test]
+    fn test_relocation_operators() {
+        // %lo is sign-extended, so %hi rounds up when bit 11 is set
+        assert_eq!(eval("%hi(0x12345678)"), Ok(0x12345));
+        assert_eq!(eval("%lo(0x12345678)"), Ok(0x678));
+        assert_eq!(eval("%hi(0x12345FFF)"), Ok(0x12346));
+        assert_eq!(eval("%lo(0x12345FFF)"), Ok(-1));
+        assert_eq!(eval("%hi(0xFFFFF800)"), Ok(0));
+        assert_eq!(eval("%lo(0xFFFFF800)"), Ok(-2048));
+        assert_eq!(eval("%hi(-1)"), Ok(0));
+        assert_eq!(eval("%hi(end) + %lo(end + 4)"), Ok(0x144));
+
+        // %pcrel_hi is relative to the current address
+        assert_eq!(eval("%pcrel_hi(start + 0x1000)"), Ok(1));
+        assert_eq!(eval("%pcrel_hi(start)"), Ok(0));
+
+        // %pcrel_lo needs the label of an auipc with %pcrel_hi
+        assert!(eval("%pcrel_lo(start)").is_err());
+        assert!(eval("%pcrel_lo(4)").is_err());
+        assert!(eval("%hi 4").is_err());
+        assert!(eval("%high(4)").is_err());
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut symbols = HashMap::<String, u32>::new();
+        symbols.insert("start".to_string(), 0x100);
+        symbols.insert("end".to_string(), 0x140);
+
+        assert_eq!(eval("end - start"), Ok(0x40));
+        assert_eq!(eval("(end - start) / 4"), Ok(0x10));
+        assert_eq!(eval(". - start"), Ok(0x20));
+        assert_eq!(evaluate("start + 8", 0, &symbols), Ok(SymbolValue::Address(0x108)));
+        assert_eq!(evaluate("4 + end - 8", 0, &symbols), Ok(SymbolValue::Address(0x13C)));
+        assert_eq!(evaluate(".+8", 0x120, &symbols), Ok(SymbolValue::Address(0x128)));
+
+        // Addresses only support offsets and differences
+        assert!(eval("start").is_err());
+        assert!(evaluate("start * 2", 0, &symbols).is_err());
+        assert!(evaluate("start + end", 0, &symbols).is_err());
+        assert!(evaluate("-start", 0, &symbols).is_err());
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        assert_eq!(eval("missing + 1"), Err(AssemblerError::UndefinedLabel("missing".to_string())));
+        assert!(eval("").is_err());
+        assert!(eval("1 +").is_err());
+        assert!(eval("(1 + 2").is_err());
+        assert!(eval("1 2").is_err());
+        assert!(eval("4 / 0").is_err());
+        assert!(eval("3 @ 4").is_err());
+
+        // Local label references are symbols, other digit-letter mixes are invalid numbers
+        assert_eq!(eval("1f"), Err(AssemblerError::UndefinedLabel("1f".to_string())));
+        assert!(eval("1x").is_err());
+    }
+}
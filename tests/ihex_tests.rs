@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{Assembler, AssemblerError, MemoryLayout, Segment};
+    use riscv_assembler::assembler::ihex::{generate_ihex, parse_ihex};
+
+    const EXAMPLE: &str = "\
+:10010000214601360121470136007EFE09D2190140
+:100110002146017E17C20001FF5F16002148011928
+:10012000194E79234623965778239EDA3F01B2CAA7
+:100130003F0156702B5E712B722B732146013421C7
+:00000001FF
+";
+
+    fn segment(address: u32, data: Vec<u8>) -> Segment {
+        Segment { address, data }
+    }
+
+    #[test]
+    fn test_ihex_example() {
+        let (segments, entry) = parse_ihex(EXAMPLE).unwrap();
+
+        assert_eq!(entry, None);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].address, 0x100);
+        assert_eq!(segments[0].data.len(), 64);
+        assert_eq!(&segments[0].data[..4], &[0x21, 0x46, 0x01, 0x36]);
+
+        // The writer produces the same records
+        assert_eq!(generate_ihex(&segments, None), EXAMPLE);
+    }
+
+    #[test]
+    fn test_ihex_extended_address() {
+        let segments = vec![segment(0x0800_0000, vec![0x13, 0x00, 0x00, 0x00])];
+
+        assert_eq!(generate_ihex(&segments, Some(0x0800_0000)), "\
+:020000040800F2
+:0400000013000000E9
+:0400000508000000EF
+:00000001FF
+");
+    }
+
+    #[test]
+    fn test_ihex_round_trip() {
+        // Sparse segments, one crossing a 64 KiB boundary and one far above it
+        let segments = vec![
+            segment(0, (0..40).collect()),
+            segment(0xFFF8, (0..=255).collect()),
+            segment(0x8000_0000, vec![0xAA; 20])
+        ];
+
+        let ihex = generate_ihex(&segments, Some(0x10));
+        assert!(ihex.contains(":020000040001F9\n"));
+        assert!(ihex.contains(":020000048000"));
+        assert!(ihex.lines().all(|line| line.len() <= 11 + 32));
+
+        assert_eq!(parse_ihex(&ihex).unwrap(), (segments, Some(0x10)));
+    }
+
+    #[test]
+    fn test_ihex_errors() {
+        let errors = [
+            ":10010000214601360121470136007EFE09D2190141",
+            "10010000214601360121470136007EFE09D2190140",
+            ":10010000214601360121470136007EFE09D21901",
+            ":0001000G00",
+            ":00000006FA"
+        ];
+
+        for ihex in errors {
+            assert!(matches!(parse_ihex(ihex), Err(AssemblerError::ParseError(_))), "{}", ihex);
+        }
+    }
+
+    #[test]
+    fn test_ihex_layout() {
+        let mut assembler = Assembler::new();
+        assembler.set_layout(MemoryLayout::from_file("test_asm_files/layout/fpga.ld").unwrap());
+        let segments = assembler.assemble_segments("test_asm_files/layout/program.s").unwrap();
+
+        // ROM and RAM stay apart instead of filling the 2 GiB between them
+        let addresses: Vec<(u32, usize)> = segments.iter().map(|s| (s.address, s.data.len())).collect();
+        assert_eq!(addresses, vec![(0, 0x2D), (0x8000_0000, 4), (0x8000_0010, 4)]);
+
+        let ihex = generate_ihex(&segments, Some(assembler.entry_address().unwrap()));
+        assert_eq!(parse_ihex(&ihex).unwrap(), (segments, Some(0)));
+    }
+}
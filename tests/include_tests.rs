@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{Assembler, AssemblerError};
+
+    fn words(binary: &[u8]) -> Vec<u32> {
+        binary
+            .chunks(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_include() {
+        let mut assembler = Assembler::new();
+        assembler.add_include_path("test_asm_files/include/lib");
+        let binary = assembler.assemble("test_asm_files/include/main.s").unwrap();
+
+        assert_eq!(words(&binary[..16]), vec![
+            0x10000537,  // li a0, UART_BASE
+            0x04100293,  // store_byte a0, 'A': li t0, 65
+            0x00550023,  //                     sb t0, 0(a0)
+            0x00008067   // ret
+        ]);
+
+        // .incbin with an offset and length, then the rest of the file
+        assert_eq!(assembler.symbol_address("font"), Some(16));
+        assert_eq!(assembler.symbol_address("rest"), Some(20));
+        assert_eq!(&binary[16..], &[2, 3, 4, 5, 6, 7]);
+
+        // Nested includes are relative to the including file
+        assert_eq!(assembler.symbols()["NESTED"].value, 5);
+
+        // The macro header is only found through the include path
+        let mut assembler = Assembler::new();
+        assert!(matches!(
+            assembler.assemble("test_asm_files/include/main.s"),
+            Err(AssemblerError::IOError(_))
+        ));
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let mut assembler = Assembler::new();
+
+        let error = assembler.assemble("test_asm_files/include/cycle_a.s").unwrap_err();
+        assert!(matches!(error.root(), AssemblerError::InvalidDirective(e) if e.starts_with("Circular include")));
+
+        // Errors inside an included file carry the include chain
+        let error = assembler.assemble("test_asm_files/include/bad_include.s").unwrap_err();
+        match &error {
+            AssemblerError::Context(context, _) => assert_eq!(
+                context,
+                "in test_asm_files/include/common/broken.s:2, \
+                 included from test_asm_files/include/bad_include.s:1"
+            ),
+            _ => panic!("Expected an error with context but received {:?}", error)
+        }
+
+        assert!(matches!(
+            assembler.assemble("test_asm_files/include/missing_include.s"),
+            Err(AssemblerError::IOError(_))
+        ));
+        assert!(matches!(
+            assembler.assemble("test_asm_files/include/incbin_range.s"),
+            Err(AssemblerError::InvalidDirective(_))
+        ));
+    }
+}
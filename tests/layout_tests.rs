@@ -0,0 +1,192 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{Assembler, AssemblerError, MemoryLayout};
+    use riscv_assembler::linker::{Linker, LinkerError};
+
+    fn section_addresses(assembler: &Assembler) -> Vec<(&str, u32)> {
+        assembler.sections().iter().map(|s| (s.name.as_str(), s.address)).collect()
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        let layout = MemoryLayout::from_file("test_asm_files/layout/fpga.ld").unwrap();
+        let regions: Vec<(&str, u32, u64)> = layout.regions()
+            .iter()
+            .map(|r| (r.name.as_str(), r.origin, r.length))
+            .collect();
+
+        assert_eq!(regions, vec![("ROM", 0, 0x400), ("RAM", 0x8000_0000, 0x400)]);
+        assert_eq!(layout.entry(), Some("reset"));
+
+        let errors = [
+            "SECTIONS { .text : { *(.text) } > FLASH }",
+            "SECTIONS { .text : { main.o(.text) } }",
+            "SECTIONS { .text : AT(0x100) { *(.text) } }",
+            "SECTIONS { _end = . }",
+            "MEMORY { ROM : ORIGIN = 0 }",
+            "INCLUDE other.ld"
+        ];
+
+        for script in errors {
+            assert!(
+                matches!(MemoryLayout::parse(script), Err(AssemblerError::LayoutError(_))),
+                "{}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn test_layout_regions() {
+        let mut assembler = Assembler::new();
+        assembler.set_layout(MemoryLayout::from_file("test_asm_files/layout/fpga.ld").unwrap());
+        assembler.assemble_executable("test_asm_files/layout/program.s").unwrap();
+
+        // .text.init comes first in ROM, .data is padded to 16 bytes in RAM
+        // and .sdata, which the script doesn't mention, follows .data and moves .bss up
+        assert_eq!(section_addresses(&assembler), vec![
+            (".text", 0x10),
+            (".text.init", 0),
+            (".rodata", 0x28),
+            (".data", 0x8000_0000),
+            (".bss", 0x8000_0014),
+            (".sdata", 0x8000_0010)
+        ]);
+
+        let symbol = |name: &str| assembler.symbols().get(name).map(|s| s.value);
+        assert_eq!(symbol("_data_start"), Some(0x8000_0000));
+        assert_eq!(symbol("_bss_end"), Some(0x8000_0054));
+        assert_eq!(symbol("_heap_start"), Some(0x8000_0054));
+        assert_eq!(symbol("_stack_top"), Some(0x8000_0400));
+
+        // PROVIDE doesn't replace the label of the source
+        assert!(assembler.symbols()["reset"].is_label());
+
+        // la sp, _stack_top
+        let segments = assembler.assemble_segments("test_asm_files/layout/program.s").unwrap();
+        assert_eq!(segments[0].address, 0);
+        assert_eq!(&segments[0].data[..8], &[0x17, 0x01, 0x00, 0x80, 0x13, 0x01, 0x01, 0x40]);
+
+        // ROM and RAM are 2 GiB apart, too far for a flat binary
+        assert!(matches!(
+            assembler.assemble("test_asm_files/layout/program.s"),
+            Err(AssemblerError::LayoutError(_))
+        ));
+    }
+
+    #[test]
+    fn test_layout_location() {
+        let mut assembler = Assembler::new();
+        assembler.set_layout(MemoryLayout::from_file("test_asm_files/layout/flat.ld").unwrap());
+        let image = assembler.assemble("test_asm_files/layout/program.s").unwrap();
+
+        assert_eq!(section_addresses(&assembler), vec![
+            (".text", 0x1000),
+            (".text.init", 0x1018),
+            (".rodata", 0x1028),
+            (".data", 0x1030),
+            (".bss", 0x2000),
+            (".sdata", 0x1034)
+        ]);
+        assert_eq!(assembler.symbols()["_end"].value, 0x2040);
+
+        // The image starts at the first section instead of 0
+        assert_eq!(image.len(), 0x38);
+    }
+
+    #[test]
+    fn test_layout_overflow() {
+        let mut assembler = Assembler::new();
+        assembler.set_layout(MemoryLayout::from_file("test_asm_files/layout/overflow.ld").unwrap());
+
+        let error = assembler.assemble("test_asm_files/layout/program.s").unwrap_err();
+        assert_eq!(error, AssemblerError::LayoutError(".text overflows ROM by 24 bytes".to_string()));
+
+        // Sections can't run past the end of the 32-bit address space
+        assembler.set_layout(MemoryLayout::parse("SECTIONS { . = 0xFFFFFFF0; .text : { *(.text*) } }").unwrap());
+        assert_eq!(
+            assembler.assemble("test_asm_files/layout/program.s").unwrap_err(),
+            AssemblerError::LayoutError(".text runs past the end of the address space".to_string())
+        );
+
+        // A symbol of the source can't be redefined by the script
+        assembler.set_layout(MemoryLayout::parse("SECTIONS { main = 0; }").unwrap());
+        assert!(matches!(
+            assembler.assemble("test_asm_files/layout/program.s"),
+            Err(AssemblerError::DuplicateSymbol(_))
+        ));
+
+        // Nor from the base address
+        let mut assembler = Assembler::new();
+        assembler.set_base_address(0xFFFF_FFF0);
+        assert_eq!(
+            assembler.assemble("test_asm_files/layout/program.s").unwrap_err(),
+            AssemblerError::LayoutError(".text runs past the end of the address space".to_string())
+        );
+    }
+
+    #[test]
+    fn test_layout_provided_entry() {
+        // ENTRY(reset) names the symbol PROVIDE defines when the source has no reset label
+        let mut assembler = Assembler::new();
+        assembler.set_layout(MemoryLayout::from_file("test_asm_files/layout/fpga.ld").unwrap());
+        assembler.assemble_executable("test_asm_files/layout/provided_entry.s").unwrap();
+
+        assert_eq!(assembler.entry_address(), Ok(0));
+        assert_eq!(assembler.symbol_address("_stack_top"), Some(0x8000_0400));
+
+        let mut linker = Linker::new();
+        linker.set_layout(MemoryLayout::from_file("test_asm_files/layout/fpga.ld").unwrap());
+        linker.add_source(&mut Assembler::new(), "test_asm_files/layout/provided_entry.s").unwrap();
+
+        let program = linker.link().unwrap();
+        assert_eq!(program.entry(), 0);
+        assert_eq!(program.symbol_address("_stack_top"), Some(0x8000_0400));
+    }
+
+    #[test]
+    fn test_link_layout() {
+        let mut assembler = Assembler::new();
+        let mut linker = Linker::new();
+        linker.set_layout(MemoryLayout::from_file("test_asm_files/layout/fpga.ld").unwrap());
+        linker.add_source(&mut assembler, "test_asm_files/layout/program.s").unwrap();
+        linker.add_source(&mut assembler, "test_asm_files/linker/lib.s").unwrap();
+
+        let program = linker.link().unwrap();
+        let sections: Vec<(&str, u32)> = program.sections().iter().map(|s| (s.name.as_str(), s.address)).collect();
+
+        assert_eq!(sections, vec![
+            (".text", 0x10),
+            (".text.init", 0),
+            (".rodata", 0x38),
+            (".data", 0x8000_0000),
+            (".bss", 0x8000_0014),
+            (".sdata", 0x8000_0010)
+        ]);
+        assert_eq!(program.entry(), 0);
+        assert_eq!(program.symbol_address("print"), Some(0x28));
+
+        // Symbols of the script resolve references of the inputs
+        let segments = program.segments();
+        assert_eq!(&segments[0].data[..4], &[0x17, 0x01, 0x00, 0x80]);
+        assert!(matches!(program.image(), Err(LinkerError::Layout(_))));
+
+        // The script can't define a symbol an input defines
+        let mut linker = Linker::new();
+        linker.set_layout(MemoryLayout::parse("SECTIONS { print = 0; }").unwrap());
+        linker.add_source(&mut assembler, "test_asm_files/linker/lib.s").unwrap();
+        assert_eq!(
+            linker.link().err(),
+            Some(LinkerError::DuplicateSymbol(
+                "print".to_string(),
+                "test_asm_files/linker/lib.s".to_string(),
+                "the memory layout".to_string()
+            ))
+        );
+
+        let mut linker = Linker::new();
+        linker.set_layout(MemoryLayout::from_file("test_asm_files/layout/overflow.ld").unwrap());
+        linker.add_source(&mut assembler, "test_asm_files/layout/program.s").unwrap();
+        assert!(matches!(linker.link(), Err(LinkerError::Layout(AssemblerError::LayoutError(_)))));
+    }
+}
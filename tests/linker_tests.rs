@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{Assembler, Xlen};
+    use riscv_assembler::linker::{Linker, LinkerError};
+
+    const MAIN: &str = "test_asm_files/linker/main.s";
+    const LIB: &str = "test_asm_files/linker/lib.s";
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn link(paths: &[&str], base_address: u32) -> Result<Vec<u8>, LinkerError> {
+        let mut assembler = Assembler::new();
+        let mut linker = Linker::new();
+        linker.set_base_address(base_address);
+
+        for path in paths {
+            linker.add_source(&mut assembler, path)?;
+        }
+
+        linker.link()?.image()
+    }
+
+    #[test]
+    fn test_link_matches_single_file() {
+        // Linking the two files gives the same image as assembling them as one
+        for base_address in [0, 0x8000_0000] {
+            let mut assembler = Assembler::new();
+            assembler.set_base_address(base_address);
+            let expected = assembler.assemble("test_asm_files/linker/combined.s").unwrap();
+
+            assert_eq!(link(&[MAIN, LIB], base_address).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_link_objects() {
+        // Object files written by the assembler link like the sources they came from
+        let mut assembler = Assembler::new();
+        let mut linker = Linker::new();
+        linker.add_object("main.o", &assembler.assemble_object(MAIN).unwrap()).unwrap();
+        linker.add_source(&mut assembler, LIB).unwrap();
+
+        let program = linker.link().unwrap();
+        assert_eq!(program.image().unwrap(), link(&[MAIN, LIB], 0).unwrap());
+        assert_eq!(program.entry(), 0);
+
+        // .word print, counter + 4
+        let image = program.image().unwrap();
+        let pointers = program.sections().iter().find(|s| s.name == ".data").unwrap().address as usize;
+        assert_eq!(program.symbol_address("print"), Some(0x30));
+        assert_eq!(u32_at(&image, pointers), 0x30);
+        assert_eq!(u32_at(&image, pointers + 4), program.symbol_address("counter").unwrap() + 4);
+
+        // RV64 objects
+        assembler.set_xlen(Xlen::Rv64);
+        let mut linker = Linker::new();
+        linker.add_source(&mut assembler, MAIN).unwrap();
+        linker.add_source(&mut assembler, LIB).unwrap();
+        assert_eq!(linker.link().unwrap().image().unwrap(), link(&[MAIN, LIB], 0).unwrap());
+
+        // ELF32 and ELF64 don't mix
+        let mut linker = Linker::new();
+        linker.add_source(&mut assembler, MAIN).unwrap();
+        assembler.set_xlen(Xlen::Rv32);
+        assert!(matches!(linker.add_source(&mut assembler, LIB), Err(LinkerError::InvalidObject(..))));
+        assert!(matches!(linker.add_object("lib.s", b"not an object"), Err(LinkerError::InvalidObject(..))));
+    }
+
+    #[test]
+    fn test_link_executable() {
+        let mut assembler = Assembler::new();
+        let mut linker = Linker::new();
+        linker.set_base_address(0x8000_0000);
+        linker.add_source(&mut assembler, LIB).unwrap();
+        linker.add_source(&mut assembler, MAIN).unwrap();
+
+        // Entered at _start, which comes after lib.s in .text
+        let program = linker.link_executable().unwrap();
+        assert_eq!(program.entry(), 0x8000_0010);
+
+        let elf = program.to_elf();
+        assert_eq!(&elf[..5], b"\x7FELF\x01");
+        assert_eq!(u32_at(&elf, 24), 0x8000_0010);
+        assert_eq!(&elf[0x1000..0x1040], &program.image().unwrap()[..0x40]);
+
+        // Text, read-only data and data each get their own page and PT_LOAD
+        let headers = u32_at(&elf, 28) as usize;
+        let segments: Vec<(u32, u32)> = (0..u16::from_le_bytes([elf[44], elf[45]]) as usize)
+            .map(|idx| (u32_at(&elf, headers + idx * 32 + 8), u32_at(&elf, headers + idx * 32 + 24)))
+            .collect();
+        assert_eq!(segments, vec![(0x8000_0000, 5), (0x8000_1000, 4), (0x8000_2000, 6)]);
+
+        linker.set_entry("print");
+        assert_eq!(linker.link().unwrap().entry(), 0x8000_0000);
+
+        linker.set_entry("missing");
+        assert!(matches!(linker.link(), Err(LinkerError::UndefinedEntry(_))));
+    }
+
+    #[test]
+    fn test_link_weak() {
+        // The strong exit of lib.s wins over the weak one, the undefined weak handler is 0
+        let mut assembler = Assembler::new();
+        let mut linker = Linker::new();
+        linker.add_source(&mut assembler, "test_asm_files/linker/weak.s").unwrap();
+        linker.add_source(&mut assembler, LIB).unwrap();
+
+        let program = linker.link().unwrap();
+        let image = program.image().unwrap();
+        let handlers = program.sections().iter().find(|s| s.name == ".data").unwrap().address as usize;
+
+        assert_eq!(u32_at(&image, handlers), program.symbol_address("exit").unwrap());
+        assert_eq!(program.symbol_address("exit"), Some(0x10 + 0x8));
+        assert_eq!(u32_at(&image, handlers + 4), 0);
+    }
+
+    #[test]
+    fn test_link_errors() {
+        assert_eq!(
+            link(&[MAIN], 0),
+            Err(LinkerError::UndefinedSymbols(vec![
+                ("greeting".to_string(), MAIN.to_string()),
+                ("print".to_string(), MAIN.to_string()),
+                ("counter".to_string(), MAIN.to_string()),
+                ("buffer".to_string(), MAIN.to_string()),
+                ("exit".to_string(), MAIN.to_string())
+            ]))
+        );
+
+        assert_eq!(
+            link(&[MAIN, LIB, "test_asm_files/linker/duplicate.s"], 0),
+            Err(LinkerError::DuplicateSymbol(
+                "print".to_string(),
+                LIB.to_string(),
+                "test_asm_files/linker/duplicate.s".to_string()
+            ))
+        );
+
+        assert!(matches!(
+            link(&["test_asm_files/linker/far.s", "test_asm_files/linker/far_away.s"], 0),
+            Err(LinkerError::RelocationError(..))
+        ));
+
+        assert!(matches!(
+            link(&["test_asm_files/linker/missing.s"], 0),
+            Err(LinkerError::Assembler(..))
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::Assembler;
+
+    fn listing(path: &str) -> String {
+        let mut assembler = Assembler::new();
+        assembler.set_listing(true);
+        assembler.assemble(path).unwrap();
+        assembler.listing().unwrap()
+    }
+
+    #[test]
+    fn test_listing_expansions() {
+        let listing = listing("test_asm_files/listing/program.s");
+
+        // One row per word of a pseudo-instruction
+        assert!(listing.contains(concat!(
+            "   10  00000000                    main:\n",
+            "   11  00000000  00012537              li a0, 0x12345\n",
+            "       00000004  34550513\n",
+            "   12  00000008  00000097              call helper\n",
+            "       0000000c  018080e7\n"
+        )));
+
+        // Data in bytes, elided after 4 rows
+        assert!(listing.contains(concat!(
+            "   20  00000028  4c697374696e6720  message: .asciz \"Listing test: more than 32 bytes long!\"\n",
+            "       00000030  746573743a206d6f\n",
+            "       00000038  7265207468616e20\n",
+            "       00000040  3332206279746573\n",
+            "                 ...\n",
+            "   21  0000004f  00                .align 2\n",
+            "   22  00000050  78563412          value:   .word 0x12345678\n"
+        )));
+    }
+
+    #[test]
+    fn test_listing_context() {
+        let listing = listing("test_asm_files/listing/program.s");
+
+        // Lines of the macro body under the invocation, numbered in the macro definition
+        assert!(listing.contains(concat!(
+            "   13                                  push ra\n",
+            "; macro push from test_asm_files/listing/program.s:4, invoked at test_asm_files/listing/program.s:13\n",
+            "    5  00000010  ffc10113              addi sp, sp, -4\n",
+            "    6  00000014  00112023              sw ra, 0(sp)\n",
+            "; test_asm_files/listing/program.s\n",
+            "   14                              .rept 2\n"
+        )));
+
+        assert!(listing.contains(concat!(
+            "; repetition 2 of 2 at test_asm_files/listing/program.s:14\n",
+            "   15  0000001c  00000013              nop\n"
+        )));
+
+        assert!(listing.contains(concat!(
+            "; test_asm_files/listing/helper.s, included from test_asm_files/listing/program.s:17\n",
+            "    1  00000020                    helper:\n",
+            "    2  00000020  00350513              addi a0, a0, COUNT\n"
+        )));
+
+        // Labels of a macro definition get no address until the macro is invoked
+        assert!(listing.contains(concat!(
+            "   24                              .macro countdown reg\n",
+            "   25                              1:  addi \\reg, \\reg, -1\n"
+        )));
+    }
+
+    #[test]
+    fn test_listing_symbols() {
+        let listing = listing("test_asm_files/listing/program.s");
+        let (_, symbols) = listing.split_once("\nSymbols\n").unwrap();
+
+        assert_eq!(symbols, "\
+00000000  .text      main
+00000003  *ABS*      COUNT
+00000020  .text      helper
+00000028  .data      message
+00000050  .data      value
+");
+    }
+
+    #[test]
+    fn test_listing_image() {
+        // Recording the listing doesn't change the machine code
+        let mut assembler = Assembler::new();
+        let image = assembler.assemble("test_asm_files/listing/program.s").unwrap();
+        assert_eq!(assembler.listing(), None);
+
+        assembler.set_listing(true);
+        assert_eq!(assembler.assemble("test_asm_files/listing/program.s").unwrap(), image);
+    }
+
+    #[test]
+    fn test_line_map() {
+        let mut assembler = Assembler::new();
+        assembler.set_listing(true);
+        assembler.assemble("test_asm_files/listing/program.s").unwrap();
+        let lines = assembler.line_map().unwrap();
+        let line = |address: u32| {
+            let line = lines.iter().find(|line| line.address == address).unwrap();
+            (line.size, line.file.as_str(), line.line, line.source.trim())
+        };
+
+        // Pseudo-instructions cover all of their words, expansions point into the macro and the included file
+        assert_eq!(line(0), (8, "test_asm_files/listing/program.s", 11, "li a0, 0x12345"));
+        assert_eq!(line(20), (4, "test_asm_files/listing/program.s", 6, "sw ra, 0(sp)"));
+        assert_eq!(line(32), (4, "test_asm_files/listing/helper.s", 2, "addi a0, a0, COUNT"));
+        assert_eq!(line(40).0, 39);
+
+        // Only lines that emitted something, in address order
+        assert_eq!(lines.len(), 11);
+        assert!(lines.windows(2).all(|pair| pair[0].address < pair[1].address));
+
+        // Without a listing there is no line map
+        let mut assembler = Assembler::new();
+        assembler.assemble("test_asm_files/listing/program.s").unwrap();
+        assert!(assembler.line_map().is_none());
+    }
+}
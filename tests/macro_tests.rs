@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::macros::*;
+    use riscv_assembler::assembler::{Assembler, AssemblerError};
+
+    fn words(binary: &[u8]) -> Vec<u32> {
+        binary
+            .chunks(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+
+    fn define(header: &str, body: &[&str]) -> Macro {
+        let (name, params) = parse_macro_header(header).unwrap();
+
+        Macro {
+            name,
+            params,
+            body: body.iter().map(|line| line.to_string()).collect(),
+            file: "test.s".to_string(),
+            line: 1
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        let store = define("store reg, offset=0", &["sw \\reg, \\offset(sp)"]);
+        assert_eq!(store.expand("a0, 8", 0), Ok(vec!["sw a0, 8(sp)".to_string()]));
+        assert_eq!(store.expand("a0", 0), Ok(vec!["sw a0, 0(sp)".to_string()]));
+        assert_eq!(store.expand("offset=4, reg=t0", 0), Ok(vec!["sw t0, 4(sp)".to_string()]));
+        assert!(store.expand("a0, 4, 8", 0).is_err());
+
+        // \@ counts expansions, \() ends a parameter name
+        let label = define("label, name", &["\\name\\()_\\@:", ".ascii \"\\n\""]);
+        assert_eq!(label.expand("done", 7), Ok(vec!["done_7:".to_string(), ".ascii \"\\n\"".to_string()]));
+
+        let list = define("list size, values:vararg", &[".\\size \\values"]);
+        assert_eq!(list.expand("word, 1, 2, 3", 0), Ok(vec![".word 1, 2, 3".to_string()]));
+
+        let required = define("required value:req", &[".word \\value"]);
+        assert!(required.expand("", 0).is_err());
+
+        // Invalid headers
+        assert!(parse_macro_header("").is_err());
+        assert!(parse_macro_header("bad rest:vararg, last").is_err());
+        assert!(parse_macro_header("bad value:optional").is_err());
+    }
+
+    #[test]
+    fn test_macros() {
+        let mut assembler = Assembler::new();
+        let binary = assembler.assemble("test_asm_files/macros/macros.s").unwrap();
+
+        assert_eq!(words(&binary), vec![
+            0xFFC10113,  // push ra: addi sp, sp, -4
+            0x00112023,  //          sw ra, 0(sp)
+            0x100002B7,  // write_mmio UART, 65: lui t0, 0x10000
+            0x04100313,  //                      li t1, 65
+            0x0062A023,  //                      sw t1, 0(t0)
+            0x100002B7,  // write_mmio UART, offset=4: lui t0, 0x10000
+            0x00000313,  //                            li t1, 0
+            0x0062A223,  //                            sw t1, 4(t0)
+            0xFFF50513,  // wait a0: addi a0, a0, -1
+            0xFE051EE3,  //          bnez a0, loop3
+            0xFFF58593,  // wait a1: addi a1, a1, -1
+            0xFE059EE3,  //          bnez a1, loop4
+            0x00012083,  // pop ra: lw ra, 0(sp)
+            0x00410113,  //         addi sp, sp, 4
+            0x00008067,  // ret
+            1, 2, 3, 4   // words 1, 2, 3, 4
+        ]);
+
+        assert_eq!(assembler.symbol_address("loop3"), Some(32));
+        assert_eq!(assembler.symbol_address("loop4"), Some(40));
+    }
+
+    #[test]
+    fn test_macro_errors() {
+        let mut assembler = Assembler::new();
+
+        // Errors point at the line inside the macro and at the invocation
+        let error = assembler.assemble("test_asm_files/macros/macro_error.s").unwrap_err();
+        match &error {
+            AssemblerError::Context(context, _) => assert_eq!(
+                context,
+                "in macro load_pair at test_asm_files/macros/macro_error.s:2, \
+                 invoked at test_asm_files/macros/macro_error.s:7"
+            ),
+            _ => panic!("Expected an error with context but received {:?}", error)
+        }
+
+        let error = assembler.assemble("test_asm_files/macros/macro_recursive.s").unwrap_err();
+        assert!(matches!(error.root(), AssemblerError::InvalidDirective(_)));
+
+        assert!(matches!(
+            assembler.assemble("test_asm_files/macros/macro_unterminated.s"),
+            Err(AssemblerError::InvalidDirective(_))
+        ));
+    }
+}
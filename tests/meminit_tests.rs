@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{AssemblerError, Segment};
+    use riscv_assembler::assembler::meminit::*;
+
+    // addi a0, x0, 8
+    // addi a1, x0, 8
+    fn program() -> Vec<Segment> {
+        vec![Segment { address: 0x1000, data: vec![0x13, 0x05, 0x80, 0x00, 0x93, 0x05, 0x80, 0x00] }]
+    }
+
+    // A word at the origin, two bytes at 0x0A and a word far above them
+    fn sparse() -> Vec<Segment> {
+        vec![
+            Segment { address: 0, data: vec![0x13, 0x05, 0x80, 0x00] },
+            Segment { address: 0x0A, data: vec![0xAA, 0xBB] },
+            Segment { address: 0x40, data: vec![0x01, 0x02, 0x03, 0x04] }
+        ]
+    }
+
+    #[test]
+    fn test_readmemh() {
+        assert_eq!(generate_readmemh(&program(), 0x1000, 4, false).unwrap(), "00800513\n00800593\n");
+        assert_eq!(generate_readmemh(&program(), 0x1000, 4, true).unwrap(), "@0\n00800513\n00800593\n");
+        assert_eq!(generate_readmemh(&program(), 0x1000, 2, false).unwrap(), "0513\n0080\n0593\n0080\n");
+        assert_eq!(generate_readmemh(&program(), 0x1000, 8, false).unwrap(), "0080059300800513\n");
+
+        // Without addresses the gaps are filled with zeros
+        assert_eq!(
+            generate_readmemh(&sparse(), 0, 4, false).unwrap().lines().collect::<Vec<_>>(),
+            [&["00800513", "00000000", "bbaa0000"][..], &["00000000"; 13], &["04030201"]].concat()
+        );
+
+        // With addresses every run starts with the index of its first word
+        assert_eq!(generate_readmemh(&sparse(), 0, 4, true).unwrap(), "@0\n00800513\n@2\nbbaa0000\n@10\n04030201\n");
+    }
+
+    #[test]
+    fn test_readmemb() {
+        assert_eq!(
+            generate_readmemb(&program(), 0x1000, 4, true).unwrap(),
+            "@0\n00000000100000000000010100010011\n00000000100000000000010110010011\n"
+        );
+        assert_eq!(generate_readmemb(&sparse()[1..2], 0, 1, true).unwrap(), "@a\n10101010\n10111011\n");
+    }
+
+    #[test]
+    fn test_coe() {
+        assert_eq!(generate_coe(&program(), 0x1000, 4).unwrap(), "\
+memory_initialization_radix=16;
+memory_initialization_vector=
+00800513,
+00800593;
+");
+        assert_eq!(generate_coe(&[], 0, 4).unwrap(), "memory_initialization_radix=16;\nmemory_initialization_vector=\n;\n");
+    }
+
+    #[test]
+    fn test_mif() {
+        assert_eq!(generate_mif(&program(), 0x1000, 4).unwrap(), "\
+DEPTH = 2;
+WIDTH = 32;
+ADDRESS_RADIX = HEX;
+DATA_RADIX = HEX;
+CONTENT
+BEGIN
+0 : 00800513;
+1 : 00800593;
+END;
+");
+        let mif = generate_mif(&sparse(), 0, 4).unwrap();
+        assert!(mif.starts_with("DEPTH = 17;\n"));
+        assert!(mif.contains("\n2 : bbaa0000;\n3 : 00000000;\n"));
+        assert!(mif.ends_with("\n10 : 04030201;\nEND;\n"));
+    }
+
+    #[test]
+    fn test_distant_segments() {
+        // ROM at 0 and RAM at 0x8000_0000 can only be written with addresses
+        let segments = vec![
+            Segment { address: 0, data: vec![0x13, 0x05, 0x80, 0x00] },
+            Segment { address: 0x8000_0000, data: vec![0x01, 0x02, 0x03, 0x04] }
+        ];
+
+        assert!(matches!(generate_coe(&segments, 0, 4), Err(AssemblerError::LayoutError(_))));
+        assert!(matches!(generate_mif(&segments, 0, 4), Err(AssemblerError::LayoutError(_))));
+        assert!(matches!(generate_readmemh(&segments, 0, 4, false), Err(AssemblerError::LayoutError(_))));
+        assert_eq!(generate_readmemh(&segments, 0, 4, true).unwrap(), "@0\n00800513\n@20000000\n04030201\n");
+    }
+
+    #[test]
+    fn test_byte_lanes() {
+        let lanes = split_byte_lanes(&program(), 0x1000, 4);
+        let files: Vec<String> = lanes.iter().map(|lane| generate_readmemh(lane, 0x1000, 1, false).unwrap()).collect();
+        assert_eq!(files, ["13\n93\n", "05\n05\n", "80\n80\n", "00\n00\n"]);
+
+        // Lanes keep the word index of sparse images
+        let lanes = split_byte_lanes(&sparse(), 0, 4);
+        assert_eq!(generate_readmemh(&lanes[0], 0, 1, true).unwrap(), "@0\n13\n@10\n01\n");
+        assert_eq!(generate_readmemh(&lanes[2], 0, 1, true).unwrap(), "@0\n80\n@2\naa\n@10\n03\n");
+        assert_eq!(
+            generate_readmemh(&lanes[3], 0, 1, false).unwrap().lines().collect::<Vec<_>>(),
+            [&["00", "00", "bb"][..], &["00"; 13], &["04"]].concat()
+        );
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::Assembler;
+    use riscv_assembler::disassembler::objdump::{dump_binary, dump_elf};
+
+    const COMBINED: &str = "test_asm_files/linker/combined.s";
+
+    #[test]
+    fn test_dump_executable() {
+        let elf = Assembler::new().assemble_executable(COMBINED).unwrap();
+        let dump = dump_elf("combined.elf", &elf).unwrap();
+
+        assert!(dump.starts_with("\ncombined.elf:     file format elf32-littleriscv\n\n\nDisassembly of section .text:\n"));
+        assert!(dump.contains("\n00000000 <_start>:\n       0:\t00001517\tauipc a0, 0x1000\n"));
+        assert!(dump.contains("      28:\t00030863\tbeq t1, zero, exit # 0x38\n"));
+        assert!(dump.contains("\n00000030 <print>:\n      30:\t00050293\taddi t0, a0, 0\n"));
+        assert!(dump.contains("\n00000038 <exit>:\n      38:\t05d00893\taddi a7, zero, 93\n      3c:\t00000073\tecall\n"));
+
+        // Only executable sections are listed
+        assert!(!dump.contains(".rodata"));
+        assert!(!dump.contains("greeting>"));
+    }
+
+    #[test]
+    fn test_dump_object() {
+        let object = Assembler::new().assemble_object("test_asm_files/linker/main.s").unwrap();
+        let dump = dump_elf("main.o", &object).unwrap();
+
+        // Global symbols win over local labels at the same address
+        assert!(dump.contains("\n00000000 <_start>:\n"));
+        assert!(dump.contains("\n00000020 <.Lpcrel_hi1>:\n"));
+    }
+
+    #[test]
+    fn test_dump_binary() {
+        let mut image = Assembler::new().assemble(COMBINED).unwrap();
+        image.truncate(0x40);
+        image.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x12, 0x34]);
+
+        let dump = dump_binary("combined.bin", &image, 0x100);
+
+        assert!(dump.starts_with("\ncombined.bin:     file format binary\n\n\nDisassembly of section .data:\n\n00000100 <.data>:\n"));
+        assert!(dump.contains("     100:\t00000517\tauipc a0, 0x0\n"));
+        assert!(dump.contains("     128:\t00030863\tbeq t1, zero, 16 # 0x138\n"));
+        assert!(dump.contains("     140:\tffffffff\t.word 0xffffffff\n"));
+        assert!(dump.ends_with("     144:\t3412    \t.byte 0x12, 0x34\n"));
+    }
+
+    #[test]
+    fn test_dump_rejects_other_files() {
+        assert!(dump_elf("empty", &[]).is_err());
+        assert!(dump_elf("text", b"\x7FELF but not really").is_err());
+    }
+}
@@ -65,9 +65,25 @@ mod tests {
         };
 
         assert_eq!(
-            parser.parse_i_type(&addi, &["x4", "x5", "16"]),
+            parser.parse_i_type(&addi, &["x4", "x5", "16"], 0, &HashMap::new()),
             Ok(0b0000_0001_0000_0010_1000_0010_0001_0011)
         );
+
+        // Shifts keep funct7 above the shift amount
+        let srai = InstructionFormat {
+            fmt: InstructionType::I,
+            opcode: 0b001_0011,
+            funct3: Some(0b101),
+            funct7: Some(0b010_0000)
+        };
+
+        assert_eq!(parser.parse_i_type(&srai, &["a0", "a0", "3"], 0, &HashMap::new()), Ok(0x40355513));
+        assert!(parser.parse_i_type(&srai, &["a0", "a0", "64"], 0, &HashMap::new()).is_err());
+
+        // Immediates are signed 12-bit values
+        assert!(parser.parse_i_type(&addi, &["a0", "a0", "-2048"], 0, &HashMap::new()).is_ok());
+        assert!(parser.parse_i_type(&addi, &["a0", "a0", "4096"], 0, &HashMap::new()).is_err());
+        assert!(parser.parse_i_type(&addi, &["a0", "2048(sp)"], 0, &HashMap::new()).is_err());
     }
 
     #[test]
@@ -81,9 +97,11 @@ mod tests {
         };
 
         assert_eq!(
-            parser.parse_s_type(&lb, &["x6", "0x111(x5)"]),
+            parser.parse_s_type(&lb, &["x6", "0x111(x5)"], 0, &HashMap::new()),
             Ok(0b0001_0000_0110_0010_1000_1000_1010_0011)
-        )
+        );
+
+        assert!(parser.parse_s_type(&lb, &["x6", "-2049(x5)"], 0, &HashMap::new()).is_err());
     }
 
     #[test]
@@ -103,9 +121,12 @@ mod tests {
 
         let mut current_address = 0;
 
+        // Offsets are even and fit in 13 bits
+        assert!(parser.parse_b_type(&beq, &["x5", "x6", "0x123"], current_address, &symbols).is_err());
+        assert!(parser.parse_b_type(&beq, &["x5", "x6", "0x1000"], current_address, &symbols).is_err());
         assert_eq!(
-            parser.parse_b_type(&beq, &["x5", "x6", "0x123"], current_address, &symbols),
-            Ok(0b0001_0010_0110_0010_1000_0001_0110_0011)
+            parser.parse_b_type(&beq, &["x5", "x6", "-0x1000"], current_address, &symbols),
+            Ok(0x80628063)
         );
 
         assert_eq!(
@@ -140,12 +161,12 @@ mod tests {
         symbols.insert("test".to_string(), 10000);
 
         assert_eq!(
-            parser.parse_u_type(&lui, &["x4", "0x12345678"], &symbols),
+            parser.parse_u_type(&lui, &["x4", "0x12345678"], 0, &symbols),
             Ok(0b0001_0010_0011_0100_0101_0010_0011_0111)
         );
 
         assert_eq!(
-            parser.parse_u_type(&lui, &["x15", "test"], &symbols),
+            parser.parse_u_type(&lui, &["x15", "test"], 0, &symbols),
             Ok(0x27b7)
         );
     }
@@ -165,10 +186,10 @@ mod tests {
 
         let mut current_address = 8;
 
-        assert_eq!(
-            parser.parse_j_type(&jal, &["x4", "0x7FFFFFFF"], current_address, &symbols),
-            Ok(0b1111_1111_1111_1111_1111_0010_0110_1111)
-        );
+        // Offsets are even and fit in 21 bits
+        assert!(parser.parse_j_type(&jal, &["x4", "0x7FFFFFFF"], current_address, &symbols).is_err());
+        assert!(parser.parse_j_type(&jal, &["x4", "0x100000"], current_address, &symbols).is_err());
+        assert!(parser.parse_j_type(&jal, &["x4", "3"], current_address, &symbols).is_err());
 
         assert_eq!(
             parser.parse_j_type(&jal, &["x5", "12"], current_address, &symbols),
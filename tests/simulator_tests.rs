@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{Assembler, Xlen};
+    use riscv_assembler::simulator::{Simulator, SimulatorError, Stop, STACK_TOP};
+
+    // Registers by ABI name
+    const SP: usize = 2;
+    const T0: usize = 5;
+    const S0: usize = 8;
+    const S1: usize = 9;
+    const A0: usize = 10;
+    const A1: usize = 11;
+    const A2: usize = 12;
+    const A3: usize = 13;
+    const A4: usize = 14;
+    const A5: usize = 15;
+    const A6: usize = 16;
+    const A7: usize = 17;
+    const S2: usize = 18;
+
+    fn run(path: &str, xlen: Xlen) -> Simulator {
+        let mut assembler = Assembler::new();
+        assembler.set_xlen(xlen);
+        assembler.set_base_address(0x1000);
+        let image = assembler.assemble(path).unwrap();
+
+        let mut simulator = Simulator::new(xlen);
+        simulator.load(0x1000, &image);
+        simulator.set_pc(assembler.entry_address().unwrap() as u64);
+        assert_eq!(simulator.run(10_000), Ok(Stop::Ebreak));
+        simulator
+    }
+
+    #[test]
+    fn test_factorial() {
+        let simulator = run("test_asm_files/simulator/factorial.s", Xlen::Rv32);
+
+        assert_eq!(simulator.register(A0), 3628800);
+        assert_eq!(simulator.register(SP), STACK_TOP);
+        assert_eq!(simulator.pc(), 0x100c);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let simulator = run("test_asm_files/simulator/arithmetic.s", Xlen::Rv32);
+        let register = |number: usize| simulator.register(number) as u32;
+
+        assert_eq!(register(S0), -3i32 as u32);
+        assert_eq!(register(S1), -1i32 as u32);
+        assert_eq!(register(S2), 0xffffffff);
+        assert_eq!(register(19), -7i32 as u32);
+        assert_eq!(register(20), 0x80000000);
+        assert_eq!(register(21), 0);
+        assert_eq!(register(22), 0x40000000);
+        assert_eq!(register(23), 0xfffffffe);
+        assert_eq!(register(24), 0xffffffff);
+        assert_eq!(register(25), 0x3fffffff);
+        assert_eq!(register(26), 0xffffffff);
+        assert_eq!(register(27), 1);
+        assert_eq!(register(A0), 1);
+        assert_eq!(register(A1), 0xffffffff);
+
+        // RV32 values are kept sign-extended
+        assert_eq!(simulator.register(20), 0xffffffff80000000);
+    }
+
+    #[test]
+    fn test_memory() {
+        let simulator = run("test_asm_files/simulator/memory.s", Xlen::Rv32);
+        let register = |number: usize| simulator.register(number) as u32;
+
+        assert_eq!(register(A0), 0xffffffff);
+        assert_eq!(register(A1), 0xff);
+        assert_eq!(register(A2), 0xffff8000);
+        assert_eq!(register(A3), 0x8000);
+        assert_eq!(register(A4), 0x12345678);
+        assert_eq!(register(A5), 0x800000ff);
+        assert_eq!(register(A6), 13);
+        assert_eq!(simulator.instructions_retired(), 14);
+    }
+
+    #[test]
+    fn test_rv64() {
+        let simulator = run("test_asm_files/simulator/rv64.s", Xlen::Rv64);
+
+        assert_eq!(simulator.register(A0), 0xffffffff80000000);
+        assert_eq!(simulator.register(A1), 0xfffffffffffffffe);
+        assert_eq!(simulator.register(A2), 0);
+        assert_eq!(simulator.register(A3), 0x7fffffff);
+        assert_eq!(simulator.register(A4), 0xfffffffff8000000);
+        assert_eq!(simulator.register(A5), 0x8000000);
+        assert_eq!(simulator.register(A6), 0x7fffffff00000000);
+        assert_eq!(simulator.register(A7), 0x7fffffff);
+        assert_eq!(simulator.register(S0), 0xffffffff80000001);
+        assert_eq!(simulator.register(S1), 0xffffffff80000000);
+    }
+
+    #[test]
+    fn test_stops() {
+        // An endless loop runs into the instruction limit
+        let mut simulator = Simulator::new(Xlen::Rv32);
+        simulator.load(0, &0x0000006fu32.to_le_bytes());
+        assert_eq!(simulator.run(100), Ok(Stop::Limit));
+        assert_eq!(simulator.instructions_retired(), 100);
+
+        // ecall stops after the instruction, ebreak on it
+        let mut simulator = Simulator::new(Xlen::Rv32);
+        simulator.load(0, &[0x73, 0, 0, 0, 0x73, 0, 0x10, 0]);
+        assert_eq!(simulator.run(100), Ok(Stop::Ecall));
+        assert_eq!(simulator.pc(), 4);
+        assert_eq!(simulator.run(100), Ok(Stop::Ebreak));
+        assert_eq!(simulator.pc(), 4);
+
+        // The word variants don't exist on RV32, and zeros aren't an instruction
+        let mut simulator = Simulator::new(Xlen::Rv32);
+        simulator.load(0, &0x0015051bu32.to_le_bytes());
+        assert_eq!(simulator.run(100), Err(SimulatorError::IllegalInstruction(0, 0x0015051b)));
+
+        let mut simulator = Simulator::new(Xlen::Rv32);
+        simulator.set_pc(0x100);
+        assert_eq!(simulator.step(), Err(SimulatorError::IllegalInstruction(0x100, 0)));
+
+        let mut simulator = Simulator::new(Xlen::Rv32);
+        simulator.set_pc(0x102);
+        assert_eq!(simulator.step(), Err(SimulatorError::MisalignedFetch(0x102)));
+    }
+
+    #[test]
+    fn test_dump_registers() {
+        let mut simulator = Simulator::new(Xlen::Rv32);
+        simulator.set_register(T0, 0xdeadbeef);
+        simulator.set_register(0, 5);
+        let dump = simulator.dump_registers();
+
+        assert!(dump.starts_with("pc   0x00000000\nzero 0x00000000  ra   0x00000000  sp   0x7ffffff0  gp   0x00000000\n"));
+        assert!(dump.contains("tp   0x00000000  t0   0xdeadbeef  t1   0x00000000"));
+        assert!(dump.ends_with("t6   0x00000000\n"));
+        assert_eq!(dump.lines().count(), 9);
+    }
+}
@@ -0,0 +1,82 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{AssemblerError, Segment};
+    use riscv_assembler::assembler::srec::{generate_srec, parse_srec, SrecFormat};
+
+    fn segment(address: u32, data: Vec<u8>) -> Segment {
+        Segment { address, data }
+    }
+
+    #[test]
+    fn test_srec_example() {
+        let srec = "\
+S00F000068656C6C6F202020202000003C
+S11F00007C0802A6900100049421FFF07C6C1B787C8C23783C6000003863000026
+S11F001C4BFFFFE5398000007D83637880010014382100107C0803A64E800020E9
+S111003848656C6C6F20776F726C642E0A0042
+S5030003F9
+S9030000FC
+";
+        let (segments, entry) = parse_srec(srec).unwrap();
+
+        assert_eq!(entry, Some(0));
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].address, 0);
+        assert_eq!(segments[0].data.len(), 70);
+        assert_eq!(&segments[0].data[56..], b"Hello world.\n\0");
+    }
+
+    #[test]
+    fn test_srec_records() {
+        let segments = vec![segment(0, vec![0x13, 0x05, 0x80, 0x00])];
+
+        assert_eq!(generate_srec(&segments, 0, SrecFormat::S19).unwrap(), "\
+S0030000FC
+S10700001305800060
+S5030001FB
+S9030000FC
+");
+        assert_eq!(generate_srec(&segments, 0x100, SrecFormat::S28).unwrap().lines().last(), Some("S804000100FA"));
+        assert_eq!(generate_srec(&segments, 0, SrecFormat::S37).unwrap().lines().nth(1), Some("S30900000000130580005E"));
+    }
+
+    #[test]
+    fn test_srec_round_trip() {
+        let segments = vec![
+            segment(0, (0..40).collect()),
+            segment(0xFFF8, (0..=255).collect()),
+            segment(0x0012_0000, vec![0x55; 3])
+        ];
+
+        assert_eq!(SrecFormat::fitting(&segments, 0), SrecFormat::S28);
+        assert_eq!(SrecFormat::fitting(&segments[..1], 0), SrecFormat::S19);
+        assert_eq!(SrecFormat::fitting(&segments[..1], 0x8000_0000), SrecFormat::S37);
+
+        for format in [SrecFormat::S28, SrecFormat::S37] {
+            let srec = generate_srec(&segments, 4, format).unwrap();
+            assert_eq!(parse_srec(&srec).unwrap(), (segments.clone(), Some(4)));
+        }
+    }
+
+    #[test]
+    fn test_srec_errors() {
+        // Addresses above 64 KiB don't fit S19
+        let segments = vec![segment(0x10000, vec![0])];
+        assert!(matches!(
+            generate_srec(&segments, 0, SrecFormat::S19),
+            Err(AssemblerError::LayoutError(_))
+        ));
+
+        let errors = [
+            "S10700001305800061",
+            "X10700001305800060",
+            "S1070000130580",
+            "S4030000FC",
+            "S10700001305800060\nS5030002FA"
+        ];
+
+        for srec in errors {
+            assert!(matches!(parse_srec(srec), Err(AssemblerError::ParseError(_))), "{}", srec);
+        }
+    }
+}
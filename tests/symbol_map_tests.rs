@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod tests {
+    use riscv_assembler::assembler::{Assembler, Binding};
+    use riscv_assembler::linker::Linker;
+
+    #[test]
+    fn test_map_file() {
+        let mut assembler = Assembler::new();
+        assembler.assemble("test_asm_files/listing/program.s").unwrap();
+
+        assert_eq!(assembler.symbol_map().to_map(), "\
+Linker script and memory map
+
+.text           0x00000000       0x28
+                0x00000000                main
+                0x00000020                helper
+
+.data           0x00000028       0x2c
+                0x00000028                message
+                0x00000050                value
+
+                0x00000003                COUNT = 0x3
+");
+    }
+
+    #[test]
+    fn test_nm() {
+        let mut assembler = Assembler::new();
+        let mut linker = Linker::new();
+        linker.add_source(&mut assembler, "test_asm_files/linker/main.s").unwrap();
+        linker.add_source(&mut assembler, "test_asm_files/linker/lib.s").unwrap();
+        let program = linker.link().unwrap();
+
+        assert_eq!(program.symbol_map().to_nm(), "\
+00000000 T _start
+00000050 B buffer
+0000004c D counter
+00000038 T exit
+00000040 R greeting
+00000030 T print
+");
+
+        // Local symbols are lowercase, weak ones W and undefined ones are left out
+        let mut assembler = Assembler::new();
+        assembler.assemble_object("test_asm_files/linker/weak.s").unwrap();
+        let symbol_map = assembler.symbol_map();
+
+        assert_eq!(symbol_map.get("exit").map(|s| s.binding), Some(Binding::Weak));
+        assert_eq!(symbol_map.get("handler"), None);
+        assert_eq!(symbol_map.to_nm(), "\
+00000000 T _start
+0000000c W exit
+00000010 d handlers
+");
+    }
+
+    #[test]
+    fn test_json() {
+        let mut assembler = Assembler::new();
+        assembler.set_base_address(0x8000_0000);
+        assembler.assemble("test_asm_files/listing/program.s").unwrap();
+        let json = assembler.symbol_map().to_json();
+
+        assert!(json.starts_with("{\n  \"sections\": [\n    {\"name\": \".text\", \"address\": 2147483648, \"size\": 40},\n"));
+        assert!(json.contains(
+            "\n    {\"name\": \"COUNT\", \"value\": 3, \"section\": null, \"kind\": \"constant\", \"binding\": \"local\"},\n"
+        ));
+        assert!(json.ends_with(
+            "\n    {\"name\": \"value\", \"value\": 2147483728, \"section\": \".data\", \"kind\": \"label\", \"binding\": \"local\"}\n  ]\n}\n"
+        ));
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use riscv_assembler::assembler::{Assembler, Xlen};
+    use riscv_assembler::simulator::{Environment, Simulator, SimulatorError, Stop, SyscallTable};
+
+    const HEAP: u64 = 0x10000;
+
+    // Run a program with the given input, returns the simulator, how it stopped and what it printed
+    fn run(path: &str, table: SyscallTable, input: &str) -> (Simulator, Result<Stop, SimulatorError>, String) {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_segments(path).unwrap();
+
+        let mut simulator = Simulator::new(Xlen::Rv32);
+        simulator.load_segments(&segments);
+        simulator.set_pc(assembler.entry_address().unwrap() as u64);
+
+        let mut environment = Environment::new(table, Cursor::new(input.as_bytes()), Vec::new(), HEAP);
+        let stop = environment.run(&mut simulator, 1000);
+        let output = String::from_utf8(environment.output().clone()).unwrap();
+        (simulator, stop, output)
+    }
+
+    #[test]
+    fn test_rars() {
+        let (simulator, stop, output) = run("test_asm_files/simulator/rars.s", SyscallTable::Rars, "5\n 37 \n");
+
+        assert_eq!(stop, Ok(Stop::Exit(42)));
+        assert_eq!(output, "sum = 42\n");
+
+        // sbrk hands out consecutive blocks from the start of the heap
+        assert_eq!(simulator.register(9), HEAP);
+        assert_eq!(simulator.register(18), 16);
+    }
+
+    #[test]
+    fn test_rars_invalid_input() {
+        let (_, stop, _) = run("test_asm_files/simulator/rars.s", SyscallTable::Rars, "five\n");
+        assert_eq!(stop, Err(SimulatorError::InvalidInput("five".to_string())));
+    }
+
+    #[test]
+    fn test_venus() {
+        let (_, stop, output) = run("test_asm_files/simulator/venus.s", SyscallTable::Venus, "");
+
+        assert_eq!(stop, Ok(Stop::Exit(0)));
+        assert_eq!(output, "-42");
+    }
+
+    #[test]
+    fn test_exit2() {
+        let (_, stop, _) = run("test_asm_files/simulator/exit2.s", SyscallTable::Venus, "");
+        assert_eq!(stop, Ok(Stop::Exit(7)));
+
+        let (_, stop, _) = run("test_asm_files/simulator/exit2.s", SyscallTable::Rars, "");
+        assert_eq!(stop, Err(SimulatorError::UnknownSyscall(0xc, 17)));
+    }
+
+    #[test]
+    fn test_linux() {
+        let (simulator, stop, output) = run("test_asm_files/simulator/linux.s", SyscallTable::Linux, "");
+
+        assert_eq!(stop, Ok(Stop::Exit(3)));
+        assert_eq!(output, "hello\n");
+
+        // write returns the number of bytes written, or -EBADF for files that aren't open
+        assert_eq!(simulator.register(8), 6);
+        assert_eq!(simulator.register(19) as i64, -9);
+
+        // brk(0) returns the break, a higher address moves it
+        assert_eq!(simulator.register(9), HEAP);
+        assert_eq!(simulator.register(18), HEAP + 64);
+    }
+
+    #[test]
+    fn test_unknown_syscall() {
+        // The Linux numbers aren't RARS calls
+        let (_, stop, output) = run("test_asm_files/simulator/linux.s", SyscallTable::Rars, "");
+
+        assert_eq!(stop, Err(SimulatorError::UnknownSyscall(0x14, 64)));
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_ebreak() {
+        // Programs that don't exit stop at their ebreak
+        let (simulator, stop, _) = run("test_asm_files/simulator/factorial.s", SyscallTable::Rars, "");
+        assert_eq!(stop, Ok(Stop::Ebreak));
+        assert_eq!(simulator.register(10), 3628800);
+    }
+}